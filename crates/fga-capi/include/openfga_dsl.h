@@ -0,0 +1,72 @@
+/* Generated by cbindgen from crates/fga-capi. Do not edit. */
+
+#ifndef OPENFGA_DSL_H
+#define OPENFGA_DSL_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ Why a call failed.
+ */
+typedef struct FgaError {
+  /*
+   Description of the error.
+   */
+  char *message;
+  /*
+   One-based line of the error, or 0 if it has no location.
+   */
+  size_t line;
+  /*
+   One-based column of the error, or 0 if it has no location.
+   */
+  size_t column;
+} FgaError;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Transforms the DSL model `input` into its JSON representation.
+
+ Returns the JSON, to be freed with `fga_string_free`, or NULL on
+ failure. Unless `error` is NULL, it is set to NULL on success and to
+ an error to be freed with `fga_error_free` on failure.
+
+ # Safety
+
+ `input` must be NULL or a NUL-terminated string, and `error` must
+ be NULL or valid for writes.
+ */
+char *fga_dsl_to_json(const char *input, struct FgaError **error);
+
+/*
+ Frees a string returned by this library. Does nothing for NULL.
+
+ # Safety
+
+ `s` must be NULL or a string returned by this library that has not
+ been freed yet.
+ */
+void fga_string_free(char *s);
+
+/*
+ Frees an error returned by this library. Does nothing for NULL.
+
+ # Safety
+
+ `error` must be NULL or an error returned by this library that has
+ not been freed yet.
+ */
+void fga_error_free(struct FgaError *error);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* OPENFGA_DSL_H */
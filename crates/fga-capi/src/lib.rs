@@ -0,0 +1,116 @@
+//! C API for the OpenFGA DSL parser.
+//!
+//! The header is `include/openfga_dsl.h`, generated with cbindgen.
+//! Strings and errors returned by these functions are owned by the
+//! caller and must be released with [fga_string_free] and
+//! [fga_error_free].
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use openfga_dsl_parser::json::JsonTransformer;
+use openfga_dsl_parser::Parser;
+
+/// Why a call failed.
+#[repr(C)]
+pub struct FgaError {
+    /// Description of the error.
+    pub message: *mut c_char,
+    /// One-based line of the error, or 0 if it has no location.
+    pub line: usize,
+    /// One-based column of the error, or 0 if it has no location.
+    pub column: usize,
+}
+
+/// Transforms the DSL model `input` into its JSON representation.
+///
+/// Returns the JSON, to be freed with `fga_string_free`, or NULL on
+/// failure. Unless `error` is NULL, it is set to NULL on success and to
+/// an error to be freed with `fga_error_free` on failure.
+///
+/// # Safety
+///
+/// `input` must be NULL or a NUL-terminated string, and `error` must
+/// be NULL or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fga_dsl_to_json(
+    input: *const c_char,
+    error: *mut *mut FgaError,
+) -> *mut c_char {
+    let result = if input.is_null() {
+        Err(("input is NULL".to_string(), 0, 0))
+    } else {
+        let input = CStr::from_ptr(input);
+        panic::catch_unwind(AssertUnwindSafe(|| dsl_to_json(input)))
+            .unwrap_or_else(|_| Err(("internal error".to_string(), 0, 0)))
+    };
+
+    let (json, err) = match result {
+        Ok(json) => (json.into_raw(), ptr::null_mut()),
+        Err((message, line, column)) => {
+            let err = Box::new(FgaError {
+                message: c_string(message).into_raw(),
+                line,
+                column,
+            });
+            (ptr::null_mut(), Box::into_raw(err))
+        }
+    };
+    if error.is_null() {
+        fga_error_free(err);
+    } else {
+        *error = err;
+    }
+    json
+}
+
+/// Frees a string returned by this library. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fga_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Frees an error returned by this library. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `error` must be NULL or an error returned by this library that has
+/// not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fga_error_free(error: *mut FgaError) {
+    if !error.is_null() {
+        let error = Box::from_raw(error);
+        fga_string_free(error.message);
+    }
+}
+
+fn dsl_to_json(input: &CStr) -> Result<CString, (String, usize, usize)> {
+    let input = input
+        .to_str()
+        .map_err(|e| (format!("input is not UTF-8: {e}"), 0, 0))?;
+    let mut parser = Parser::new(input);
+    match parser.parse_document() {
+        Ok(doc) => Ok(c_string(JsonTransformer::new(&doc).serialize())),
+        Err(e) => {
+            let (line, column) = parser.error_span().line_col(input);
+            Err((e.to_string(), line + 1, column + 1))
+        }
+    }
+}
+
+/// Converts `s`, dropping any interior NUL bytes.
+fn c_string(s: String) -> CString {
+    CString::new(s).unwrap_or_else(|e| {
+        let mut bytes = e.into_vec();
+        bytes.retain(|&b| b != 0);
+        CString::new(bytes).unwrap_or_default()
+    })
+}
@@ -0,0 +1,65 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "openfga_dsl.h"
+
+#define CHECK(cond)                                                   \
+    do {                                                              \
+        if (!(cond)) {                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,    \
+                    __LINE__, #cond);                                 \
+            return 1;                                                 \
+        }                                                             \
+    } while (0)
+
+static int transforms_valid_model(void) {
+    FgaError *error = (FgaError *)1;
+    char *json = fga_dsl_to_json("type user\n"
+                                 "type document\n"
+                                 "  relations\n"
+                                 "    define viewer as self\n",
+                                 &error);
+    CHECK(json != NULL);
+    CHECK(error == NULL);
+    CHECK(strstr(json, "\"type\":\"document\"") != NULL);
+    CHECK(strstr(json, "\"viewer\":{\"this\":{}}") != NULL);
+    fga_string_free(json);
+    return 0;
+}
+
+static int reports_parse_errors(void) {
+    FgaError *error = NULL;
+    char *json = fga_dsl_to_json("type document\n"
+                                 "  relations\n"
+                                 "    define viewer as",
+                                 &error);
+    CHECK(json == NULL);
+    CHECK(error != NULL);
+    CHECK(error->line == 3);
+    CHECK(error->column == 21);
+    CHECK(strlen(error->message) > 0);
+    fga_error_free(error);
+
+    json = fga_dsl_to_json(NULL, &error);
+    CHECK(json == NULL);
+    CHECK(error != NULL && error->line == 0);
+    fga_error_free(error);
+
+    /* the error may be ignored */
+    CHECK(fga_dsl_to_json("define", NULL) == NULL);
+    return 0;
+}
+
+static int frees_null(void) {
+    fga_string_free(NULL);
+    fga_error_free(NULL);
+    return 0;
+}
+
+int main(void) {
+    if (transforms_valid_model() || reports_parse_errors() || frees_null()) {
+        return 1;
+    }
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,81 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn crate_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Regenerate the header with `UPDATE_HEADER=1 cargo test -p fga-capi`.
+#[test]
+fn header_is_up_to_date() {
+    let config = cbindgen::Config::from_file(crate_dir().join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_crate(crate_dir())
+        .with_config(config)
+        .generate()
+        .unwrap()
+        .write(&mut generated);
+
+    let path = crate_dir().join("include/openfga_dsl.h");
+    if env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(&path, &generated).unwrap();
+    }
+    let header = fs::read(&path).unwrap();
+    assert!(
+        header == generated,
+        "{} is out of date, run with UPDATE_HEADER=1",
+        path.display()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn c_program() {
+    // `cargo test` only builds the rlib. The cdylib gets its own target
+    // directory so the build does not touch the running test's artifacts.
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi");
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo
+        .args(["build", "--lib", "--manifest-path"])
+        .arg(crate_dir().join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir);
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        cargo.arg("--release");
+        "release"
+    };
+    let status = cargo.status().unwrap();
+    assert!(status.success(), "failed to build the library");
+    let lib_dir = target_dir.join(profile);
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("c_api_test");
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = Command::new(cc)
+        .arg(crate_dir().join("tests/c/test.c"))
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(crate_dir().join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lopenfga_dsl")
+        .arg("-o")
+        .arg(&out)
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to compile the C test program");
+
+    let output = Command::new(&out).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, b"ok\n");
+}
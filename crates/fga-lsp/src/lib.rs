@@ -0,0 +1,274 @@
+//! Language server for the OpenFGA DSL, built on
+//! [Analysis](openfga_dsl_parser::analysis::Analysis).
+//!
+//! Documents are synced in full on every change. Positions are
+//! converted between LSP's UTF-16 line/character pairs and the
+//! character offsets used by the parser.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, GotoDefinition, HoverRequest, References, Rename, Request as RequestTrait,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ReferenceParams, RenameParams, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Uri, WorkspaceEdit,
+};
+use openfga_dsl_parser::analysis::Analysis;
+use openfga_dsl_parser::lexer::token::Span;
+use openfga_dsl_parser::lint::Severity;
+use serde_json::Value;
+
+/// The open documents and their analyses.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<Uri, Analysis>,
+}
+
+/// The features this server offers.
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(Default::default()),
+        ..Default::default()
+    }
+}
+
+/// Serves requests on `connection` until the client shuts down.
+pub fn run(connection: &Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let capabilities = serde_json::to_value(capabilities()).unwrap_or_default();
+    connection.initialize(capabilities)?;
+
+    let mut server = Server::default();
+    for message in &connection.receiver {
+        if let Message::Request(req) = &message {
+            if connection.handle_shutdown(req)? {
+                return Ok(());
+            }
+        }
+        for reply in server.handle(message) {
+            connection.sender.send(reply)?;
+        }
+    }
+    Ok(())
+}
+
+impl Server {
+    /// Handles one message from the client, returning the messages to
+    /// send back.
+    pub fn handle(&mut self, message: Message) -> Vec<Message> {
+        match message {
+            Message::Request(req) => vec![Message::Response(self.request(req))],
+            Message::Notification(not) => self.notification(not).into_iter().collect(),
+            Message::Response(_) => Vec::new(),
+        }
+    }
+
+    fn request(&self, req: Request) -> Response {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            HoverRequest::METHOD => params(req).map(|p| to_value(self.hover(p))),
+            GotoDefinition::METHOD => params(req).map(|p| to_value(self.definition(p))),
+            References::METHOD => params(req).map(|p| to_value(self.references(p))),
+            Rename::METHOD => params(req).map(|p| to_value(self.rename(p))),
+            Completion::METHOD => params(req).map(|p| to_value(self.completion(p))),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported method '{method}'"),
+                )
+            }
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(message) => Response::new_err(id, ErrorCode::InvalidParams as i32, message),
+        }
+    }
+
+    fn notification(&mut self, not: Notification) -> Option<Message> {
+        let (uri, text) = match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let p: DidOpenTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                (p.text_document.uri, p.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let mut p: DidChangeTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                (p.text_document.uri, p.content_changes.pop()?.text)
+            }
+            DidCloseTextDocument::METHOD => {
+                let p: DidCloseTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                self.documents.remove(&p.text_document.uri);
+                return Some(publish(p.text_document.uri, Vec::new()));
+            }
+            _ => return None,
+        };
+
+        let analysis = Analysis::new(&text);
+        let diagnostics = analysis
+            .diagnostics()
+            .iter()
+            .filter(|d| d.severity != Severity::Allow)
+            .map(|d| Diagnostic {
+                range: range(&text, d.span),
+                severity: Some(match d.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    _ => DiagnosticSeverity::WARNING,
+                }),
+                source: Some("openfga".into()),
+                code: Some(lsp_types::NumberOrString::String(d.source.clone())),
+                message: d.message.clone(),
+                ..Default::default()
+            })
+            .collect();
+        self.documents.insert(uri.clone(), analysis);
+        Some(publish(uri, diagnostics))
+    }
+
+    /// The analysis of an open document and the offset of `position`.
+    fn locate(&self, uri: &Uri, position: Position) -> Option<(&Analysis, usize)> {
+        let analysis = self.documents.get(uri)?;
+        Some((analysis, offset(analysis.input(), position)))
+    }
+
+    fn hover(&self, p: HoverParams) -> Option<Hover> {
+        let doc = p.text_document_position_params;
+        let (analysis, offset) = self.locate(&doc.text_document.uri, doc.position)?;
+        let hover = analysis.hover(offset)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```\n{}\n```", hover.text),
+            }),
+            range: Some(range(analysis.input(), hover.span)),
+        })
+    }
+
+    fn definition(&self, p: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let doc = p.text_document_position_params;
+        let uri = &doc.text_document.uri;
+        let (analysis, offset) = self.locate(uri, doc.position)?;
+        let locations = locations(uri, analysis, analysis.definition(offset));
+        (!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations))
+    }
+
+    fn references(&self, p: ReferenceParams) -> Option<Vec<Location>> {
+        let doc = p.text_document_position;
+        let uri = &doc.text_document.uri;
+        let (analysis, offset) = self.locate(uri, doc.position)?;
+        let spans = analysis.references(offset, p.context.include_declaration);
+        Some(locations(uri, analysis, spans))
+    }
+
+    fn rename(&self, p: RenameParams) -> Option<WorkspaceEdit> {
+        let doc = p.text_document_position;
+        let uri = &doc.text_document.uri;
+        let (analysis, offset) = self.locate(uri, doc.position)?;
+        let edits = analysis
+            .rename(offset, &p.new_name)?
+            .into_iter()
+            .map(|(span, new_text)| TextEdit {
+                range: range(analysis.input(), span),
+                new_text,
+            })
+            .collect();
+        Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        })
+    }
+
+    fn completion(&self, p: CompletionParams) -> Option<CompletionResponse> {
+        let doc = p.text_document_position;
+        let (analysis, offset) = self.locate(&doc.text_document.uri, doc.position)?;
+        let items = analysis
+            .completions(offset)
+            .into_iter()
+            .map(|c| CompletionItem {
+                label: c.label,
+                detail: Some(c.detail),
+                kind: Some(CompletionItemKind::FIELD),
+                ..Default::default()
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+fn params<P: serde::de::DeserializeOwned>(req: Request) -> Result<P, String> {
+    serde_json::from_value(req.params).map_err(|e| e.to_string())
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn publish(uri: Uri, diagnostics: Vec<Diagnostic>) -> Message {
+    let params = PublishDiagnosticsParams {
+        uri,
+        diagnostics,
+        version: None,
+    };
+    Message::Notification(Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        params,
+    ))
+}
+
+fn locations(uri: &Uri, analysis: &Analysis, spans: Vec<Span>) -> Vec<Location> {
+    spans
+        .into_iter()
+        .map(|span| Location::new(uri.clone(), range(analysis.input(), span)))
+        .collect()
+}
+
+fn range(text: &str, span: Span) -> Range {
+    Range::new(position(text, span.start), position(text, span.end))
+}
+
+/// The LSP position of a character offset.
+pub fn position(text: &str, offset: usize) -> Position {
+    let (mut line, mut character) = (0, 0);
+    for c in text.chars().take(offset) {
+        if c == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16() as u32;
+        }
+    }
+    Position::new(line, character)
+}
+
+/// The character offset of an LSP position, clamped to its line.
+pub fn offset(text: &str, position: Position) -> usize {
+    let mut offset = 0;
+    let mut line = 0;
+    let mut character = 0;
+    for c in text.chars() {
+        if line == position.line && (character >= position.character || c == '\n') {
+            break;
+        }
+        if c == '\n' {
+            line += 1;
+        } else if line == position.line {
+            character += c.len_utf16() as u32;
+        }
+        offset += 1;
+    }
+    offset
+}
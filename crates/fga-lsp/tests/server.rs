@@ -0,0 +1,164 @@
+use std::thread;
+
+use fga_lsp::{offset, position, Server};
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::Position;
+use serde_json::{json, Value};
+
+const URI: &str = "file:///model.fga";
+
+const MODEL: &str = "type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define owner as self
+    define viewer as owner or viewer from parent";
+
+fn open(server: &mut Server, text: &str) -> Value {
+    let replies = server.handle(Message::Notification(Notification::new(
+        "textDocument/didOpen".into(),
+        json!({
+            "textDocument": {"uri": URI, "languageId": "openfga", "version": 1, "text": text}
+        }),
+    )));
+    let [Message::Notification(not)] = replies.as_slice() else {
+        panic!("expected diagnostics, got {replies:?}");
+    };
+    assert_eq!(not.method, "textDocument/publishDiagnostics");
+    not.params.clone()
+}
+
+fn request(server: &mut Server, method: &str, params: Value) -> Value {
+    let replies = server.handle(Message::Request(Request::new(
+        RequestId::from(1),
+        method.into(),
+        params,
+    )));
+    let [Message::Response(response)] = replies.as_slice() else {
+        panic!("expected a response, got {replies:?}");
+    };
+    assert!(response.error.is_none(), "{:?}", response.error);
+    response.result.clone().unwrap()
+}
+
+fn at(line: u32, character: u32) -> Value {
+    json!({"textDocument": {"uri": URI}, "position": {"line": line, "character": character}})
+}
+
+#[test]
+fn publishes_diagnostics() {
+    let mut server = Server::default();
+    let params = open(
+        &mut server,
+        "type document\n  relations\n    define viewer as",
+    );
+    assert_eq!(
+        params["diagnostics"][0]["range"]["start"],
+        json!({"line": 2, "character": 20})
+    );
+    assert_eq!(params["diagnostics"][0]["severity"], 1);
+
+    let params = open(&mut server, MODEL);
+    assert_eq!(params["diagnostics"], json!([]));
+}
+
+#[test]
+fn navigates_and_renames() {
+    let mut server = Server::default();
+    open(&mut server, MODEL);
+
+    // `owner` in `viewer as owner`
+    let definition = request(&mut server, "textDocument/definition", at(7, 22));
+    assert_eq!(
+        definition[0]["range"],
+        json!({"start": {"line": 6, "character": 11}, "end": {"line": 6, "character": 16}})
+    );
+
+    // `viewer from parent` may refer to either viewer relation
+    let definition = request(&mut server, "textDocument/definition", at(7, 31));
+    assert_eq!(definition.as_array().unwrap().len(), 2);
+
+    let mut params = at(6, 12);
+    params["context"] = json!({"includeDeclaration": false});
+    let references = request(&mut server, "textDocument/references", params);
+    assert_eq!(
+        references[0]["range"]["start"],
+        json!({"line": 7, "character": 21})
+    );
+
+    let mut params = at(6, 12);
+    params["newName"] = json!("admin");
+    let edit = request(&mut server, "textDocument/rename", params);
+    assert_eq!(edit["changes"][URI].as_array().unwrap().len(), 2);
+
+    let hover = request(&mut server, "textDocument/hover", at(7, 12));
+    assert_eq!(
+        hover["contents"]["value"],
+        "```\ndocument#viewer\ndefine viewer as owner or viewer from parent\n```"
+    );
+
+    let completions = request(&mut server, "textDocument/completion", at(7, 21));
+    let labels: Vec<&str> = completions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels, vec!["parent", "owner", "viewer"]);
+}
+
+#[test]
+fn serves_a_connection() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || fga_lsp::run(&server).map_err(|e| e.to_string()));
+
+    client
+        .sender
+        .send(
+            Request::new(
+                RequestId::from(1),
+                "initialize".into(),
+                json!({"capabilities": {}}),
+            )
+            .into(),
+        )
+        .unwrap();
+    let Message::Response(response) = client.receiver.recv().unwrap() else {
+        panic!("expected the initialize response");
+    };
+    assert_eq!(
+        response.result.unwrap()["capabilities"]["renameProvider"],
+        true
+    );
+    client
+        .sender
+        .send(Notification::new("initialized".into(), json!({})).into())
+        .unwrap();
+
+    client
+        .sender
+        .send(Request::new(RequestId::from(2), "shutdown".into(), Value::Null).into())
+        .unwrap();
+    let Message::Response(response) = client.receiver.recv().unwrap() else {
+        panic!("expected the shutdown response");
+    };
+    assert_eq!(response.id, RequestId::from(2));
+    client
+        .sender
+        .send(Notification::new("exit".into(), Value::Null).into())
+        .unwrap();
+    assert_eq!(handle.join().unwrap(), Ok(()));
+}
+
+#[test]
+fn converts_positions() {
+    let text = "type 🦀\n  relations";
+    assert_eq!(position(text, 6), Position::new(0, 7));
+    assert_eq!(offset(text, Position::new(0, 7)), 6);
+    assert_eq!(offset(text, Position::new(1, 2)), 9);
+    // positions past the end of a line are clamped to it
+    assert_eq!(offset(text, Position::new(0, 40)), 6);
+    assert_eq!(offset(text, Position::new(5, 0)), text.chars().count());
+}
@@ -0,0 +1,269 @@
+//! The [fga_model!] macro, which checks an OpenFGA model at compile
+//! time and embeds it in the crate.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use openfga_dsl_parser::analysis::Analysis;
+use openfga_dsl_parser::json::JsonTransformer;
+use openfga_dsl_parser::lint::Severity;
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{parenthesized, Attribute, Ident, LitStr, Token, Visibility};
+
+/// Parses and validates a model at compile time, expanding to a module
+/// with the model's JSON and a constant for every type and relation.
+///
+/// The model is either written inline or read from a file whose path
+/// is relative to the crate's `Cargo.toml`:
+///
+/// ```ignore
+/// fga_macros::fga_model! {
+///     /// Our authorization model.
+///     pub mod authz = "type user
+/// type document
+///   relations
+///     define viewer as self";
+/// }
+///
+/// fga_macros::fga_model! {
+///     mod from_file = file("models/authz.fga");
+/// }
+///
+/// assert_eq!(authz::document::TYPE, "document");
+/// assert_eq!(authz::document::VIEWER, "viewer");
+/// let json: &'static str = authz::JSON;
+/// ```
+///
+/// Each type becomes a module named after it, holding its name as
+/// `TYPE` and each relation's name as an upper case constant. Parse
+/// and validation errors fail the build, pointing at the model.
+#[proc_macro]
+pub fn fga_model(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as ModelInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct ModelInput {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    source: Source,
+}
+
+enum Source {
+    Inline(LitStr),
+    /// A path relative to `CARGO_MANIFEST_DIR`.
+    File(LitStr),
+}
+
+impl Parse for ModelInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![mod]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let source = if input.peek(LitStr) {
+            Source::Inline(input.parse()?)
+        } else {
+            let keyword: Ident = input.parse()?;
+            if keyword != "file" {
+                return Err(syn::Error::new(
+                    keyword.span(),
+                    "expected a string literal or `file(\"path\")`",
+                ));
+            }
+            let content;
+            parenthesized!(content in input);
+            Source::File(content.parse()?)
+        };
+        input.parse::<Option<Token![;]>>()?;
+        Ok(Self {
+            attrs,
+            vis,
+            name,
+            source,
+        })
+    }
+}
+
+fn expand(input: ModelInput) -> syn::Result<TokenStream> {
+    let (model, origin, track) = match &input.source {
+        Source::Inline(lit) => (lit.value(), None, quote!()),
+        Source::File(lit) => {
+            let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+            let path = PathBuf::from(dir).join(lit.value());
+            let model = std::fs::read_to_string(&path).map_err(|e| {
+                syn::Error::new(lit.span(), format!("cannot read {}: {e}", path.display()))
+            })?;
+            // rebuild when the file changes
+            let path = path.to_string_lossy();
+            (
+                model,
+                Some(lit.value()),
+                quote!(
+                    const _: &[u8] = include_bytes!(#path);
+                ),
+            )
+        }
+    };
+    let span = match &input.source {
+        Source::Inline(lit) | Source::File(lit) => lit.span(),
+    };
+
+    let analysis = Analysis::new(&model);
+    let mut errors = analysis
+        .diagnostics()
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .map(|d| {
+            let (line, column) = d.span.line_col(&model);
+            let at = match &origin {
+                Some(path) => format!("{path}:{}:{}", line + 1, column + 1),
+                None => format!("line {}, column {}", line + 1, column + 1),
+            };
+            syn::Error::new(span, format!("{at}: {}", d.message))
+        });
+    if let Some(mut error) = errors.next() {
+        error.extend(errors);
+        return Err(error);
+    }
+    let Some(doc) = analysis.document() else {
+        return Err(syn::Error::new(span, "invalid model"));
+    };
+
+    let json = JsonTransformer::new(doc).serialize();
+    let mut modules = Vec::new();
+    let mut seen = HashMap::new();
+    for ty in &doc.types {
+        let module = ident(&ty.kind, false, span)?;
+        if let Some(other) = seen.insert(module.to_string(), &ty.kind) {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "types '{other}' and '{}' both map to module `{module}`",
+                    ty.kind
+                ),
+            ));
+        }
+        let type_doc = format!("The `{}` type.", ty.kind);
+        let kind = &ty.kind;
+        let mut seen = HashMap::new();
+        let relations = ty.relations.iter().map(|rel| {
+            let name = ident(&rel.kind, true, span)?;
+            if let Some(other) = seen.insert(name.to_string(), &rel.kind) {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "relations '{other}' and '{}' of type '{kind}' both map to constant `{name}`",
+                        rel.kind
+                    ),
+                ));
+            }
+            let rel_doc = format!("`{rel}`");
+            let kind = &rel.kind;
+            Ok(quote! {
+                #[doc = #rel_doc]
+                pub const #name: &str = #kind;
+            })
+        });
+        let relations = relations.collect::<syn::Result<Vec<_>>>()?;
+        modules.push(quote! {
+            #[doc = #type_doc]
+            pub mod #module {
+                /// The type name.
+                pub const TYPE: &str = #kind;
+                #(#relations)*
+            }
+        });
+    }
+
+    let ModelInput {
+        attrs, vis, name, ..
+    } = input;
+    Ok(quote! {
+        #(#attrs)*
+        #vis mod #name {
+            #track
+            /// The model as the JSON accepted by the OpenFGA API.
+            pub const JSON: &str = #json;
+            #(#modules)*
+        }
+    })
+}
+
+/// The identifier for a type or relation name: `-` becomes `_`,
+/// relations are upper case, and keywords are escaped.
+fn ident(name: &str, upper: bool, span: Span) -> syn::Result<Ident> {
+    let mut name = name.replace('-', "_");
+    if upper {
+        name = name.to_uppercase();
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    if name == "TYPE" {
+        return Ok(format_ident!("TYPE_", span = span));
+    }
+    match syn::parse_str::<Ident>(&name) {
+        Ok(_) => Ok(Ident::new(&name, span)),
+        Err(_) if matches!(name.as_str(), "self" | "super" | "crate" | "Self" | "_") => Err(
+            syn::Error::new(span, format!("'{name}' cannot be used as a module name")),
+        ),
+        Err(_) => Ok(Ident::new_raw(&name, span)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(input: &str) -> syn::Result<TokenStream> {
+        expand(syn::parse_str(input)?)
+    }
+
+    #[test]
+    fn reports_every_error_with_its_location() {
+        let err = expand_str(r#"mod model = file("tests/models/invalid.fga")"#).unwrap_err();
+        let messages: Vec<String> = err.into_iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("tests/models/invalid.fga:3:12: document#viewer"));
+        assert!(messages[1].starts_with("tests/models/invalid.fga:4:12: document#editor"));
+    }
+
+    #[test]
+    fn escapes_names() {
+        assert_eq!(
+            ident("can-view", true, Span::call_site()).unwrap(),
+            "CAN_VIEW"
+        );
+        assert_eq!(ident("type", true, Span::call_site()).unwrap(), "TYPE_");
+        assert_eq!(ident("2fa", false, Span::call_site()).unwrap(), "_2fa");
+        assert_eq!(ident("match", false, Span::call_site()).unwrap(), "r#match");
+        assert!(ident("self", false, Span::call_site()).is_err());
+
+        let err = expand_str(r#"mod model = "type a-b\ntype a_b""#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "types 'a-b' and 'a_b' both map to module `a_b`"
+        );
+
+        let model = "type user\ntype doc\n  relations\n    define can-view as self\n    define can_view as self";
+        let err = expand_str(&format!("mod model = {model:?}")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "relations 'can-view' and 'can_view' of type 'doc' both map to constant `CAN_VIEW`"
+        );
+
+        let model = "type doc\n  relations\n    define Type as self\n    define type_ as self";
+        let err = expand_str(&format!("mod model = {model:?}")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "relations 'Type' and 'type_' of type 'doc' both map to constant `TYPE_`"
+        );
+    }
+}
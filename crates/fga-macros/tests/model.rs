@@ -0,0 +1,44 @@
+use fga_macros::fga_model;
+use serde_json::Value;
+
+fga_model! {
+    /// The inline model.
+    pub mod inline = "type user
+type document
+  relations
+    define owner as self
+    define viewer as self or owner";
+}
+
+fga_model! {
+    mod from_file = file("tests/models/authz.fga");
+}
+
+#[test]
+fn embeds_inline_models() {
+    assert_eq!(inline::user::TYPE, "user");
+    assert_eq!(inline::document::TYPE, "document");
+    assert_eq!(inline::document::OWNER, "owner");
+    assert_eq!(inline::document::VIEWER, "viewer");
+
+    let json: Value = serde_json::from_str(inline::JSON).unwrap();
+    assert_eq!(json["type_definitions"][1]["type"], "document");
+    assert_eq!(
+        json["type_definitions"][1]["relations"]["owner"],
+        serde_json::json!({"this": {}})
+    );
+}
+
+#[test]
+fn embeds_model_files() {
+    assert_eq!(from_file::folder::CAN_VIEW, "can-view");
+    assert_eq!(from_file::document::PARENT, "parent");
+    let json: Value = serde_json::from_str(from_file::JSON).unwrap();
+    assert_eq!(json["type_definitions"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn compile_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,7 @@
+fga_macros::fga_model! {
+    mod model = "type document
+  relations
+    define viewer as viewer from parent";
+}
+
+fn main() {}
@@ -0,0 +1,5 @@
+fga_macros::fga_model! {
+    mod model = file("models/missing.fga");
+}
+
+fn main() {}
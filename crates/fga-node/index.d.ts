@@ -0,0 +1,16 @@
+export interface AuthorizationModel {
+  type_definitions: TypeDefinition[];
+}
+
+export interface TypeDefinition {
+  type: string;
+  relations: Record<string, Userset>;
+}
+
+export type Userset = Record<string, unknown>;
+
+/** Transforms a model written in the DSL into the API's JSON form. */
+export function friendlySyntaxToApiSyntax(config: string): AuthorizationModel;
+
+/** Transforms a model in the API's JSON form back into the DSL. */
+export function apiSyntaxToFriendlySyntax(config: AuthorizationModel): string;
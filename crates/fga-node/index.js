@@ -0,0 +1 @@
+module.exports = require('./openfga-dsl-parser.node');
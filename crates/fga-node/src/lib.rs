@@ -0,0 +1,32 @@
+//! Node.js bindings for the OpenFGA DSL parser.
+//!
+//! The exports match the schema 1.0 API of the `@openfga/syntax-transformer`
+//! package, so switching is a matter of changing the import.
+
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use openfga_dsl_parser::json::{self, JsonTransformer};
+use openfga_dsl_parser::Parser;
+use serde_json::Value;
+
+/// Transforms a model written in the DSL into the API's JSON form,
+/// `{ type_definitions: [...] }`.
+#[napi]
+pub fn friendly_syntax_to_api_syntax(config: String) -> Result<Value> {
+    let mut parser = Parser::new(&config);
+    let doc = parser.parse_document().map_err(|e| {
+        let (line, column) = parser.error_span().line_col(&config);
+        let message = format!("{e} at line {}, column {}", line + 1, column + 1);
+        Error::new(Status::InvalidArg, message)
+    })?;
+    serde_json::from_str(&JsonTransformer::new(&doc).serialize())
+        .map_err(|e| Error::new(Status::GenericFailure, e.to_string()))
+}
+
+/// Transforms a model in the API's JSON form back into the DSL.
+#[napi]
+pub fn api_syntax_to_friendly_syntax(config: Value) -> Result<String> {
+    json::deserialize(&config.to_string())
+        .map(|doc| doc.to_string())
+        .map_err(|e| Error::new(Status::InvalidArg, e.to_string()))
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn crate_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Builds the addon and runs `transformer.test.js` against it. Run with
+/// `cargo test -- --ignored` where node is installed.
+#[test]
+#[ignore = "requires node"]
+fn node_tests() {
+    Command::new("node")
+        .arg("--version")
+        .output()
+        .expect("node is not installed");
+
+    // the addon gets its own target directory so the build does not
+    // touch the running test's artifacts
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("node");
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo
+        .args(["build", "--lib", "--manifest-path"])
+        .arg(crate_dir().join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir);
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        cargo.arg("--release");
+        "release"
+    };
+    assert!(
+        cargo.status().unwrap().success(),
+        "failed to build the addon"
+    );
+
+    let lib = target_dir.join(profile).join(format!(
+        "{}fga_node{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    ));
+    let addon = target_dir.join("openfga-dsl-parser.node");
+    fs::copy(lib, &addon).unwrap();
+
+    let output = Command::new("node")
+        .arg("--test")
+        .arg(crate_dir().join("tests/transformer.test.js"))
+        .env("FGA_NODE_ADDON", &addon)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,47 @@
+// Run with `node --test` after building the addon, or through
+// `cargo test -p fga-node`, which points FGA_NODE_ADDON at a fresh build.
+const assert = require('node:assert');
+const test = require('node:test');
+
+const {
+  friendlySyntaxToApiSyntax,
+  apiSyntaxToFriendlySyntax,
+} = require(process.env.FGA_NODE_ADDON || '..');
+
+const dsl = `type user
+type document
+  relations
+    define owner as self
+    define viewer as self or owner
+`;
+
+test('transforms the DSL to the API syntax', () => {
+  assert.deepStrictEqual(friendlySyntaxToApiSyntax(dsl), {
+    type_definitions: [
+      { type: 'user', relations: {} },
+      {
+        type: 'document',
+        relations: {
+          owner: { this: {} },
+          viewer: {
+            union: {
+              child: [{ this: {} }, { computedUserset: { object: '', relation: 'owner' } }],
+            },
+          },
+        },
+      },
+    ],
+  });
+});
+
+test('transforms the API syntax to the DSL', () => {
+  assert.strictEqual(apiSyntaxToFriendlySyntax(friendlySyntaxToApiSyntax(dsl)), dsl);
+});
+
+test('throws on invalid input', () => {
+  assert.throws(
+    () => friendlySyntaxToApiSyntax('type document\n  relations\n    define viewer as'),
+    { message: 'received an unexpected EOF at line 3, column 21' },
+  );
+  assert.throws(() => apiSyntaxToFriendlySyntax({ type_definitions: 1 }), /type_definitions/);
+});
@@ -0,0 +1,276 @@
+//! Python bindings for the OpenFGA DSL parser, built with maturin into
+//! the `openfga_dsl` module.
+//!
+//! Models are exposed as read-only objects mirroring the
+//! [ast](openfga_dsl_parser::ast) types.
+
+use openfga_dsl_parser::ast::{self, AliasKind, Document};
+use openfga_dsl_parser::json::{self, JsonTransformer};
+use openfga_dsl_parser::lexer::token::Span;
+use openfga_dsl_parser::Parser;
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+
+create_exception!(
+    openfga_dsl,
+    ParserError,
+    PyValueError,
+    "The DSL could not be parsed. Has `line` and `column` (one-based), \
+     `start` and `end` character offsets, and the `expected` and `found` \
+     tokens when known."
+);
+create_exception!(
+    openfga_dsl,
+    JsonError,
+    PyValueError,
+    "The JSON is not a model the DSL can express. Has the `path` of the \
+     offending value, or the `line` and `column` of a syntax error."
+);
+
+/// A parsed model.
+#[pyclass(frozen, module = "openfga_dsl")]
+pub struct Model {
+    doc: Document,
+    /// The types, in order of definition.
+    #[pyo3(get)]
+    types: Py<PyTuple>,
+}
+
+/// A `type` block, mirroring `ast::Type`.
+#[pyclass(frozen, module = "openfga_dsl")]
+pub struct Type {
+    #[pyo3(get)]
+    kind: String,
+    #[pyo3(get)]
+    relations: Py<PyTuple>,
+    text: String,
+}
+
+/// A `define` line, mirroring `ast::Relation`.
+#[pyclass(frozen, module = "openfga_dsl")]
+pub struct Relation {
+    #[pyo3(get)]
+    kind: String,
+    #[pyo3(get)]
+    aliases: Py<PyTuple>,
+    text: String,
+}
+
+/// One alias of a relation: `self`, a relation, an intersection (`and`)
+/// or an exclusion (`but not`), optionally `from` a tupleset relation.
+#[pyclass(frozen, get_all, module = "openfga_dsl")]
+pub struct Alias {
+    /// `"self"`, `"relation"`, `"intersection"` or `"exclusion"`.
+    kind: &'static str,
+    relation: Option<String>,
+    parent: Option<String>,
+}
+
+#[pymethods]
+impl Model {
+    /// Finds the type with the given name.
+    fn get_type<'py>(&self, py: Python<'py>, kind: &str) -> PyResult<Option<Bound<'py, PyAny>>> {
+        match self.doc.types.iter().position(|ty| ty.kind == kind) {
+            Some(i) => self.types.bind(py).get_item(i).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The model in canonical DSL form.
+    fn __str__(&self) -> String {
+        self.doc.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        let kinds: Vec<&str> = self.doc.types.iter().map(|ty| ty.kind.as_str()).collect();
+        format!("Model(types={kinds:?})")
+    }
+}
+
+#[pymethods]
+impl Type {
+    /// Finds the relation with the given name.
+    fn get_relation<'py>(
+        &self,
+        py: Python<'py>,
+        kind: &str,
+    ) -> PyResult<Option<Bound<'py, PyAny>>> {
+        for rel in self.relations.bind(py).iter() {
+            if rel.cast::<Relation>()?.get().kind == kind {
+                return Ok(Some(rel));
+            }
+        }
+        Ok(None)
+    }
+
+    fn __str__(&self) -> String {
+        self.text.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Type({:?})", self.kind)
+    }
+}
+
+#[pymethods]
+impl Relation {
+    /// Whether users can be directly assigned, i.e. it is defined with
+    /// `self`.
+    #[getter]
+    fn is_assignable(&self, py: Python<'_>) -> PyResult<bool> {
+        for alias in self.aliases.bind(py).iter() {
+            if alias.cast::<Alias>()?.get().kind == "self" {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn __str__(&self) -> String {
+        self.text.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Relation({:?})", self.kind)
+    }
+}
+
+#[pymethods]
+impl Alias {
+    fn __repr__(&self) -> String {
+        let mut repr = format!("Alias({:?}", self.kind);
+        if let Some(relation) = &self.relation {
+            repr.push_str(&format!(", relation={relation:?}"));
+        }
+        if let Some(parent) = &self.parent {
+            repr.push_str(&format!(", parent={parent:?}"));
+        }
+        repr + ")"
+    }
+}
+
+/// Parses a model written in the DSL.
+#[pyfunction]
+fn parse(py: Python<'_>, dsl: &str) -> PyResult<Model> {
+    let mut parser = Parser::new(dsl);
+    match parser.parse_document() {
+        Ok(doc) => model(py, doc),
+        Err(e) => Err(parser_error(py, dsl, &e, parser.error_span())?),
+    }
+}
+
+/// Transforms a model into the JSON accepted by the OpenFGA API.
+#[pyfunction]
+fn to_json(model: PyRef<'_, Model>) -> String {
+    JsonTransformer::new(&model.doc).serialize()
+}
+
+/// Reads a model from the JSON accepted by the OpenFGA API.
+#[pyfunction]
+fn from_json(py: Python<'_>, json: &str) -> PyResult<Model> {
+    match json::deserialize(json) {
+        Ok(doc) => model(py, doc),
+        Err(e) => Err(json_error(py, &e)?),
+    }
+}
+
+/// The `openfga_dsl` Python module.
+#[pymodule]
+pub mod openfga_dsl {
+    #[pymodule_export]
+    use super::{from_json, parse, to_json, Alias, JsonError, Model, ParserError, Relation, Type};
+}
+
+fn model(py: Python<'_>, doc: Document) -> PyResult<Model> {
+    let types = doc
+        .types
+        .iter()
+        .map(|ty| Py::new(py, py_type(py, ty)?))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(Model {
+        types: PyTuple::new(py, types)?.unbind(),
+        doc,
+    })
+}
+
+fn py_type(py: Python<'_>, ty: &ast::Type) -> PyResult<Type> {
+    let relations = ty
+        .relations
+        .iter()
+        .map(|rel| Py::new(py, relation(py, rel)?))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(Type {
+        kind: ty.kind.clone(),
+        relations: PyTuple::new(py, relations)?.unbind(),
+        text: ty.to_string(),
+    })
+}
+
+fn relation(py: Python<'_>, rel: &ast::Relation) -> PyResult<Relation> {
+    let aliases = rel
+        .aliases
+        .iter()
+        .map(|alias| {
+            let kind = match alias.kind {
+                AliasKind::This => "self",
+                AliasKind::Named(_) => "relation",
+                AliasKind::Intersection(_) => "intersection",
+                AliasKind::Negative(_) => "exclusion",
+            };
+            let alias = Alias {
+                kind,
+                relation: alias.kind.relation().map(String::from),
+                parent: alias.parent.clone(),
+            };
+            Py::new(py, alias)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(Relation {
+        kind: rel.kind.clone(),
+        aliases: PyTuple::new(py, aliases)?.unbind(),
+        text: rel.to_string(),
+    })
+}
+
+fn parser_error(
+    py: Python<'_>,
+    input: &str,
+    error: &openfga_dsl_parser::ParserError,
+    at: Span,
+) -> PyResult<PyErr> {
+    use openfga_dsl_parser::ParserError::*;
+    let (expected, found) = match error {
+        UnexpectedToken(expected, found) => {
+            (Some(format!("{expected:?}")), Some(format!("{found:?}")))
+        }
+        UnexpectedKeyword(found) => (None, Some(format!("{found:?}"))),
+        UnexpectedEOF => (None, None),
+        UnknownParamType(found) => (None, Some(found.clone())),
+    };
+    let (line, column) = at.line_col(input);
+
+    let err = ParserError::new_err(error.to_string());
+    let value = err.value(py);
+    value.setattr("line", line + 1)?;
+    value.setattr("column", column + 1)?;
+    value.setattr("start", at.start)?;
+    value.setattr("end", at.end)?;
+    value.setattr("expected", expected)?;
+    value.setattr("found", found)?;
+    Ok(err)
+}
+
+fn json_error(py: Python<'_>, error: &json::JsonError) -> PyResult<PyErr> {
+    let err = JsonError::new_err(error.to_string());
+    let value = err.value(py);
+    let (path, line, column) = match error {
+        json::JsonError::Syntax { line, column, .. } => (None, Some(*line), Some(*column)),
+        json::JsonError::Unsupported { path, .. } => (Some(path.clone()), None, None),
+    };
+    value.setattr("path", path)?;
+    value.setattr("line", line)?;
+    value.setattr("column", column)?;
+    Ok(err)
+}
@@ -0,0 +1,34 @@
+use std::ffi::CString;
+
+use fga_python::openfga_dsl;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Runs every `test_*` function of `test_openfga_dsl.py` in an embedded
+/// interpreter.
+#[test]
+fn python_tests() {
+    pyo3::append_to_inittab!(openfga_dsl);
+    Python::initialize();
+
+    let source = CString::new(include_str!("test_openfga_dsl.py")).unwrap();
+    Python::attach(|py| {
+        let globals = PyDict::new(py);
+        py.run(&source, Some(&globals), None).unwrap();
+
+        let mut failures = Vec::new();
+        let mut ran = 0;
+        for (name, test) in globals.iter() {
+            let name: String = name.extract().unwrap();
+            if !name.starts_with("test_") {
+                continue;
+            }
+            ran += 1;
+            if let Err(e) = test.call0() {
+                failures.push(format!("{name}: {e}"));
+            }
+        }
+        assert!(ran > 0);
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    });
+}
@@ -0,0 +1,73 @@
+"""Tests for the `openfga_dsl` module.
+
+Run with pytest after `maturin develop`, or through `cargo test -p fga-python`.
+"""
+
+import json
+import operator
+
+import openfga_dsl
+
+MODEL = """type user
+type document
+  relations
+    define owner as self
+    define viewer as self or owner or viewer from parent
+"""
+
+
+def raises(exception, f, *args):
+    try:
+        f(*args)
+    except exception as e:
+        return e
+    raise AssertionError(f"{f.__name__} did not raise {exception.__name__}")
+
+
+def test_parse():
+    model = openfga_dsl.parse(MODEL)
+    assert [ty.kind for ty in model.types] == ["user", "document"]
+    document = model.get_type("document")
+    assert document.relations[0].is_assignable
+    viewer = document.get_relation("viewer")
+    assert str(viewer) == "define viewer as self or owner or viewer from parent"
+    assert [a.kind for a in viewer.aliases] == ["self", "relation", "relation"]
+    assert viewer.aliases[2].relation == "viewer"
+    assert viewer.aliases[2].parent == "parent"
+    assert model.get_type("folder") is None
+    assert str(model) == MODEL
+
+
+def test_read_only():
+    model = openfga_dsl.parse(MODEL)
+    raises(AttributeError, setattr, model.types[0], "kind", "group")
+    raises(TypeError, operator.setitem, model.types, 0, None)
+
+
+def test_parser_errors():
+    error = raises(
+        openfga_dsl.ParserError,
+        openfga_dsl.parse,
+        "type document\n  relations\n    define viewer as",
+    )
+    assert isinstance(error, ValueError)
+    assert (error.line, error.column) == (3, 21)
+    assert str(error) == "received an unexpected EOF"
+    assert error.expected is None
+
+    error = raises(openfga_dsl.ParserError, openfga_dsl.parse, "relations")
+    assert (error.expected, error.found) == ("Type", "Relations")
+    assert (error.start, error.end) == (0, 9)
+
+
+def test_json_round_trip():
+    model = openfga_dsl.parse(MODEL)
+    data = json.loads(openfga_dsl.to_json(model))
+    assert data["type_definitions"][1]["relations"]["owner"] == {"this": {}}
+    back = openfga_dsl.from_json(json.dumps(data))
+    assert str(back) == MODEL
+
+    error = raises(openfga_dsl.JsonError, openfga_dsl.from_json, '{"type_definitions": 1}')
+    assert error.path == "type_definitions"
+    error = raises(openfga_dsl.JsonError, openfga_dsl.from_json, "{")
+    assert error.line == 1
@@ -0,0 +1,4 @@
+//! WebAssembly module of the OpenFGA DSL parser, built from the parser's
+//! `wasm` feature so the parser itself stays a plain Rust library.
+
+pub use openfga_dsl_parser::wasm::*;
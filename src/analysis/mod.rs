@@ -0,0 +1,578 @@
+use std::fmt::Write;
+
+use crate::ast::{Document, SourceMap};
+use crate::condition::check_document;
+use crate::lexer::token::{Span, TokenKind};
+use crate::lexer::Lexer;
+use crate::lint::{Linter, Severity};
+use crate::validate::{DirectlyRelated, Validator};
+use crate::Parser;
+
+/// Editor features over a model: diagnostics, hover, navigation,
+/// rename and completion. Offsets and spans are character offsets
+/// into the input, as everywhere else in this crate.
+///
+/// ```
+/// use openfga_dsl_parser::analysis::Analysis;
+///
+/// let input = "type document
+///   relations
+///     define owner as self
+///     define viewer as owner";
+/// let analysis = Analysis::new(input);
+/// let use_of_owner = input.rfind("owner").unwrap();
+/// let definition = analysis.definition(use_of_owner);
+/// assert_eq!(definition[0].start, input.find("owner").unwrap());
+/// ```
+pub struct Analysis {
+    input: String,
+    doc: Option<Document>,
+    source: SourceMap,
+    related: Option<DirectlyRelated>,
+    diagnostics: Vec<Diagnostic>,
+    occurrences: Vec<Occurrence>,
+}
+
+/// A type or relation of the model.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Symbol {
+    Type(String),
+    /// A type and one of its relations.
+    Relation(String, String),
+}
+
+/// A problem found by the parser, the validator, the condition type
+/// checker or the linter.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    pub message: String,
+    /// `parser`, `validate`, `condition` or the name of a lint rule.
+    pub source: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Hover {
+    /// The name hovered over.
+    pub span: Span,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Completion {
+    pub label: String,
+    /// What the completed name refers to, e.g. `relation of document`.
+    pub detail: String,
+}
+
+/// A name in the input and the symbols it may refer to. The computed
+/// relation of a `from` alias refers to a relation on every type the
+/// tupleset may relate to.
+#[derive(Debug, Clone)]
+struct Occurrence {
+    span: Span,
+    symbols: Vec<Symbol>,
+    declaration: bool,
+}
+
+impl Analysis {
+    pub fn new(input: &str) -> Self {
+        Self::build(input, None)
+    }
+
+    /// Resolves `from` aliases with `related` instead of every type
+    /// defining the computed relation.
+    pub fn with_related_types(input: &str, related: DirectlyRelated) -> Self {
+        Self::build(input, Some(related))
+    }
+
+    fn build(input: &str, related: Option<DirectlyRelated>) -> Self {
+        let mut parser = Parser::new(input);
+        let mut analysis = Self {
+            input: input.to_string(),
+            doc: None,
+            source: SourceMap::default(),
+            related,
+            diagnostics: Vec::new(),
+            occurrences: Vec::new(),
+        };
+        match parser.parse_document() {
+            Ok(doc) => {
+                analysis.source = parser.source_map().clone();
+                analysis.doc = Some(doc);
+                analysis.check();
+                analysis.index();
+            }
+            Err(e) => analysis.diagnostics.push(Diagnostic {
+                span: parser.error_span(),
+                severity: Severity::Error,
+                message: e.to_string(),
+                source: "parser".into(),
+            }),
+        }
+        analysis
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// The parsed document, if the input parsed.
+    pub fn document(&self) -> Option<&Document> {
+        self.doc.as_ref()
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn check(&mut self) {
+        let Some(doc) = &self.doc else {
+            return;
+        };
+        let mut validator = Validator::new(doc);
+        if let Some(related) = &self.related {
+            validator = validator.with_related_types(related);
+        }
+        for error in validator.validate() {
+            let span = self
+                .relation_source(error.ty(), error.relation())
+                .unwrap_or_default();
+            self.diagnostics.push(Diagnostic {
+                span,
+                severity: Severity::Error,
+                message: error.to_string(),
+                source: "validate".into(),
+            });
+        }
+        for error in check_document(doc, &self.source) {
+            self.diagnostics.push(Diagnostic {
+                span: error.span().unwrap_or_default(),
+                severity: Severity::Error,
+                message: error.to_string(),
+                source: "condition".into(),
+            });
+        }
+        for lint in Linter::new(&self.input, doc, &self.source).lint() {
+            self.diagnostics.push(Diagnostic {
+                span: lint.span,
+                severity: lint.severity,
+                message: lint.message,
+                source: lint.rule.name().into(),
+            });
+        }
+    }
+
+    fn relation_source(&self, ty: &str, relation: &str) -> Option<Span> {
+        let doc = self.doc.as_ref()?;
+        let t = doc.types.iter().position(|t| t.kind == ty)?;
+        let r = doc.types[t]
+            .relations
+            .iter()
+            .position(|r| r.kind == relation)?;
+        Some(self.source.types.get(t)?.relations.get(r)?.name)
+    }
+
+    fn index(&mut self) {
+        let Some(doc) = &self.doc else {
+            return;
+        };
+        let mut occurrences = Vec::new();
+        for (ty, ty_source) in doc.types.iter().zip(&self.source.types) {
+            occurrences.push(Occurrence {
+                span: ty_source.name,
+                symbols: vec![Symbol::Type(ty.kind.clone())],
+                declaration: true,
+            });
+            for (rel, rel_source) in ty.relations.iter().zip(&ty_source.relations) {
+                occurrences.push(Occurrence {
+                    span: rel_source.name,
+                    symbols: vec![Symbol::Relation(ty.kind.clone(), rel.kind.clone())],
+                    declaration: true,
+                });
+                for (alias, alias_source) in rel.aliases.iter().zip(&rel_source.aliases) {
+                    let Some(name) = alias.kind.relation() else {
+                        continue;
+                    };
+                    let symbols = match &alias.parent {
+                        None => vec![Symbol::Relation(ty.kind.clone(), name.to_string())],
+                        Some(tupleset) => self
+                            .tupleset_targets(doc, &ty.kind, tupleset, name)
+                            .into_iter()
+                            .map(|target| Symbol::Relation(target, name.to_string()))
+                            .collect(),
+                    };
+                    occurrences.push(Occurrence {
+                        span: alias_source.name,
+                        symbols,
+                        declaration: false,
+                    });
+                    if let (Some(tupleset), Some(span)) = (&alias.parent, alias_source.parent) {
+                        occurrences.push(Occurrence {
+                            span,
+                            symbols: vec![Symbol::Relation(ty.kind.clone(), tupleset.clone())],
+                            declaration: false,
+                        });
+                    }
+                }
+            }
+        }
+        self.occurrences = occurrences;
+    }
+
+    /// The types whose `computed` relation `ty#tupleset` may lead to.
+    fn tupleset_targets(
+        &self,
+        doc: &Document,
+        ty: &str,
+        tupleset: &str,
+        computed: &str,
+    ) -> Vec<String> {
+        let defines = |name: &str| {
+            doc.get_type(name)
+                .is_some_and(|t| t.get_relation(computed).is_some())
+        };
+        let mut targets: Vec<String> = match self.related.as_ref().and_then(|r| r.get(ty, tupleset))
+        {
+            Some(related) => related
+                .iter()
+                .map(|r| r.type_name().to_string())
+                .filter(|name| defines(name))
+                .collect(),
+            None => doc
+                .types
+                .iter()
+                .filter(|t| defines(&t.kind))
+                .map(|t| t.kind.clone())
+                .collect(),
+        };
+        targets.dedup();
+        targets
+    }
+
+    fn occurrence_at(&self, offset: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|o| o.span.start <= offset && offset <= o.span.end)
+    }
+
+    /// The symbols named at `offset`.
+    pub fn symbols_at(&self, offset: usize) -> &[Symbol] {
+        self.occurrence_at(offset)
+            .map_or(&[], |o| o.symbols.as_slice())
+    }
+
+    /// Where the symbols named at `offset` are declared.
+    pub fn definition(&self, offset: usize) -> Vec<Span> {
+        let symbols = self.symbols_at(offset);
+        self.occurrences
+            .iter()
+            .filter(|o| o.declaration && o.symbols.iter().any(|s| symbols.contains(s)))
+            .map(|o| o.span)
+            .collect()
+    }
+
+    /// Every occurrence of the symbols named at `offset`, optionally
+    /// including their declarations.
+    pub fn references(&self, offset: usize, include_declaration: bool) -> Vec<Span> {
+        let symbols = self.symbols_at(offset);
+        self.occurrences
+            .iter()
+            .filter(|o| include_declaration || !o.declaration)
+            .filter(|o| o.symbols.iter().any(|s| symbols.contains(s)))
+            .map(|o| o.span)
+            .collect()
+    }
+
+    /// The edits renaming the symbol at `offset` to `name`, or `None`
+    /// when there is no symbol there, `name` is not a valid name or the
+    /// name is ambiguous.
+    pub fn rename(&self, offset: usize, name: &str) -> Option<Vec<(Span, String)>> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            && TokenKind::is_to_keyword(name).is_none();
+        let [symbol] = self.symbols_at(offset) else {
+            return None;
+        };
+        if !valid {
+            return None;
+        }
+        let edits = self
+            .occurrences
+            .iter()
+            .filter(|o| o.symbols.contains(symbol))
+            .map(|o| (o.span, name.to_string()))
+            .collect();
+        Some(edits)
+    }
+
+    /// A description of the type or relation at `offset`; for relations
+    /// the rewrite they resolve to.
+    pub fn hover(&self, offset: usize) -> Option<Hover> {
+        let doc = self.doc.as_ref()?;
+        let occurrence = self.occurrence_at(offset)?;
+        let mut text = String::new();
+        for symbol in &occurrence.symbols {
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+            match symbol {
+                Symbol::Type(name) => {
+                    let ty = doc.get_type(name)?;
+                    let _ = write!(text, "type {name}");
+                    let relations: Vec<&str> =
+                        ty.relations.iter().map(|r| r.kind.as_str()).collect();
+                    if !relations.is_empty() {
+                        let _ = write!(text, "\nrelations: {}", relations.join(", "));
+                    }
+                }
+                Symbol::Relation(ty, name) => {
+                    let rel = doc.get_type(ty)?.get_relation(name)?;
+                    let _ = write!(text, "{ty}#{name}\n{rel}");
+                }
+            }
+        }
+        Some(Hover {
+            span: occurrence.span,
+            text,
+        })
+    }
+
+    /// The names that may be written at `offset`: relations of the
+    /// enclosing type after `as`, `or`, `but not` and `from`, and
+    /// relations of every type before `from`. Schema 1.0 has no type
+    /// references, so type names are not completed. Works on input that
+    /// does not parse, as while typing.
+    pub fn completions(&self, offset: usize) -> Vec<Completion> {
+        // relation names declared by each type, in order
+        let mut types: Vec<(String, Vec<String>)> = Vec::new();
+        let mut current = None;
+        let mut before = TokenKind::EOF;
+        let mut after = None;
+        let mut prev = TokenKind::EOF;
+        let mut lexer = Lexer::new(&self.input);
+        loop {
+            let token = lexer.next_token();
+            let (kind, span) = (token.kind(), lexer.span());
+            if kind == TokenKind::EOF {
+                break;
+            }
+            match (prev, kind) {
+                (TokenKind::Type, TokenKind::Text | TokenKind::Condition) => {
+                    types.push((token.literal().to_string(), Vec::new()))
+                }
+                (TokenKind::Define, TokenKind::Text | TokenKind::Condition) => {
+                    if let Some((_, relations)) = types.last_mut() {
+                        relations.push(token.literal().to_string());
+                    }
+                }
+                _ => {}
+            }
+            prev = kind;
+
+            // the word being completed is neither before nor after
+            let completing = kind == TokenKind::Text && span.start <= offset && offset <= span.end;
+            if span.end <= offset && !completing {
+                before = kind;
+                current = types.len().checked_sub(1);
+            } else if span.start >= offset && !completing && after.is_none() {
+                after = Some(kind);
+            }
+        }
+
+        let relations_of = |(ty, relations): &(String, Vec<String>)| {
+            relations
+                .iter()
+                .map(|r| Completion {
+                    label: r.clone(),
+                    detail: format!("relation of {ty}"),
+                })
+                .collect::<Vec<_>>()
+        };
+        match (before, after, current) {
+            (
+                TokenKind::As | TokenKind::Or | TokenKind::And | TokenKind::Not,
+                Some(TokenKind::From),
+                _,
+            ) => {
+                let mut completions: Vec<Completion> = Vec::new();
+                for completion in types.iter().flat_map(relations_of) {
+                    if !completions.iter().any(|c| c.label == completion.label) {
+                        completions.push(completion);
+                    }
+                }
+                completions
+            }
+            (
+                TokenKind::As | TokenKind::Or | TokenKind::And | TokenKind::Not | TokenKind::From,
+                _,
+                Some(ty),
+            ) => relations_of(&types[ty]),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = "type user
+type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define owner as self
+    define viewer as owner or viewer from parent";
+
+    fn at(needle: &str, nth: usize) -> usize {
+        MODEL.match_indices(needle).nth(nth).unwrap().0
+    }
+
+    fn text(span: Span) -> &'static str {
+        &MODEL[span.start..span.end]
+    }
+
+    #[test]
+    fn reports_diagnostics() {
+        let analysis = Analysis::new("type document\n  relations\n    define viewer as");
+        assert_eq!(analysis.diagnostics().len(), 1);
+        assert_eq!(analysis.diagnostics()[0].source, "parser");
+        assert_eq!(analysis.diagnostics()[0].span, Span::new(46, 46));
+
+        let analysis = Analysis::new(
+            "type document
+  relations
+    define Viewer as self or viewer from parent",
+        );
+        let messages: Vec<(&str, Severity)> = analysis
+            .diagnostics()
+            .iter()
+            .map(|d| (d.source.as_str(), d.severity))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("validate", Severity::Error),
+                ("snake-case", Severity::Warning)
+            ]
+        );
+        assert_eq!(analysis.diagnostics()[0].span, Span::new(37, 43));
+
+        let input = "type user
+condition recent(t: timestamp) {
+  t > now
+}";
+        let analysis = Analysis::new(input);
+        assert_eq!(analysis.diagnostics().len(), 1);
+        assert_eq!(analysis.diagnostics()[0].source, "condition");
+        let at = input.find("now").unwrap();
+        assert_eq!(analysis.diagnostics()[0].span, Span::new(at, at + 3));
+    }
+
+    #[test]
+    fn navigates_relations() {
+        let analysis = Analysis::new(MODEL);
+        // `owner` in `viewer as owner`
+        let owner = at("owner", 1);
+        assert_eq!(
+            analysis.symbols_at(owner),
+            &[Symbol::Relation("document".into(), "owner".into())]
+        );
+        assert_eq!(
+            analysis.definition(owner),
+            vec![Span::new(at("owner", 0), at("owner", 0) + 5)]
+        );
+
+        // `viewer from parent` may refer to either viewer relation
+        let computed = at("viewer", 2);
+        let definitions = analysis.definition(computed);
+        assert_eq!(definitions.len(), 2);
+        assert!(definitions.iter().all(|span| text(*span) == "viewer"));
+
+        let parent = at("parent", 1);
+        assert_eq!(
+            analysis.definition(parent),
+            vec![Span::new(at("parent", 0), at("parent", 0) + 6)]
+        );
+
+        let folder_viewer = at("viewer", 0);
+        assert_eq!(analysis.references(folder_viewer, false).len(), 1);
+        assert_eq!(analysis.references(folder_viewer, true).len(), 2);
+    }
+
+    #[test]
+    fn resolves_from_with_related_types() {
+        let mut related = DirectlyRelated::new();
+        related.insert("document", "parent", ["folder"]);
+        let analysis = Analysis::with_related_types(MODEL, related);
+        assert_eq!(
+            analysis.symbols_at(at("viewer", 2)),
+            &[Symbol::Relation("folder".into(), "viewer".into())]
+        );
+    }
+
+    #[test]
+    fn renames_relations() {
+        let analysis = Analysis::new(MODEL);
+        let edits = analysis.rename(at("owner", 0), "admin").unwrap();
+        assert_eq!(
+            edits.iter().map(|(span, _)| span.start).collect::<Vec<_>>(),
+            vec![at("owner", 0), at("owner", 1)]
+        );
+        assert_eq!(analysis.rename(at("owner", 0), "not a name"), None);
+        assert_eq!(analysis.rename(at("owner", 0), "self"), None);
+        // ambiguous without related types
+        assert_eq!(analysis.rename(at("viewer", 2), "reader"), None);
+    }
+
+    #[test]
+    fn hovers_show_rewrites() {
+        let analysis = Analysis::new(MODEL);
+        let hover = analysis.hover(at("viewer", 1)).unwrap();
+        assert_eq!(
+            hover.text,
+            "document#viewer\ndefine viewer as owner or viewer from parent"
+        );
+        let hover = analysis.hover(at("folder", 0)).unwrap();
+        assert_eq!(hover.text, "type folder\nrelations: viewer");
+        assert_eq!(analysis.hover(at("relations", 0)), None);
+    }
+
+    #[test]
+    fn completes_relations_in_scope() {
+        let input = "type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define owner as self
+    define viewer as ow or viewer from parent
+    define editor as ";
+        let analysis = Analysis::new(input);
+        let labels = |offset| -> Vec<String> {
+            analysis
+                .completions(offset)
+                .into_iter()
+                .map(|c| c.label)
+                .collect()
+        };
+        let partial = input.find("ow or").unwrap() + 2;
+        assert_eq!(labels(partial), vec!["parent", "owner", "viewer", "editor"]);
+        let computed = input.rfind("viewer from").unwrap();
+        assert_eq!(
+            labels(computed),
+            vec!["viewer", "parent", "owner", "editor"]
+        );
+        assert_eq!(labels(input.rfind("define").unwrap()), Vec::<String>::new());
+        // the input does not parse while the rewrite is being typed
+        assert_eq!(analysis.diagnostics()[0].source, "parser");
+        assert_eq!(labels(input.len()).len(), 4);
+    }
+}
@@ -1,9 +1,15 @@
+use std::fmt::Display;
+
+use crate::condition::ParamType;
+use crate::lexer::token::Span;
+
 /// Root node for the DSL AST.
 /// Contains any number of [Type](crate::ast::Type)
-/// nodes.
+/// and [Condition](crate::ast::Condition) nodes.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Document {
     pub types: Vec<Type>,
+    pub conditions: Vec<Condition>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -15,18 +21,194 @@ pub struct Type {
 #[derive(Debug, PartialEq, Eq)]
 pub struct Relation {
     pub kind: String,
+    /// Users of any alias are related, as long as they also match every
+    /// `and` alias and no `but not` alias.
     pub aliases: Vec<Alias>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct Alias {
     pub kind: AliasKind,
     pub parent: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub enum AliasKind {
     This,
     Named(String),
+    /// A relation required with `and`, on top of the other aliases.
+    Intersection(String),
     Negative(String),
 }
+
+/// A CEL expression over typed parameters, deciding whether a
+/// conditional tuple applies.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub name: String,
+    pub params: Vec<Parameter>,
+    /// The expression between the braces, without surrounding whitespace.
+    pub expression: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Parameter {
+    pub name: String,
+    pub ty: ParamType,
+}
+
+/// Source locations of the nodes of a [Document](crate::ast::Document),
+/// recorded by the [Parser](crate::Parser). Entries are in the same
+/// order as the nodes they describe.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    pub types: Vec<TypeSource>,
+    pub conditions: Vec<ConditionSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeSource {
+    /// The whole type block, from `type` to the end of its last relation.
+    pub span: Span,
+    pub name: Span,
+    pub relations: Vec<RelationSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelationSource {
+    /// The whole definition, from `define` to the end of its last alias.
+    pub span: Span,
+    pub name: Span,
+    pub aliases: Vec<AliasSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasSource {
+    pub span: Span,
+    /// The `self` keyword or the referenced relation name.
+    pub name: Span,
+    pub parent: Option<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionSource {
+    /// The whole block, from `condition` to the closing brace.
+    pub span: Span,
+    pub name: Span,
+    /// Each parameter, from its name to the end of its type.
+    pub params: Vec<Span>,
+    /// The expression, so that spans within it can be located in the
+    /// input by adding its start.
+    pub expression: Span,
+}
+
+impl Document {
+    /// Finds the [Type](crate::ast::Type) with the given name.
+    pub fn get_type(&self, kind: &str) -> Option<&Type> {
+        self.types.iter().find(|ty| ty.kind == kind)
+    }
+
+    /// Finds the [Condition](crate::ast::Condition) with the given name.
+    pub fn get_condition(&self, name: &str) -> Option<&Condition> {
+        self.conditions.iter().find(|c| c.name == name)
+    }
+}
+
+impl Type {
+    /// Finds the [Relation](crate::ast::Relation) with the given name.
+    pub fn get_relation(&self, kind: &str) -> Option<&Relation> {
+        self.relations.iter().find(|rel| rel.kind == kind)
+    }
+}
+
+impl Relation {
+    /// Whether users can be directly assigned to this relation,
+    /// i.e. it is defined with `self`.
+    pub fn is_assignable(&self) -> bool {
+        self.aliases.iter().any(|a| a.kind == AliasKind::This)
+    }
+}
+
+impl AliasKind {
+    /// The relation this alias refers to, if any.
+    pub fn relation(&self) -> Option<&str> {
+        match self {
+            AliasKind::This => None,
+            AliasKind::Named(name) | AliasKind::Intersection(name) | AliasKind::Negative(name) => {
+                Some(name)
+            }
+        }
+    }
+}
+
+impl Display for Document {
+    /// Writes the document in canonical DSL form, one type after
+    /// another and then the conditions.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for ty in &self.types {
+            write!(f, "{ty}")?;
+        }
+        for condition in &self.conditions {
+            write!(f, "{condition}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Type {
+    /// Writes the type block, ending with a newline.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "type {}", self.kind)?;
+        if !self.relations.is_empty() {
+            writeln!(f, "  relations")?;
+        }
+        for rel in &self.relations {
+            writeln!(f, "    {rel}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Relation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "define {}", self.kind)?;
+        for (i, alias) in self.aliases.iter().enumerate() {
+            match (&alias.kind, i) {
+                (AliasKind::Negative(_), _) => write!(f, " but not ")?,
+                (AliasKind::Intersection(_), _) => write!(f, " and ")?,
+                (_, 0) => write!(f, " as ")?,
+                _ => write!(f, " or ")?,
+            }
+            write!(f, "{alias}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Alias {
+    /// Writes the alias without any leading `or`, `and` or `but not`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind.relation() {
+            Some(name) => write!(f, "{name}")?,
+            None => write!(f, "self")?,
+        }
+        if let Some(parent) = &self.parent {
+            write!(f, " from {parent}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Condition {
+    /// Writes the condition block, ending with a newline.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|p| format!("{}: {}", p.name, p.ty))
+            .collect();
+        writeln!(f, "condition {}({}) {{", self.name, params.join(", "))?;
+        writeln!(f, "  {}", self.expression)?;
+        writeln!(f, "}}")
+    }
+}
@@ -0,0 +1,273 @@
+//! Command-line interface to the OpenFGA DSL parser.
+//!
+//! Exits with 0 on success, 1 when the model has errors or is not
+//! formatted, and 2 on usage or I/O errors, or when formatting would
+//! drop comments.
+
+use std::fs;
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+use openfga_dsl_parser::ast::{Document, SourceMap};
+use openfga_dsl_parser::condition::check_document;
+use openfga_dsl_parser::json::JsonTransformer;
+use openfga_dsl_parser::lexer::token::Span;
+use openfga_dsl_parser::validate::{ValidationError, Validator};
+use openfga_dsl_parser::Parser;
+use serde_json::json;
+
+const USAGE: &str = "usage: fga-dsl <command> [options] [FILE]
+
+Reads the model from FILE, or stdin when FILE is missing or '-'.
+
+commands:
+  transform   print the model as OpenFGA JSON
+  validate    check the model for errors
+  format      print the model in canonical form
+
+options:
+  --error-format <human|json>   how to print errors, json prints one object per line
+  --check                       format: fail if the model is not formatted
+  --write                       format: rewrite FILE in place
+  -h, --help                    print this help";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Transform,
+    Validate,
+    Format,
+}
+
+struct Options {
+    command: Command,
+    json_errors: bool,
+    check: bool,
+    write: bool,
+    path: Option<String>,
+}
+
+/// An error in the model, with a one-based location.
+struct Diagnostic {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+enum Failure {
+    Io(String),
+    /// The command cannot run on this model, e.g. formatting one with
+    /// comments.
+    Refused(Diagnostic),
+    Model(Vec<Diagnostic>),
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Io(message)) => {
+            report(&options, "error", &message, None);
+            ExitCode::from(2)
+        }
+        Err(Failure::Refused(d)) => {
+            report(&options, "error", &d.message, Some((d.line, d.column)));
+            ExitCode::from(2)
+        }
+        Err(Failure::Model(diagnostics)) => {
+            for d in &diagnostics {
+                report(&options, "error", &d.message, Some((d.line, d.column)));
+            }
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut args = args.iter();
+    let command = match args.next().map(String::as_str) {
+        Some("transform") => Command::Transform,
+        Some("validate") => Command::Validate,
+        Some("format") => Command::Format,
+        Some(other) => return Err(format!("unknown command '{other}'")),
+        None => return Err("missing command".into()),
+    };
+    let mut options = Options {
+        command,
+        json_errors: false,
+        check: false,
+        write: false,
+        path: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--error-format" => match args.next().map(String::as_str) {
+                Some("human") => options.json_errors = false,
+                Some("json") => options.json_errors = true,
+                other => {
+                    return Err(format!(
+                        "--error-format expects 'human' or 'json', got '{}'",
+                        other.unwrap_or_default()
+                    ))
+                }
+            },
+            "--check" if command == Command::Format => options.check = true,
+            "--write" if command == Command::Format => options.write = true,
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{flag}'")),
+            path if options.path.is_none() => options.path = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument '{extra}'")),
+        }
+    }
+    if options.write && options.check {
+        return Err("--check and --write cannot be combined".into());
+    }
+    if options.write && matches!(options.path.as_deref(), None | Some("-")) {
+        return Err("--write needs a FILE".into());
+    }
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(), Failure> {
+    let input = read_input(options.path.as_deref())?;
+    let mut parser = Parser::new(&input);
+    let doc = parser.parse_document().map_err(|e| {
+        let span = parser.error_span();
+        Failure::Model(vec![diagnostic(&input, span, e.to_string())])
+    })?;
+
+    match options.command {
+        Command::Transform => println!("{}", JsonTransformer::new(&doc).serialize()),
+        Command::Validate => {
+            let source = parser.source_map();
+            let errors = Validator::new(&doc).validate();
+            let mut diagnostics: Vec<Diagnostic> = errors
+                .iter()
+                .map(|e| {
+                    let span = relation_span(&doc, source, e).unwrap_or_default();
+                    diagnostic(&input, span, e.to_string())
+                })
+                .collect();
+            diagnostics.extend(check_document(&doc, source).into_iter().map(|e| {
+                let span = e.span().unwrap_or_default();
+                diagnostic(&input, span, e.to_string())
+            }));
+            if !diagnostics.is_empty() {
+                return Err(Failure::Model(diagnostics));
+            }
+        }
+        Command::Format => {
+            // the document does not keep comments, so formatting would drop them
+            if let Some(comment) = comment_span(&input, parser.source_map()) {
+                let message = "cannot format a model with comments, they would be lost";
+                return Err(Failure::Refused(diagnostic(
+                    &input,
+                    comment,
+                    message.into(),
+                )));
+            }
+            let formatted = doc.to_string();
+            if options.check {
+                // line endings and trailing newlines are left to the editor
+                let normalized = input.replace("\r\n", "\n");
+                if formatted.trim_end() != normalized.trim_end() {
+                    return Err(Failure::Model(vec![Diagnostic {
+                        line: 1,
+                        column: 1,
+                        message: "model is not formatted".into(),
+                    }]));
+                }
+            } else if options.write {
+                let path = options.path.as_deref().unwrap_or_default();
+                fs::write(path, formatted)
+                    .map_err(|e| Failure::Io(format!("cannot write {path}: {e}")))?;
+            } else {
+                print!("{formatted}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_input(path: Option<&str>) -> Result<String, Failure> {
+    match path {
+        None | Some("-") => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| Failure::Io(format!("cannot read stdin: {e}")))?;
+            Ok(input)
+        }
+        Some(path) => {
+            fs::read_to_string(path).map_err(|e| Failure::Io(format!("cannot read {path}: {e}")))
+        }
+    }
+}
+
+/// The first `#` comment of the input, outside of condition expressions.
+fn comment_span(input: &str, source: &SourceMap) -> Option<Span> {
+    let in_condition = |i: usize| {
+        source
+            .conditions
+            .iter()
+            .any(|c| c.span.start <= i && i < c.span.end)
+    };
+    input
+        .chars()
+        .enumerate()
+        .find(|&(i, c)| c == '#' && !in_condition(i))
+        .map(|(i, _)| Span::new(i, i + 1))
+}
+
+fn diagnostic(input: &str, span: Span, message: String) -> Diagnostic {
+    let (line, column) = span.line_col(input);
+    Diagnostic {
+        line: line + 1,
+        column: column + 1,
+        message,
+    }
+}
+
+/// The name of the relation a validation error is about.
+fn relation_span(doc: &Document, source: &SourceMap, error: &ValidationError) -> Option<Span> {
+    let t = doc.types.iter().position(|t| t.kind == error.ty())?;
+    let r = doc.types[t]
+        .relations
+        .iter()
+        .position(|r| r.kind == error.relation())?;
+    Some(source.types.get(t)?.relations.get(r)?.name)
+}
+
+fn report(options: &Options, severity: &str, message: &str, location: Option<(usize, usize)>) {
+    let file = options.path.as_deref().filter(|p| *p != "-");
+    if options.json_errors {
+        let (line, column) = location.unzip();
+        let object = json!({
+            "file": file,
+            "line": line,
+            "column": column,
+            "severity": severity,
+            "message": message,
+        });
+        eprintln!("{object}");
+        return;
+    }
+    match location {
+        Some((line, column)) => {
+            let file = file.unwrap_or("<stdin>");
+            eprintln!("{file}:{line}:{column}: {severity}: {message}");
+        }
+        None => eprintln!("{severity}: {message}"),
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::analysis::{Analysis, Diagnostic};
+use crate::json::JsonTransformer;
+use crate::lint::Severity;
+
+/// Compiles the `.fga` model files of a directory into JSON from a
+/// build script.
+///
+/// ```no_run
+/// // in build.rs
+/// openfga_dsl_parser::build::Builder::new("models").run();
+/// ```
+///
+/// `models/authz.fga` is then available to the crate as:
+///
+/// ```ignore
+/// const AUTHZ: &str = include_str!(concat!(env!("OUT_DIR"), "/authz.json"));
+/// ```
+pub struct Builder {
+    dir: PathBuf,
+    out_dir: Option<PathBuf>,
+}
+
+/// A model written by a [Builder](crate::build::Builder).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Compiled {
+    pub source: PathBuf,
+    pub json: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// Some models have errors. Nothing was written.
+    Invalid(Vec<InvalidModel>),
+    /// Neither an output directory nor `OUT_DIR` is set.
+    NoOutDir,
+}
+
+/// A model file and its errors.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InvalidModel {
+    pub path: PathBuf,
+    pub input: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Builder {
+    /// Compiles the `.fga` files in `dir` and its subdirectories.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            out_dir: None,
+        }
+    }
+
+    /// Writes the JSON to `dir` instead of `OUT_DIR`.
+    pub fn with_out_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(dir.into());
+        self
+    }
+
+    /// Compiles every model from a build script: tells cargo to rerun
+    /// the script when a model changes, and on errors prints them and
+    /// fails the build.
+    pub fn run(&self) -> Vec<Compiled> {
+        println!("cargo:rerun-if-changed={}", self.dir.display());
+        if let Ok(sources) = self.sources() {
+            for source in sources {
+                println!("cargo:rerun-if-changed={}", source.display());
+            }
+        }
+        match self.compile() {
+            Ok(compiled) => compiled,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Parses and validates every model, then writes each one's JSON to
+    /// the same relative path in the output directory, with a `.json`
+    /// extension.
+    pub fn compile(&self) -> Result<Vec<Compiled>, BuildError> {
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or(BuildError::NoOutDir)?,
+        };
+
+        let mut models = Vec::new();
+        let mut invalid = Vec::new();
+        for source in self.sources()? {
+            let input = fs::read_to_string(&source).map_err(|e| io_error(&source, e))?;
+            let analysis = Analysis::new(&input);
+            let diagnostics: Vec<Diagnostic> = analysis
+                .diagnostics()
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .cloned()
+                .collect();
+            match analysis.document() {
+                Some(doc) if diagnostics.is_empty() => {
+                    models.push((source, JsonTransformer::new(doc).serialize()));
+                }
+                _ => invalid.push(InvalidModel {
+                    path: source,
+                    input,
+                    diagnostics,
+                }),
+            }
+        }
+        if !invalid.is_empty() {
+            return Err(BuildError::Invalid(invalid));
+        }
+
+        let mut compiled = Vec::new();
+        for (source, json) in models {
+            let relative = source.strip_prefix(&self.dir).unwrap_or(&source);
+            let path = out_dir.join(relative).with_extension("json");
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| io_error(parent, e))?;
+            }
+            fs::write(&path, json).map_err(|e| io_error(&path, e))?;
+            compiled.push(Compiled { source, json: path });
+        }
+        Ok(compiled)
+    }
+
+    /// The `.fga` files under the directory, in path order.
+    fn sources(&self) -> Result<Vec<PathBuf>, BuildError> {
+        let mut sources = Vec::new();
+        let mut dirs = vec![self.dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            let entries = fs::read_dir(&dir).map_err(|e| io_error(&dir, e))?;
+            for entry in entries {
+                let path = entry.map_err(|e| io_error(&dir, e))?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "fga") {
+                    sources.push(path);
+                }
+            }
+        }
+        sources.sort();
+        Ok(sources)
+    }
+}
+
+fn io_error(path: &Path, error: io::Error) -> BuildError {
+    BuildError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            BuildError::Invalid(models) => {
+                for model in models {
+                    write!(f, "{model}")?;
+                }
+                Ok(())
+            }
+            BuildError::NoOutDir => write!(f, "OUT_DIR is not set; call with_out_dir"),
+        }
+    }
+}
+
+impl Display for InvalidModel {
+    /// Renders each diagnostic with the line it points at.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for d in &self.diagnostics {
+            let (line, column) = d.span.line_col(&self.input);
+            let text = self.input.lines().nth(line).unwrap_or_default();
+            let number = (line + 1).to_string();
+            let pad = " ".repeat(number.len());
+            let rest = text.chars().count().saturating_sub(column);
+            let width = (d.span.end - d.span.start).min(rest).max(1);
+            writeln!(f, "error: {}", d.message)?;
+            writeln!(
+                f,
+                "{pad}--> {}:{}:{}",
+                self.path.display(),
+                line + 1,
+                column + 1
+            )?;
+            writeln!(f, "{pad} |")?;
+            writeln!(f, "{number} | {text}")?;
+            writeln!(f, "{pad} | {}{}", " ".repeat(column), "^".repeat(width))?;
+        }
+        Ok(())
+    }
+}
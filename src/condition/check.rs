@@ -0,0 +1,442 @@
+use std::collections::BTreeMap;
+
+use super::evaluate::{is_function, is_macro, op_name};
+use super::expr::{BinaryOp, Expr, ExprKind, UnaryOp};
+use super::{ConditionError, ParamType, Value};
+use crate::lexer::token::Span;
+
+/// Infers the types of an expression from the declared parameters,
+/// collecting every error instead of stopping at the first. Parts that
+/// fail to check get type `any` so one mistake is reported once.
+pub(super) struct TypeChecker<'c> {
+    params: &'c BTreeMap<String, ParamType>,
+    /// Variables bound by macros such as `exists`.
+    scope: Vec<(String, ParamType)>,
+    errors: Vec<ConditionError>,
+}
+
+impl<'c> TypeChecker<'c> {
+    pub(super) fn new(params: &'c BTreeMap<String, ParamType>) -> Self {
+        Self {
+            params,
+            scope: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Checks that `expr` is a well-typed boolean expression.
+    pub(super) fn check(mut self, expr: &Expr) -> Vec<ConditionError> {
+        let ty = self.ty(expr);
+        self.expect(ParamType::Bool, &ty, expr.span);
+        self.errors
+    }
+
+    fn ty(&mut self, expr: &Expr) -> ParamType {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Literal(value) => literal(value),
+            ExprKind::Ident(name) => self.ident(name, span),
+            ExprKind::List(items) => {
+                let types: Vec<ParamType> = items.iter().map(|i| self.ty(i)).collect();
+                ParamType::List(Box::new(common(&types)))
+            }
+            ExprKind::Map(entries) => {
+                let mut types = Vec::new();
+                for (key, value) in entries {
+                    let key_ty = self.ty(key);
+                    self.expect(ParamType::String, &key_ty, key.span);
+                    types.push(self.ty(value));
+                }
+                ParamType::Map(Box::new(common(&types)))
+            }
+            ExprKind::Unary(op, operand) => {
+                let ty = self.ty(operand);
+                let result = match (op, &ty) {
+                    (_, ParamType::Any) => Some(ParamType::Any),
+                    (UnaryOp::Not, ParamType::Bool) => Some(ParamType::Bool),
+                    (UnaryOp::Neg, ParamType::Int | ParamType::Double | ParamType::Duration) => {
+                        Some(ty.clone())
+                    }
+                    _ => None,
+                };
+                let name = match op {
+                    UnaryOp::Not => "!_",
+                    UnaryOp::Neg => "-_",
+                };
+                self.overload(result, name, &[ty], span)
+            }
+            ExprKind::Binary(BinaryOp::And | BinaryOp::Or, lhs, rhs) => {
+                for side in [lhs, rhs] {
+                    let ty = self.ty(side);
+                    self.expect(ParamType::Bool, &ty, side.span);
+                }
+                ParamType::Bool
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let (a, b) = (self.ty(lhs), self.ty(rhs));
+                let result = binary(*op, &a, &b);
+                self.overload(result, op_name(*op), &[a, b], span)
+            }
+            ExprKind::Ternary(cond, then, otherwise) => {
+                let cond_ty = self.ty(cond);
+                self.expect(ParamType::Bool, &cond_ty, cond.span);
+                let (a, b) = (self.ty(then), self.ty(otherwise));
+                let result = compatible(&a, &b).then(|| common(&[a.clone(), b.clone()]));
+                self.overload(result, "_?_:_", &[cond_ty, a, b], span)
+            }
+            ExprKind::Member(target, field) => {
+                let ty = self.ty(target);
+                let result = match &ty {
+                    ParamType::Any => Some(ParamType::Any),
+                    ParamType::Map(value) => Some((**value).clone()),
+                    _ => None,
+                };
+                self.overload(result, &format!(".{field}"), &[ty], span)
+            }
+            ExprKind::Index(target, index) => {
+                let (target, index) = (self.ty(target), self.ty(index));
+                let result = match (&target, &index) {
+                    (ParamType::Any, _) => Some(ParamType::Any),
+                    (ParamType::List(item), ParamType::Int | ParamType::Uint | ParamType::Any)
+                    | (ParamType::Map(item), ParamType::String | ParamType::Any) => {
+                        Some((**item).clone())
+                    }
+                    _ => None,
+                };
+                self.overload(result, "_[_]", &[target, index], span)
+            }
+            ExprKind::Call { target, name, args } => {
+                if let (Some(target), true) = (target, is_macro(name)) {
+                    return self.call_macro(target, name, args, span);
+                }
+                let types: Vec<ParamType> = target
+                    .iter()
+                    .map(|t| &**t)
+                    .chain(args)
+                    .map(|arg| self.ty(arg))
+                    .collect();
+                if !is_function(name) {
+                    self.errors
+                        .push(ConditionError::UndefinedFunction(name.clone(), span));
+                    return ParamType::Any;
+                }
+                let result = call(name, target.is_some(), &types);
+                let name = match target {
+                    Some(_) => format!(".{name}"),
+                    None => name.clone(),
+                };
+                self.overload(result, &name, &types, span)
+            }
+        }
+    }
+
+    fn ident(&mut self, name: &str, span: Span) -> ParamType {
+        if let Some((_, ty)) = self.scope.iter().rev().find(|(n, _)| n == name) {
+            return ty.clone();
+        }
+        match self.params.get(name) {
+            Some(ty) => ty.clone(),
+            None => {
+                self.errors
+                    .push(ConditionError::UndefinedIdentifier(name.to_string(), span));
+                ParamType::Any
+            }
+        }
+    }
+
+    fn call_macro(&mut self, target: &Expr, name: &str, args: &[Expr], span: Span) -> ParamType {
+        let range = self.ty(target);
+        let item = match &range {
+            ParamType::List(item) => Some((**item).clone()),
+            ParamType::Map(_) => Some(ParamType::String),
+            ParamType::Any => Some(ParamType::Any),
+            _ => None,
+        };
+        let (Some(item), [var, body]) = (item, args) else {
+            let mut types = vec![range];
+            types.extend(args.iter().map(|arg| self.ty(arg)));
+            return self.overload(None, &format!(".{name}"), &types, span);
+        };
+        let ExprKind::Ident(var) = &var.kind else {
+            self.errors.push(ConditionError::Syntax {
+                message: format!("{name} expects a variable name"),
+                span: var.span,
+            });
+            return ParamType::Any;
+        };
+
+        self.scope.push((var.clone(), item.clone()));
+        let body_ty = self.ty(body);
+        self.scope.pop();
+        match name {
+            "map" => ParamType::List(Box::new(body_ty)),
+            _ => {
+                self.expect(ParamType::Bool, &body_ty, body.span);
+                match name {
+                    "filter" => ParamType::List(Box::new(item)),
+                    _ => ParamType::Bool,
+                }
+            }
+        }
+    }
+
+    /// Records a mismatch unless `found` is `expected` or unknown.
+    fn expect(&mut self, expected: ParamType, found: &ParamType, span: Span) {
+        if !fits(found, &expected) {
+            self.errors.push(ConditionError::TypeMismatch {
+                expected,
+                found: found.clone(),
+                span,
+            });
+        }
+    }
+
+    /// The type of a resolved overload, recording an error if there
+    /// was none.
+    fn overload(
+        &mut self,
+        result: Option<ParamType>,
+        function: &str,
+        args: &[ParamType],
+        span: Span,
+    ) -> ParamType {
+        result.unwrap_or_else(|| {
+            let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            self.errors.push(ConditionError::NoSuchOverload {
+                function: function.to_string(),
+                args: args.join(", "),
+                span,
+            });
+            ParamType::Any
+        })
+    }
+}
+
+fn literal(value: &Value) -> ParamType {
+    match value {
+        Value::Bool(_) => ParamType::Bool,
+        Value::Int(_) => ParamType::Int,
+        Value::Uint(_) => ParamType::Uint,
+        Value::Double(_) => ParamType::Double,
+        Value::String(_) => ParamType::String,
+        Value::Duration(_) => ParamType::Duration,
+        Value::Timestamp(_) => ParamType::Timestamp,
+        Value::IpAddress(_) => ParamType::IpAddress,
+        Value::Null | Value::List(_) | Value::Map(_) => ParamType::Any,
+    }
+}
+
+fn is_number(ty: &ParamType) -> bool {
+    matches!(ty, ParamType::Int | ParamType::Uint | ParamType::Double)
+}
+
+/// Whether a value of type `ty` can be passed where `pattern` is
+/// expected, `any` on either side matching everything.
+fn fits(ty: &ParamType, pattern: &ParamType) -> bool {
+    match (ty, pattern) {
+        (ParamType::Any, _) | (_, ParamType::Any) => true,
+        (ParamType::List(a), ParamType::List(b)) | (ParamType::Map(a), ParamType::Map(b)) => {
+            fits(a, b)
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// Whether values of the two types can be compared for equality.
+fn compatible(a: &ParamType, b: &ParamType) -> bool {
+    fits(a, b) || is_number(a) && is_number(b)
+}
+
+/// The type shared by all of `types`, or `any` if they differ.
+fn common(types: &[ParamType]) -> ParamType {
+    let mut known = types.iter().filter(|ty| **ty != ParamType::Any);
+    match known.next() {
+        Some(first) if known.all(|ty| ty == first) => first.clone(),
+        _ => ParamType::Any,
+    }
+}
+
+fn binary(op: BinaryOp, a: &ParamType, b: &ParamType) -> Option<ParamType> {
+    use ParamType::*;
+
+    match op {
+        BinaryOp::Eq | BinaryOp::Ne => return compatible(a, b).then_some(Bool),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordered = matches!(a, Any | Bool | String | Duration | Timestamp) || is_number(a);
+            return (ordered && compatible(a, b)).then_some(Bool);
+        }
+        BinaryOp::In => {
+            return match b {
+                Any => Some(Bool),
+                List(item) => compatible(a, item).then_some(Bool),
+                Map(_) => fits(a, &String).then_some(Bool),
+                _ => None,
+            };
+        }
+        _ => {}
+    }
+
+    match (op, a, b) {
+        (BinaryOp::Add | BinaryOp::Sub, Timestamp, Duration)
+        | (BinaryOp::Add, Duration, Timestamp) => Some(Timestamp),
+        (BinaryOp::Sub, Timestamp, Timestamp) => Some(Duration),
+        (_, Any, Any) => Some(Any),
+        (BinaryOp::Add | BinaryOp::Sub, Duration, Duration) => Some(Duration),
+        (BinaryOp::Add, String, String) => Some(String),
+        (BinaryOp::Add, List(x), List(y)) => {
+            fits(x, y).then(|| List(Box::new(common(&[(**x).clone(), (**y).clone()]))))
+        }
+        (BinaryOp::Rem, Double, _) | (BinaryOp::Rem, _, Double) => None,
+        (_, ty, other) | (_, other, ty) if is_number(ty) && fits(other, ty) => Some(ty.clone()),
+        (BinaryOp::Add | BinaryOp::Sub, ty, Any) | (BinaryOp::Add, Any, ty)
+            if matches!(ty, String | Duration | Timestamp | List(_)) =>
+        {
+            Some(Any)
+        }
+        _ => None,
+    }
+}
+
+/// The result type of a function, given the types of its arguments;
+/// for methods the target is the first argument.
+fn call(name: &str, method: bool, args: &[ParamType]) -> Option<ParamType> {
+    use ParamType::*;
+
+    let any_list = || List(Box::new(Any));
+    let any_map = || Map(Box::new(Any));
+    let getters = [
+        "getFullYear",
+        "getMonth",
+        "getDate",
+        "getDayOfMonth",
+        "getDayOfYear",
+        "getDayOfWeek",
+    ];
+    let overloads: Vec<(Vec<ParamType>, ParamType)> = match (name, method) {
+        ("size", _) => vec![
+            (vec![String], Int),
+            (vec![any_list()], Int),
+            (vec![any_map()], Int),
+        ],
+        ("contains" | "startsWith" | "endsWith", true) | ("matches", _) => {
+            vec![(vec![String, String], Bool)]
+        }
+        ("in_cidr", true) => vec![(vec![IpAddress, String], Bool)],
+        ("timestamp", false) => vec![(vec![String], Timestamp), (vec![Timestamp], Timestamp)],
+        ("duration", false) => vec![(vec![String], Duration), (vec![Duration], Duration)],
+        ("ipaddress", false) => vec![(vec![String], IpAddress), (vec![IpAddress], IpAddress)],
+        ("int", false) => [Int, Uint, Double, String, Timestamp]
+            .into_iter()
+            .map(|ty| (vec![ty], Int))
+            .collect(),
+        ("uint", false) => [Uint, Int, Double, String]
+            .into_iter()
+            .map(|ty| (vec![ty], Uint))
+            .collect(),
+        ("double", false) => [Double, Int, Uint, String]
+            .into_iter()
+            .map(|ty| (vec![ty], Double))
+            .collect(),
+        ("string", false) => [
+            String, Bool, Int, Uint, Double, Duration, Timestamp, IpAddress,
+        ]
+        .into_iter()
+        .map(|ty| (vec![ty], String))
+        .collect(),
+        ("bool", false) => vec![(vec![Bool], Bool), (vec![String], Bool)],
+        (getter, true) if getters.contains(&getter) => vec![(vec![Timestamp], Int)],
+        ("getHours" | "getMinutes" | "getSeconds" | "getMilliseconds", true) => {
+            vec![(vec![Timestamp], Int), (vec![Duration], Int)]
+        }
+        _ => Vec::new(),
+    };
+
+    let results: Vec<ParamType> = overloads
+        .into_iter()
+        .filter(|(params, _)| {
+            params.len() == args.len() && args.iter().zip(params).all(|(a, p)| fits(a, p))
+        })
+        .map(|(_, result)| result)
+        .collect();
+    match results.split_first() {
+        Some((first, rest)) if rest.iter().all(|r| r == first) => Some(first.clone()),
+        Some(_) => Some(Any),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Condition;
+
+    fn check(expression: &str) -> Vec<String> {
+        let doc = crate::Parser::new(&format!(
+            "condition c(ip: ipaddress, request_ip: string, count: int, at: timestamp, \
+             allowed: list<string>, limits: map<uint>, extra: any) {{ {expression} }}"
+        ))
+        .parse_document()
+        .unwrap();
+        Condition::from_ast(&doc.conditions[0])
+            .unwrap()
+            .check()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_well_typed_expressions() {
+        let expressions = [
+            "ip.in_cidr('10.0.0.0/8') && request_ip.startsWith('10.')",
+            "count + 1 < 10 && count > 1u && count != 2.5",
+            "at + duration('1h') > timestamp('2024-01-01T00:00:00Z') && at.getHours() < 9",
+            "request_ip in allowed && limits[request_ip] > 0u && size(limits) == 2",
+            "allowed.exists(a, a.endsWith('eu')) && allowed.map(a, size(a)).all(n, n > 0)",
+            "extra.anything[0] == 'x' && extra",
+            "(count > 0 ? allowed : ['default']).filter(a, a != '').size() > 0",
+        ];
+        for expression in expressions {
+            assert_eq!(check(expression), Vec::<String>::new(), "{expression}");
+        }
+    }
+
+    #[test]
+    fn reports_undeclared_names() {
+        assert_eq!(
+            check("request.ip == '10.0.0.1' || ip.in_network('10.0.0.0/8')"),
+            vec![
+                "undeclared reference to 'request' at position 0",
+                "undeclared function 'in_network' at position 28",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_type_mismatches() {
+        assert_eq!(
+            check("count + 'a' == 1 && ip.in_cidr(count) && !count"),
+            vec![
+                "found no matching overload for '_+_' applied to (int, string) at position 0",
+                "found no matching overload for '.in_cidr' applied to (ipaddress, int) \
+                 at position 20",
+                "found no matching overload for '!_' applied to (int) at position 41",
+            ]
+        );
+        assert_eq!(
+            check("allowed.all(a, a) && (count ? 1 : 2) == 1"),
+            vec![
+                "expected bool, found string at position 15",
+                "expected bool, found int at position 22",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_non_boolean_results() {
+        assert_eq!(
+            check("count * 2"),
+            vec!["expected bool, found int at position 0"]
+        );
+        assert_eq!(check("limits[request_ip]").len(), 1);
+    }
+}
@@ -0,0 +1,553 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::expr::{BinaryOp, Expr, ExprKind, UnaryOp};
+use super::value::{
+    checked_duration, checked_timestamp, format_duration, format_timestamp, in_cidr,
+    parse_duration, parse_ip, parse_timestamp, TimeParts,
+};
+use super::{ConditionError, ConditionResult, ParamType, Value};
+use crate::lexer::token::Span;
+
+/// A value, or the parameters missing from the context to compute it.
+#[derive(Debug)]
+pub(super) enum Partial {
+    Known(Value),
+    Missing(BTreeSet<String>),
+}
+
+pub(super) struct Interpreter<'c> {
+    params: &'c BTreeMap<String, ParamType>,
+    context: &'c HashMap<String, Value>,
+    /// Variables bound by macros such as `exists`.
+    scope: Vec<(String, Value)>,
+}
+
+fn runtime(message: impl Into<String>, span: Span) -> ConditionError {
+    ConditionError::Runtime {
+        message: message.into(),
+        span,
+    }
+}
+
+fn no_overload(op: &str, args: &[&Value], span: Span) -> ConditionError {
+    let types: Vec<&str> = args.iter().map(|v| v.type_name()).collect();
+    ConditionError::NoSuchOverload {
+        function: op.to_string(),
+        args: types.join(", "),
+        span,
+    }
+}
+
+/// Evaluates each expression, returning the values once all are known.
+macro_rules! known {
+    ($interp:expr, $($expr:expr),+) => {{
+        let mut missing = BTreeSet::new();
+        let values = ($(
+            match $interp.eval($expr)? {
+                Partial::Known(value) => Some(value),
+                Partial::Missing(names) => {
+                    missing.extend(names);
+                    None
+                }
+            },
+        )+);
+        if !missing.is_empty() {
+            return Ok(Partial::Missing(missing));
+        }
+        values
+    }};
+}
+
+impl<'c> Interpreter<'c> {
+    pub(super) fn new(
+        params: &'c BTreeMap<String, ParamType>,
+        context: &'c HashMap<String, Value>,
+    ) -> Self {
+        Self {
+            params,
+            context,
+            scope: Vec::new(),
+        }
+    }
+
+    pub(super) fn eval(&mut self, expr: &Expr) -> ConditionResult<Partial> {
+        let span = expr.span;
+        let value = match &expr.kind {
+            ExprKind::Literal(value) => value.clone(),
+            ExprKind::Ident(name) => return self.ident(name, span),
+            ExprKind::List(items) => {
+                let mut values = Vec::new();
+                let mut missing = BTreeSet::new();
+                for item in items {
+                    match self.eval(item)? {
+                        Partial::Known(value) => values.push(value),
+                        Partial::Missing(names) => missing.extend(names),
+                    }
+                }
+                if !missing.is_empty() {
+                    return Ok(Partial::Missing(missing));
+                }
+                Value::List(values)
+            }
+            ExprKind::Map(entries) => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let (k, v) = known!(self, key, value);
+                    let (Some(Value::String(k)), Some(v)) = (k, v) else {
+                        return Err(runtime("map keys must be strings", key.span));
+                    };
+                    map.insert(k, v);
+                }
+                Value::Map(map)
+            }
+            ExprKind::Unary(op, operand) => {
+                let (Some(value),) = known!(self, operand) else {
+                    unreachable!()
+                };
+                unary(*op, value, span)?
+            }
+            ExprKind::Binary(BinaryOp::And, lhs, rhs) => return self.logical(false, lhs, rhs),
+            ExprKind::Binary(BinaryOp::Or, lhs, rhs) => return self.logical(true, lhs, rhs),
+            ExprKind::Binary(op, lhs, rhs) => {
+                let (Some(a), Some(b)) = known!(self, lhs, rhs) else {
+                    unreachable!()
+                };
+                binary(*op, a, b, span)?
+            }
+            ExprKind::Ternary(cond, then, otherwise) => {
+                let (Some(cond),) = known!(self, cond) else {
+                    unreachable!()
+                };
+                return match cond {
+                    Value::Bool(true) => self.eval(then),
+                    Value::Bool(false) => self.eval(otherwise),
+                    other => Err(no_overload("_?_:_", &[&other], span)),
+                };
+            }
+            ExprKind::Member(target, field) => {
+                let (Some(target),) = known!(self, target) else {
+                    unreachable!()
+                };
+                match target {
+                    Value::Map(mut map) => map
+                        .remove(field)
+                        .ok_or_else(|| runtime(format!("no such key: {field}"), span))?,
+                    other => return Err(no_overload(&format!(".{field}"), &[&other], span)),
+                }
+            }
+            ExprKind::Index(target, index) => {
+                let (Some(target), Some(index)) = known!(self, target, index) else {
+                    unreachable!()
+                };
+                self.index(target, index, span)?
+            }
+            ExprKind::Call { target, name, args } => {
+                if let (Some(target), true) = (target, is_macro(name)) {
+                    return self.call_macro(target, name, args, span);
+                }
+                let mut values = Vec::new();
+                let mut missing = BTreeSet::new();
+                for arg in target.iter().map(|t| &**t).chain(args) {
+                    match self.eval(arg)? {
+                        Partial::Known(value) => values.push(value),
+                        Partial::Missing(names) => missing.extend(names),
+                    }
+                }
+                if !missing.is_empty() {
+                    return Ok(Partial::Missing(missing));
+                }
+                call(name, target.is_some(), values, span)?
+            }
+        };
+        Ok(Partial::Known(value))
+    }
+
+    fn ident(&self, name: &str, span: Span) -> ConditionResult<Partial> {
+        if let Some((_, value)) = self.scope.iter().rev().find(|(n, _)| n == name) {
+            return Ok(Partial::Known(value.clone()));
+        }
+        if !self.params.contains_key(name) {
+            return Err(ConditionError::UndefinedIdentifier(name.to_string(), span));
+        }
+        Ok(match self.context.get(name) {
+            Some(value) => Partial::Known(value.clone()),
+            None => Partial::Missing(BTreeSet::from([name.to_string()])),
+        })
+    }
+
+    /// `||` when `short` is true, `&&` otherwise. Either side decides
+    /// the result on its own, so a missing parameter on one side does
+    /// not matter if the other side is `short`.
+    fn logical(&mut self, short: bool, lhs: &Expr, rhs: &Expr) -> ConditionResult<Partial> {
+        let mut missing = BTreeSet::new();
+        for side in [lhs, rhs] {
+            match self.eval(side)? {
+                Partial::Known(Value::Bool(b)) if b == short => {
+                    return Ok(Partial::Known(Value::Bool(short)))
+                }
+                Partial::Known(Value::Bool(_)) => {}
+                Partial::Known(other) => {
+                    let op = if short { "_||_" } else { "_&&_" };
+                    return Err(no_overload(op, &[&other], side.span));
+                }
+                Partial::Missing(names) => missing.extend(names),
+            }
+        }
+        if missing.is_empty() {
+            Ok(Partial::Known(Value::Bool(!short)))
+        } else {
+            Ok(Partial::Missing(missing))
+        }
+    }
+
+    fn index(&self, target: Value, index: Value, span: Span) -> ConditionResult<Value> {
+        match (&target, &index) {
+            (Value::List(items), Value::Int(_) | Value::Uint(_)) => {
+                let i = match index {
+                    Value::Int(i) => usize::try_from(i).ok(),
+                    Value::Uint(u) => usize::try_from(u).ok(),
+                    _ => None,
+                };
+                i.and_then(|i| items.get(i))
+                    .cloned()
+                    .ok_or_else(|| runtime(format!("index out of range: {index}"), span))
+            }
+            (Value::Map(map), Value::String(key)) => map
+                .get(key)
+                .cloned()
+                .ok_or_else(|| runtime(format!("no such key: {key}"), span)),
+            _ => Err(no_overload("_[_]", &[&target, &index], span)),
+        }
+    }
+
+    /// The comprehension macros `all`, `exists`, `exists_one`, `map`
+    /// and `filter` over lists and map keys.
+    fn call_macro(
+        &mut self,
+        target: &Expr,
+        name: &str,
+        args: &[Expr],
+        span: Span,
+    ) -> ConditionResult<Partial> {
+        let [var, body] = args else {
+            return Err(runtime(format!("{name} expects 2 arguments"), span));
+        };
+        let ExprKind::Ident(var) = &var.kind else {
+            return Err(runtime(format!("{name} expects a variable name"), var.span));
+        };
+        let (Some(range),) = known!(self, target) else {
+            unreachable!()
+        };
+        let items = match range {
+            Value::List(items) => items,
+            Value::Map(map) => map.into_keys().map(Value::String).collect(),
+            other => return Err(no_overload(name, &[&other], span)),
+        };
+
+        let mut results = Vec::new();
+        let mut missing = BTreeSet::new();
+        for item in items {
+            self.scope.push((var.clone(), item.clone()));
+            let res = self.eval(body);
+            self.scope.pop();
+            match res? {
+                Partial::Known(value) => results.push((item, value)),
+                Partial::Missing(names) => missing.extend(names),
+            }
+        }
+
+        let as_bool = |value: &Value| match value {
+            Value::Bool(b) => Ok(*b),
+            other => Err(no_overload(name, &[other], body.span)),
+        };
+        let value = match name {
+            "all" | "exists" => {
+                let short = name == "exists";
+                for (_, value) in &results {
+                    if as_bool(value)? == short {
+                        return Ok(Partial::Known(Value::Bool(short)));
+                    }
+                }
+                Value::Bool(!short)
+            }
+            _ if !missing.is_empty() => return Ok(Partial::Missing(missing)),
+            "exists_one" => {
+                let mut count = 0;
+                for (_, value) in &results {
+                    count += usize::from(as_bool(value)?);
+                }
+                Value::Bool(count == 1)
+            }
+            "filter" => {
+                let mut kept = Vec::new();
+                for (item, value) in results {
+                    if as_bool(&value)? {
+                        kept.push(item);
+                    }
+                }
+                Value::List(kept)
+            }
+            _ => Value::List(results.into_iter().map(|(_, value)| value).collect()),
+        };
+        if missing.is_empty() {
+            Ok(Partial::Known(value))
+        } else {
+            Ok(Partial::Missing(missing))
+        }
+    }
+}
+
+pub(crate) fn is_macro(name: &str) -> bool {
+    matches!(name, "all" | "exists" | "exists_one" | "map" | "filter")
+}
+
+fn unary(op: UnaryOp, value: Value, span: Span) -> ConditionResult<Value> {
+    Ok(match (op, value) {
+        (UnaryOp::Not, Value::Bool(b)) => Value::Bool(!b),
+        (UnaryOp::Neg, Value::Int(i)) => Value::Int(
+            i.checked_neg()
+                .ok_or_else(|| runtime("integer overflow", span))?,
+        ),
+        (UnaryOp::Neg, Value::Double(d)) => Value::Double(-d),
+        (UnaryOp::Neg, Value::Duration(d)) => Value::Duration(
+            d.checked_neg()
+                .and_then(checked_duration)
+                .ok_or_else(|| runtime("integer overflow", span))?,
+        ),
+        (UnaryOp::Not, value) => return Err(no_overload("!_", &[&value], span)),
+        (UnaryOp::Neg, value) => return Err(no_overload("-_", &[&value], span)),
+    })
+}
+
+fn binary(op: BinaryOp, a: Value, b: Value, span: Span) -> ConditionResult<Value> {
+    use Value::*;
+
+    let overflow = || runtime("integer overflow", span);
+    let timestamp = |t: Option<i128>| {
+        t.and_then(checked_timestamp)
+            .map(Timestamp)
+            .ok_or_else(|| runtime("timestamp out of range", span))
+    };
+    let duration = |d: Option<i128>| {
+        d.and_then(checked_duration)
+            .map(Duration)
+            .ok_or_else(overflow)
+    };
+    let name = match op {
+        BinaryOp::Eq => return Ok(Bool(a.equals(&b))),
+        BinaryOp::Ne => return Ok(Bool(!a.equals(&b))),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = a
+                .compare(&b)
+                .filter(|_| !matches!(a, IpAddress(_) | Null))
+                .ok_or_else(|| no_overload(op_name(op), &[&a, &b], span))?;
+            return Ok(Bool(match op {
+                BinaryOp::Lt => ordering.is_lt(),
+                BinaryOp::Le => ordering.is_le(),
+                BinaryOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }));
+        }
+        BinaryOp::In => {
+            return match &b {
+                List(items) => Ok(Bool(items.iter().any(|item| item.equals(&a)))),
+                Map(map) => match &a {
+                    String(key) => Ok(Bool(map.contains_key(key))),
+                    _ => Ok(Bool(false)),
+                },
+                _ => Err(no_overload("@in", &[&a, &b], span)),
+            };
+        }
+        op => op_name(op),
+    };
+
+    Ok(match (op, &a, &b) {
+        (BinaryOp::Add, Int(x), Int(y)) => Int(x.checked_add(*y).ok_or_else(overflow)?),
+        (BinaryOp::Sub, Int(x), Int(y)) => Int(x.checked_sub(*y).ok_or_else(overflow)?),
+        (BinaryOp::Mul, Int(x), Int(y)) => Int(x.checked_mul(*y).ok_or_else(overflow)?),
+        (BinaryOp::Div | BinaryOp::Rem, Int(_), Int(0))
+        | (BinaryOp::Div | BinaryOp::Rem, Uint(_), Uint(0)) => {
+            return Err(runtime("division by zero", span))
+        }
+        (BinaryOp::Div, Int(x), Int(y)) => Int(x.checked_div(*y).ok_or_else(overflow)?),
+        (BinaryOp::Rem, Int(x), Int(y)) => Int(x.checked_rem(*y).ok_or_else(overflow)?),
+        (BinaryOp::Add, Uint(x), Uint(y)) => Uint(x.checked_add(*y).ok_or_else(overflow)?),
+        (BinaryOp::Sub, Uint(x), Uint(y)) => Uint(x.checked_sub(*y).ok_or_else(overflow)?),
+        (BinaryOp::Mul, Uint(x), Uint(y)) => Uint(x.checked_mul(*y).ok_or_else(overflow)?),
+        (BinaryOp::Div, Uint(x), Uint(y)) => Uint(x / y),
+        (BinaryOp::Rem, Uint(x), Uint(y)) => Uint(x % y),
+        (BinaryOp::Add, Double(x), Double(y)) => Double(x + y),
+        (BinaryOp::Sub, Double(x), Double(y)) => Double(x - y),
+        (BinaryOp::Mul, Double(x), Double(y)) => Double(x * y),
+        (BinaryOp::Div, Double(x), Double(y)) => Double(x / y),
+        (BinaryOp::Add, String(x), String(y)) => String(format!("{x}{y}")),
+        (BinaryOp::Add, List(x), List(y)) => List(x.iter().chain(y).cloned().collect()),
+        (BinaryOp::Add, Duration(x), Duration(y)) => duration(x.checked_add(*y))?,
+        (BinaryOp::Sub, Duration(x), Duration(y)) => duration(x.checked_sub(*y))?,
+        (BinaryOp::Add, Timestamp(t), Duration(d)) | (BinaryOp::Add, Duration(d), Timestamp(t)) => {
+            timestamp(t.checked_add(*d))?
+        }
+        (BinaryOp::Sub, Timestamp(t), Duration(d)) => timestamp(t.checked_sub(*d))?,
+        (BinaryOp::Sub, Timestamp(x), Timestamp(y)) => duration(x.checked_sub(*y))?,
+        _ => return Err(no_overload(name, &[&a, &b], span)),
+    })
+}
+
+pub(super) fn op_name(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Or => "_||_",
+        BinaryOp::And => "_&&_",
+        BinaryOp::Eq => "_==_",
+        BinaryOp::Ne => "_!=_",
+        BinaryOp::Lt => "_<_",
+        BinaryOp::Le => "_<=_",
+        BinaryOp::Gt => "_>_",
+        BinaryOp::Ge => "_>=_",
+        BinaryOp::In => "@in",
+        BinaryOp::Add => "_+_",
+        BinaryOp::Sub => "_-_",
+        BinaryOp::Mul => "_*_",
+        BinaryOp::Div => "_/_",
+        BinaryOp::Rem => "_%_",
+    }
+}
+
+/// Calls a function; for methods the target is the first argument.
+fn call(name: &str, method: bool, args: Vec<Value>, span: Span) -> ConditionResult<Value> {
+    use Value::*;
+
+    let invalid = |message: std::string::String| runtime(message, span);
+    let fail = |args: &[Value]| {
+        let args: Vec<&Value> = args.iter().collect();
+        let name = if method {
+            format!(".{name}")
+        } else {
+            name.to_string()
+        };
+        Err(no_overload(&name, &args, span))
+    };
+
+    Ok(match (name, method, args.as_slice()) {
+        ("size", _, [String(s)]) => Int(s.chars().count() as i64),
+        ("size", _, [List(items)]) => Int(items.len() as i64),
+        ("size", _, [Map(map)]) => Int(map.len() as i64),
+        ("contains", true, [String(s), String(sub)]) => Bool(s.contains(sub.as_str())),
+        ("startsWith", true, [String(s), String(p)]) => Bool(s.starts_with(p.as_str())),
+        ("endsWith", true, [String(s), String(p)]) => Bool(s.ends_with(p.as_str())),
+        ("matches", _, [String(_), String(_)]) => {
+            return Err(invalid("regular expressions are not supported".into()))
+        }
+        ("in_cidr", true, [IpAddress(ip), String(cidr)]) => {
+            Bool(in_cidr(ip, cidr).map_err(invalid)?)
+        }
+
+        ("timestamp", false, [String(s)]) => Timestamp(parse_timestamp(s).map_err(invalid)?),
+        ("timestamp", false, [t @ Timestamp(_)]) => t.clone(),
+        ("duration", false, [String(s)]) => Duration(parse_duration(s).map_err(invalid)?),
+        ("duration", false, [d @ Duration(_)]) => d.clone(),
+        ("ipaddress", false, [String(s)]) => IpAddress(parse_ip(s).map_err(invalid)?),
+        ("ipaddress", false, [ip @ IpAddress(_)]) => ip.clone(),
+
+        ("int", false, [Int(i)]) => Int(*i),
+        ("int", false, [Uint(u)]) => {
+            Int(i64::try_from(*u).map_err(|_| invalid("integer overflow".into()))?)
+        }
+        ("int", false, [Double(d)]) if d.is_finite() && d.abs() < 9.2e18 => Int(*d as i64),
+        ("int", false, [String(s)]) => Int(s
+            .parse()
+            .map_err(|_| invalid(format!("invalid int '{s}'")))?),
+        ("int", false, [Timestamp(t)]) => Int((t.div_euclid(1_000_000_000)) as i64),
+        ("uint", false, [Uint(u)]) => Uint(*u),
+        ("uint", false, [Int(i)]) => {
+            Uint(u64::try_from(*i).map_err(|_| invalid("integer overflow".into()))?)
+        }
+        ("uint", false, [Double(d)]) if d.is_finite() && *d >= 0.0 && *d < 1.8e19 => {
+            Uint(*d as u64)
+        }
+        ("uint", false, [String(s)]) => Uint(
+            s.parse()
+                .map_err(|_| invalid(format!("invalid uint '{s}'")))?,
+        ),
+        ("double", false, [Double(d)]) => Double(*d),
+        ("double", false, [Int(i)]) => Double(*i as f64),
+        ("double", false, [Uint(u)]) => Double(*u as f64),
+        ("double", false, [String(s)]) => Double(
+            s.parse()
+                .map_err(|_| invalid(format!("invalid double '{s}'")))?,
+        ),
+        ("string", false, [value]) => String(match value {
+            String(s) => s.clone(),
+            Bool(b) => b.to_string(),
+            Int(i) => i.to_string(),
+            Uint(u) => u.to_string(),
+            Double(d) => d.to_string(),
+            Duration(d) => format_duration(*d),
+            Timestamp(t) => format_timestamp(*t),
+            IpAddress(ip) => ip.to_string(),
+            _ => return fail(&args),
+        }),
+        ("bool", false, [Bool(b)]) => Bool(*b),
+        ("bool", false, [String(s)]) => match s.as_str() {
+            "true" => Bool(true),
+            "false" => Bool(false),
+            _ => return Err(invalid(format!("invalid bool '{s}'"))),
+        },
+
+        (getter, true, [Timestamp(t)]) if getter.starts_with("get") => {
+            let parts = TimeParts::of(*t);
+            Int((match getter {
+                "getFullYear" => parts.year,
+                "getMonth" => parts.month - 1,
+                "getDate" => parts.day,
+                "getDayOfMonth" => parts.day - 1,
+                "getDayOfYear" => parts.day_of_year(),
+                "getDayOfWeek" => (parts.days + 4).rem_euclid(7),
+                "getHours" => parts.hour,
+                "getMinutes" => parts.minute,
+                "getSeconds" => parts.second,
+                "getMilliseconds" => parts.nanos / 1_000_000,
+                _ => return fail(&args),
+            }) as i64)
+        }
+        (getter, true, [Duration(d)]) if getter.starts_with("get") => Int((match getter {
+            "getHours" => d / 3_600_000_000_000,
+            "getMinutes" => d / 60_000_000_000,
+            "getSeconds" => d / 1_000_000_000,
+            "getMilliseconds" => d / 1_000_000,
+            _ => return fail(&args),
+        }) as i64),
+        _ if is_function(name) => return fail(&args),
+        _ => return Err(ConditionError::UndefinedFunction(name.to_string(), span)),
+    })
+}
+
+/// Functions and methods known to the evaluator.
+pub(crate) fn is_function(name: &str) -> bool {
+    const FUNCTIONS: &[&str] = &[
+        "size",
+        "contains",
+        "startsWith",
+        "endsWith",
+        "matches",
+        "in_cidr",
+        "timestamp",
+        "duration",
+        "ipaddress",
+        "int",
+        "uint",
+        "double",
+        "string",
+        "bool",
+        "getFullYear",
+        "getMonth",
+        "getDate",
+        "getDayOfMonth",
+        "getDayOfYear",
+        "getDayOfWeek",
+        "getHours",
+        "getMinutes",
+        "getSeconds",
+        "getMilliseconds",
+    ];
+    FUNCTIONS.contains(&name) || is_macro(name)
+}
@@ -0,0 +1,542 @@
+use super::{ConditionError, ConditionResult, Value};
+use crate::lexer::token::Span;
+
+/// A parsed CEL expression, located within the condition body.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Expr {
+    pub(crate) kind: ExprKind,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum ExprKind {
+    Literal(Value),
+    Ident(String),
+    List(Vec<Expr>),
+    Map(Vec<(Expr, Expr)>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// Field selection, e.g. `request.ip`.
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    /// A function call, or a method call when there is a target.
+    Call {
+        target: Option<Box<Expr>>,
+        name: String,
+        args: Vec<Expr>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Tok {
+    Ident(String),
+    Int(i64),
+    Uint(u64),
+    Double(f64),
+    Str(String),
+    Punct(&'static str),
+    Eof,
+}
+
+/// Operators, longest first so `<=` is not read as `<`.
+const PUNCTS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "!", "?", ":", ".", ",",
+    "(", ")", "[", "]", "{", "}",
+];
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+/// Parses a CEL expression.
+pub(crate) fn parse(input: &str) -> ConditionResult<Expr> {
+    let mut lex = Lexer {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+    let mut tokens = Vec::new();
+    loop {
+        let (tok, span) = lex.next()?;
+        let eof = tok == Tok::Eof;
+        tokens.push((tok, span));
+        if eof {
+            break;
+        }
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.expr()?;
+    match parser.peek() {
+        Tok::Eof => Ok(expr),
+        _ => Err(parser.unexpected()),
+    }
+}
+
+fn syntax(message: impl Into<String>, span: Span) -> ConditionError {
+    ConditionError::Syntax {
+        message: message.into(),
+        span,
+    }
+}
+
+impl Lexer {
+    fn peek_char(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn next(&mut self) -> ConditionResult<(Tok, Span)> {
+        while self.peek_char(0).is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let Some(c) = self.peek_char(0) else {
+            return Ok((Tok::Eof, Span::new(start, start)));
+        };
+
+        let tok = if c.is_ascii_alphabetic() || c == '_' {
+            while self
+                .peek_char(0)
+                .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                self.pos += 1;
+            }
+            Tok::Ident(self.chars[start..self.pos].iter().collect())
+        } else if c.is_ascii_digit() {
+            self.number()?
+        } else if c == '"' || c == '\'' {
+            self.string(c)?
+        } else {
+            let rest: String = self.chars[self.pos..].iter().take(2).collect();
+            let Some(punct) = PUNCTS.iter().find(|p| rest.starts_with(**p)) else {
+                return Err(syntax(
+                    format!("unexpected character '{c}'"),
+                    Span::new(start, start + 1),
+                ));
+            };
+            self.pos += punct.len();
+            Tok::Punct(punct)
+        };
+        Ok((tok, Span::new(start, self.pos)))
+    }
+
+    fn number(&mut self) -> ConditionResult<Tok> {
+        let start = self.pos;
+        if self.peek_char(0) == Some('0') && matches!(self.peek_char(1), Some('x' | 'X')) {
+            self.pos += 2;
+            while self.peek_char(0).is_some_and(|c| c.is_ascii_hexdigit()) {
+                self.pos += 1;
+            }
+            let digits: String = self.chars[start + 2..self.pos].iter().collect();
+            return self.integer(&digits, 16, start);
+        }
+
+        while self.peek_char(0).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let mut double = false;
+        if self.peek_char(0) == Some('.') && self.peek_char(1).is_some_and(|c| c.is_ascii_digit()) {
+            double = true;
+            self.pos += 1;
+            while self.peek_char(0).is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+        }
+        if matches!(self.peek_char(0), Some('e' | 'E')) {
+            let sign = usize::from(matches!(self.peek_char(1), Some('+' | '-')));
+            if self.peek_char(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                double = true;
+                self.pos += 1 + sign;
+                while self.peek_char(0).is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+            }
+        }
+
+        let literal: String = self.chars[start..self.pos].iter().collect();
+        if double {
+            return literal
+                .parse()
+                .map(Tok::Double)
+                .map_err(|_| syntax("invalid double literal", Span::new(start, self.pos)));
+        }
+        self.integer(&literal, 10, start)
+    }
+
+    fn integer(&mut self, digits: &str, radix: u32, start: usize) -> ConditionResult<Tok> {
+        let unsigned = matches!(self.peek_char(0), Some('u' | 'U'));
+        if unsigned {
+            self.pos += 1;
+        }
+        let span = Span::new(start, self.pos);
+        if unsigned {
+            u64::from_str_radix(digits, radix)
+                .map(Tok::Uint)
+                .map_err(|_| syntax("invalid uint literal", span))
+        } else {
+            i64::from_str_radix(digits, radix)
+                .map(Tok::Int)
+                .map_err(|_| syntax("invalid int literal", span))
+        }
+    }
+
+    fn string(&mut self, quote: char) -> ConditionResult<Tok> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let Some(c) = self.peek_char(0) else {
+                return Err(syntax("unterminated string", Span::new(start, self.pos)));
+            };
+            self.pos += 1;
+            match c {
+                c if c == quote => return Ok(Tok::Str(s)),
+                '\n' => {
+                    return Err(syntax(
+                        "unterminated string",
+                        Span::new(start, self.pos - 1),
+                    ))
+                }
+                '\\' => s.push(self.escape()?),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> ConditionResult<char> {
+        let start = self.pos - 1;
+        let c = self.peek_char(0);
+        self.pos += 1;
+        Ok(match c {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some(c @ ('\\' | '"' | '\'' | '`' | '?')) => c,
+            Some('u') => {
+                let hex: String = self.chars[self.pos..].iter().take(4).collect();
+                self.pos += hex.chars().count();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 4)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| syntax("invalid unicode escape", Span::new(start, self.pos)))?
+            }
+            _ => return Err(syntax("invalid escape", Span::new(start, self.pos))),
+        })
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Tok, Span)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].0
+    }
+
+    fn span(&self) -> Span {
+        self.tokens[self.pos].1
+    }
+
+    /// The span of the last consumed token.
+    fn prev_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].1
+    }
+
+    fn advance(&mut self) -> Tok {
+        let tok = self.tokens[self.pos].0.clone();
+        if tok != Tok::Eof {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Tok::Punct(p) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> ConditionResult<()> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(syntax(format!("expected '{punct}'"), self.span()))
+        }
+    }
+
+    fn unexpected(&self) -> ConditionError {
+        match self.peek() {
+            Tok::Eof => syntax("unexpected end of expression", self.span()),
+            _ => syntax("unexpected token", self.span()),
+        }
+    }
+
+    fn expr(&mut self) -> ConditionResult<Expr> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.binary(0)?;
+        self.expect(":")?;
+        let otherwise = self.expr()?;
+        let span = cond.span.to(otherwise.span);
+        Ok(Expr {
+            kind: ExprKind::Ternary(Box::new(cond), Box::new(then), Box::new(otherwise)),
+            span,
+        })
+    }
+
+    /// Binary operators by increasing precedence.
+    fn binary(&mut self, level: usize) -> ConditionResult<Expr> {
+        const LEVELS: &[&[(&str, BinaryOp)]] = &[
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+                ("in", BinaryOp::In),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+        ];
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+
+        let mut lhs = self.binary(level + 1)?;
+        loop {
+            let op = ops.iter().find(|(tok, _)| match self.peek() {
+                Tok::Punct(p) => p == tok,
+                Tok::Ident(ident) => ident == tok,
+                _ => false,
+            });
+            let Some((_, op)) = op else {
+                return Ok(lhs);
+            };
+            self.advance();
+            let rhs = self.binary(level + 1)?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr {
+                kind: ExprKind::Binary(*op, Box::new(lhs), Box::new(rhs)),
+                span,
+            };
+        }
+    }
+
+    fn unary(&mut self) -> ConditionResult<Expr> {
+        let start = self.span();
+        let op = if self.eat("!") {
+            UnaryOp::Not
+        } else if self.eat("-") {
+            UnaryOp::Neg
+        } else {
+            return self.member();
+        };
+        let operand = self.unary()?;
+        let span = start.to(operand.span);
+        Ok(Expr {
+            kind: ExprKind::Unary(op, Box::new(operand)),
+            span,
+        })
+    }
+
+    fn member(&mut self) -> ConditionResult<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let Tok::Ident(name) = self.advance() else {
+                    return Err(syntax("expected field name", self.prev_span()));
+                };
+                if self.eat("(") {
+                    let args = self.args(")")?;
+                    let span = expr.span.to(self.prev_span());
+                    expr = Expr {
+                        kind: ExprKind::Call {
+                            target: Some(Box::new(expr)),
+                            name,
+                            args,
+                        },
+                        span,
+                    };
+                } else {
+                    let span = expr.span.to(self.prev_span());
+                    expr = Expr {
+                        kind: ExprKind::Member(Box::new(expr), name),
+                        span,
+                    };
+                }
+            } else if self.eat("[") {
+                let index = self.expr()?;
+                self.expect("]")?;
+                let span = expr.span.to(self.prev_span());
+                expr = Expr {
+                    kind: ExprKind::Index(Box::new(expr), Box::new(index)),
+                    span,
+                };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> ConditionResult<Expr> {
+        let start = self.span();
+        let pos = self.pos;
+        let kind = match self.advance() {
+            Tok::Int(i) => ExprKind::Literal(Value::Int(i)),
+            Tok::Uint(u) => ExprKind::Literal(Value::Uint(u)),
+            Tok::Double(d) => ExprKind::Literal(Value::Double(d)),
+            Tok::Str(s) => ExprKind::Literal(Value::String(s)),
+            Tok::Ident(ident) => match ident.as_str() {
+                "true" => ExprKind::Literal(Value::Bool(true)),
+                "false" => ExprKind::Literal(Value::Bool(false)),
+                "null" => ExprKind::Literal(Value::Null),
+                "in" => return Err(syntax("unexpected keyword 'in'", start)),
+                _ if self.eat("(") => ExprKind::Call {
+                    target: None,
+                    name: ident,
+                    args: self.args(")")?,
+                },
+                _ => ExprKind::Ident(ident),
+            },
+            Tok::Punct("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                return Ok(Expr {
+                    kind: expr.kind,
+                    span: start.to(self.prev_span()),
+                });
+            }
+            Tok::Punct("[") => ExprKind::List(self.args("]")?),
+            Tok::Punct("{") => {
+                let mut entries = Vec::new();
+                while !self.eat("}") {
+                    let key = self.expr()?;
+                    self.expect(":")?;
+                    entries.push((key, self.expr()?));
+                    if !self.eat(",") {
+                        self.expect("}")?;
+                        break;
+                    }
+                }
+                ExprKind::Map(entries)
+            }
+            _ => {
+                self.pos = pos;
+                return Err(self.unexpected());
+            }
+        };
+        Ok(Expr {
+            kind,
+            span: start.to(self.prev_span()),
+        })
+    }
+
+    /// Comma separated expressions up to and including `close`.
+    fn args(&mut self, close: &str) -> ConditionResult<Vec<Expr>> {
+        let mut args = Vec::new();
+        while !self.eat(close) {
+            args.push(self.expr()?);
+            if !self.eat(",") {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str, start: usize) -> Expr {
+        Expr {
+            kind: ExprKind::Ident(name.into()),
+            span: Span::new(start, start + name.len()),
+        }
+    }
+
+    #[test]
+    fn parses_precedence() {
+        let expr = parse("a || b && !c").unwrap();
+        let ExprKind::Binary(BinaryOp::Or, lhs, rhs) = expr.kind else {
+            panic!("expected ||, got {expr:?}");
+        };
+        assert_eq!(*lhs, ident("a", 0));
+        let ExprKind::Binary(BinaryOp::And, _, not) = rhs.kind else {
+            panic!("expected &&");
+        };
+        assert_eq!(not.span, Span::new(10, 12));
+    }
+
+    #[test]
+    fn parses_calls_and_members() {
+        let expr = parse(r#"request.ip.in_cidr("10.0.0.0/8")"#).unwrap();
+        let ExprKind::Call { target, name, args } = expr.kind else {
+            panic!("expected call");
+        };
+        assert_eq!(name, "in_cidr");
+        assert_eq!(args.len(), 1);
+        let target = target.unwrap();
+        assert_eq!(target.span, Span::new(0, 10));
+        assert!(matches!(target.kind, ExprKind::Member(_, ref f) if f == "ip"));
+
+        let expr = parse("x in [1, 2u, 3.5, 'a'] ? {'k': v}['k'] : 0x10").unwrap();
+        assert!(matches!(expr.kind, ExprKind::Ternary(..)));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            parse("a < ").unwrap_err(),
+            syntax("unexpected end of expression", Span::new(4, 4))
+        );
+        assert_eq!(
+            parse("a # b").unwrap_err(),
+            syntax("unexpected character '#'", Span::new(2, 3))
+        );
+        assert_eq!(
+            parse("'abc").unwrap_err(),
+            syntax("unterminated string", Span::new(0, 4))
+        );
+    }
+}
@@ -0,0 +1,546 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde_json::{Map, Value as Json};
+
+use crate::ast::{self, Document, SourceMap};
+use crate::lexer::token::Span;
+
+mod check;
+mod evaluate;
+mod expr;
+mod value;
+
+use check::TypeChecker;
+use evaluate::{Interpreter, Partial};
+use expr::Expr;
+
+pub use value::Value;
+
+/// Result type for parsing and evaluating conditions.
+pub type ConditionResult<T> = Result<T, ConditionError>;
+
+/// The type of a condition parameter, as declared in OpenFGA models,
+/// e.g. `string`, `timestamp` or `list<ipaddress>`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParamType {
+    Any,
+    Bool,
+    String,
+    Int,
+    Uint,
+    Double,
+    Duration,
+    Timestamp,
+    IpAddress,
+    List(Box<ParamType>),
+    /// A map with string keys.
+    Map(Box<ParamType>),
+}
+
+/// A named condition: a CEL expression over typed parameters that
+/// decides whether a conditional tuple applies, compiled from a
+/// `condition` block of the model.
+///
+/// ```
+/// use openfga_dsl_parser::condition::{Condition, ConditionOutcome};
+/// use openfga_dsl_parser::Parser;
+/// use serde_json::json;
+///
+/// let doc = Parser::new(
+///     "condition in_office(ip: ipaddress) {
+///   ip.in_cidr('10.0.0.0/8')
+/// }",
+/// )
+/// .parse_document()
+/// .unwrap();
+/// let condition = Condition::from_ast(&doc.conditions[0]).unwrap();
+///
+/// let context = json!({"ip": "10.1.2.3"});
+/// assert_eq!(
+///     condition.evaluate(context.as_object().unwrap()),
+///     Ok(ConditionOutcome::Met)
+/// );
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Condition {
+    pub name: String,
+    pub params: BTreeMap<String, ParamType>,
+    pub expression: String,
+    expr: Expr,
+}
+
+/// Whether a condition holds for a context.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ConditionOutcome {
+    Met,
+    NotMet,
+    /// The context lacks parameters the result depends on.
+    MissingContext(Vec<String>),
+}
+
+/// The conditions declared in a model, by name. A condition that fails
+/// to compile is kept with its error, so only the tuples using it fail.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Conditions {
+    conditions: HashMap<String, ConditionResult<Condition>>,
+}
+
+/// Enumerated error type for conditions. Spans are character
+/// offsets into the condition's expression, or into the model for
+/// errors from [check_document].
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConditionError {
+    Syntax {
+        message: String,
+        span: Span,
+    },
+    UnknownParamType(String),
+    UndefinedIdentifier(String, Span),
+    UndefinedFunction(String, Span),
+    /// An operator or function applied to arguments of the wrong types.
+    NoSuchOverload {
+        function: String,
+        args: String,
+        span: Span,
+    },
+    /// Evaluation failed, e.g. on division by zero or a missing map key.
+    Runtime {
+        message: String,
+        span: Span,
+    },
+    /// A context value that does not match its declared type.
+    InvalidParameter {
+        name: String,
+        message: String,
+    },
+    /// The expression evaluated to a non-boolean value.
+    NotBoolean(Value),
+    /// A subexpression whose type cannot be used where it appears,
+    /// found by [Condition::check].
+    TypeMismatch {
+        expected: ParamType,
+        found: ParamType,
+        span: Span,
+    },
+}
+
+impl Condition {
+    /// Compiles a condition declared in a model.
+    pub fn from_ast(condition: &ast::Condition) -> ConditionResult<Self> {
+        Ok(Self {
+            name: condition.name.clone(),
+            params: condition
+                .params
+                .iter()
+                .map(|p| (p.name.clone(), p.ty.clone()))
+                .collect(),
+            expression: condition.expression.clone(),
+            expr: expr::parse(&condition.expression)?,
+        })
+    }
+
+    /// Type-checks the expression against the declared parameters,
+    /// returning every undeclared name, mismatched type and
+    /// non-boolean result found, without needing a context.
+    pub fn check(&self) -> Vec<ConditionError> {
+        TypeChecker::new(&self.params).check(&self.expr)
+    }
+
+    /// Evaluates the condition against `context`. Values not declared
+    /// as parameters are ignored.
+    pub fn evaluate(&self, context: &Map<String, Json>) -> ConditionResult<ConditionOutcome> {
+        let mut values = HashMap::new();
+        for (name, ty) in &self.params {
+            if let Some(json) = context.get(name) {
+                let value = Value::from_json(json, ty).map_err(|message| {
+                    ConditionError::InvalidParameter {
+                        name: name.clone(),
+                        message,
+                    }
+                })?;
+                values.insert(name.clone(), value);
+            }
+        }
+
+        match Interpreter::new(&self.params, &values).eval(&self.expr)? {
+            Partial::Known(Value::Bool(true)) => Ok(ConditionOutcome::Met),
+            Partial::Known(Value::Bool(false)) => Ok(ConditionOutcome::NotMet),
+            Partial::Known(other) => Err(ConditionError::NotBoolean(other)),
+            Partial::Missing(names) => Ok(ConditionOutcome::MissingContext(
+                names.into_iter().collect(),
+            )),
+        }
+    }
+}
+
+impl Conditions {
+    /// Compiles the conditions declared in `doc`.
+    pub fn from_document(doc: &Document) -> Self {
+        let conditions = doc
+            .conditions
+            .iter()
+            .map(|c| (c.name.clone(), Condition::from_ast(c)))
+            .collect();
+        Self { conditions }
+    }
+
+    /// The condition named `name`, or the error compiling it.
+    pub fn get(&self, name: &str) -> Option<Result<&Condition, &ConditionError>> {
+        self.conditions.get(name).map(Result::as_ref)
+    }
+}
+
+/// Parses and type-checks the conditions declared in `doc`, as
+/// [Condition::check] does. Spans of the errors are located in the
+/// input `source` was recorded from.
+pub fn check_document(doc: &Document, source: &SourceMap) -> Vec<ConditionError> {
+    let mut errors = Vec::new();
+    for (condition, source) in doc.conditions.iter().zip(&source.conditions) {
+        let found = match Condition::from_ast(condition) {
+            Ok(condition) => condition.check(),
+            Err(e) => vec![e],
+        };
+        let start = source.expression.start;
+        errors.extend(found.into_iter().map(|e| e.offset(start)));
+    }
+    errors
+}
+
+impl ConditionError {
+    /// The error with its span moved `by` characters further, e.g. from
+    /// the expression into the file containing it.
+    pub fn offset(mut self, by: usize) -> Self {
+        use ConditionError::*;
+        match &mut self {
+            Syntax { span, .. }
+            | UndefinedIdentifier(_, span)
+            | UndefinedFunction(_, span)
+            | NoSuchOverload { span, .. }
+            | Runtime { span, .. }
+            | TypeMismatch { span, .. } => {
+                span.start += by;
+                span.end += by;
+            }
+            UnknownParamType(_) | InvalidParameter { .. } | NotBoolean(_) => {}
+        }
+        self
+    }
+
+    /// Where in the expression the error occurred. Add the offset of
+    /// the expression to locate it within a larger file.
+    pub fn span(&self) -> Option<Span> {
+        use ConditionError::*;
+        match self {
+            Syntax { span, .. }
+            | UndefinedIdentifier(_, span)
+            | UndefinedFunction(_, span)
+            | NoSuchOverload { span, .. }
+            | Runtime { span, .. }
+            | TypeMismatch { span, .. } => Some(*span),
+            UnknownParamType(_) | InvalidParameter { .. } | NotBoolean(_) => None,
+        }
+    }
+}
+
+impl FromStr for ParamType {
+    type Err = ConditionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let generic = |prefix: &str| {
+            s.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix('>'))
+                .map(|inner| inner.trim().parse().map(Box::new))
+        };
+        Ok(match s.trim() {
+            "any" => ParamType::Any,
+            "bool" => ParamType::Bool,
+            "string" => ParamType::String,
+            "int" => ParamType::Int,
+            "uint" => ParamType::Uint,
+            "double" => ParamType::Double,
+            "duration" => ParamType::Duration,
+            "timestamp" => ParamType::Timestamp,
+            "ipaddress" => ParamType::IpAddress,
+            _ => match (generic("list<"), generic("map<")) {
+                (Some(item), _) => ParamType::List(item?),
+                (_, Some(item)) => ParamType::Map(item?),
+                _ => return Err(ConditionError::UnknownParamType(s.to_string())),
+            },
+        })
+    }
+}
+
+impl Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamType::Any => write!(f, "any"),
+            ParamType::Bool => write!(f, "bool"),
+            ParamType::String => write!(f, "string"),
+            ParamType::Int => write!(f, "int"),
+            ParamType::Uint => write!(f, "uint"),
+            ParamType::Double => write!(f, "double"),
+            ParamType::Duration => write!(f, "duration"),
+            ParamType::Timestamp => write!(f, "timestamp"),
+            ParamType::IpAddress => write!(f, "ipaddress"),
+            ParamType::List(item) => write!(f, "list<{item}>"),
+            ParamType::Map(item) => write!(f, "map<{item}>"),
+        }
+    }
+}
+
+impl Display for ConditionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ConditionError::*;
+        match self {
+            Syntax { message, span } => write!(f, "{message} at position {}", span.start),
+            UnknownParamType(ty) => write!(f, "unknown parameter type '{ty}'"),
+            UndefinedIdentifier(name, span) => {
+                write!(
+                    f,
+                    "undeclared reference to '{name}' at position {}",
+                    span.start
+                )
+            }
+            UndefinedFunction(name, span) => {
+                write!(f, "undeclared function '{name}' at position {}", span.start)
+            }
+            NoSuchOverload {
+                function,
+                args,
+                span,
+            } => write!(
+                f,
+                "found no matching overload for '{function}' applied to ({args}) at position {}",
+                span.start
+            ),
+            Runtime { message, span } => write!(f, "{message} at position {}", span.start),
+            InvalidParameter { name, message } => {
+                write!(f, "invalid value for parameter '{name}': {message}")
+            }
+            NotBoolean(value) => write!(f, "expected a bool result, got {value}"),
+            TypeMismatch {
+                expected,
+                found,
+                span,
+            } => write!(
+                f,
+                "expected {expected}, found {found} at position {}",
+                span.start
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Compiles the only condition of `dsl`.
+    fn compile(dsl: &str) -> Condition {
+        let doc = crate::Parser::new(dsl).parse_document().unwrap();
+        Condition::from_ast(&doc.conditions[0]).unwrap()
+    }
+
+    fn eval(condition: &Condition, context: Json) -> ConditionResult<ConditionOutcome> {
+        condition.evaluate(context.as_object().unwrap())
+    }
+
+    #[test]
+    fn comparisons_and_logic() {
+        let condition = compile(
+            "condition quota(count: int, limit: uint, tier: string) {
+  count < limit && (tier == 'pro' || count < 3)
+}",
+        );
+
+        let res = eval(&condition, json!({"count": 5, "limit": 10, "tier": "pro"}));
+        assert_eq!(res, Ok(ConditionOutcome::Met));
+        let res = eval(&condition, json!({"count": 5, "limit": 10, "tier": "free"}));
+        assert_eq!(res, Ok(ConditionOutcome::NotMet));
+        // the result does not depend on `tier` once `count < 3`
+        let res = eval(&condition, json!({"count": 1, "limit": 10}));
+        assert_eq!(res, Ok(ConditionOutcome::Met));
+        let res = eval(&condition, json!({"count": 5}));
+        assert_eq!(
+            res,
+            Ok(ConditionOutcome::MissingContext(vec![
+                "limit".into(),
+                "tier".into()
+            ]))
+        );
+    }
+
+    #[test]
+    fn timestamps_and_durations() {
+        let condition = compile(
+            "condition grant(current_time: timestamp, grant_time: timestamp, grant_duration: duration) {
+  current_time < grant_time + grant_duration && grant_time.getDayOfWeek() == 1
+}",
+        );
+
+        let context = json!({
+            "grant_time": "2024-01-01T10:00:00Z",
+            "grant_duration": "1h30m",
+            "current_time": "2024-01-01T11:29:59Z",
+        });
+        assert_eq!(eval(&condition, context), Ok(ConditionOutcome::Met));
+        let context = json!({
+            "grant_time": "2024-01-01T10:00:00Z",
+            "grant_duration": "1h30m",
+            "current_time": "2024-01-01T11:30:00Z",
+        });
+        assert_eq!(eval(&condition, context), Ok(ConditionOutcome::NotMet));
+
+        let condition = compile(
+            "condition t(t: timestamp) { timestamp('2024-01-01T00:00:00Z') - duration('1s') < t }",
+        );
+        assert_eq!(
+            eval(&condition, json!({"t": "2024-01-01T00:00:00Z"})),
+            Ok(ConditionOutcome::Met)
+        );
+    }
+
+    #[test]
+    fn ip_lists_and_maps() {
+        let condition = compile(
+            "condition network(
+  ip: ipaddress,
+  cidr: string,
+  region: string,
+  allowed: list<string>,
+  limits: map<int>
+) {
+  ip.in_cidr(cidr) && region in allowed && limits[region] > 0
+    && allowed.exists(r, r.startsWith('eu'))
+}",
+        );
+
+        let context = json!({
+            "ip": "192.168.1.7",
+            "cidr": "192.168.0.0/16",
+            "region": "eu-west",
+            "allowed": ["us-east", "eu-west"],
+            "limits": {"eu-west": 2},
+        });
+        assert_eq!(eval(&condition, context), Ok(ConditionOutcome::Met));
+
+        let context = json!({
+            "ip": "192.168.1.7",
+            "cidr": "192.168.0.0/16",
+            "region": "eu-west",
+            "allowed": ["us-east"],
+            "limits": {},
+        });
+        assert_eq!(eval(&condition, context), Ok(ConditionOutcome::NotMet));
+    }
+
+    #[test]
+    fn typed_parameters() {
+        let condition = compile("condition c(x: int) { x > 1 }");
+        assert_eq!(
+            eval(&condition, json!({"x": "2"})),
+            Err(ConditionError::InvalidParameter {
+                name: "x".into(),
+                message: "expected int, got \"2\"".into()
+            })
+        );
+        // undeclared context values are ignored
+        assert_eq!(
+            eval(&condition, json!({"x": 2, "y": "z"})),
+            Ok(ConditionOutcome::Met)
+        );
+        assert_eq!(
+            "list<map<timestamp>>".parse(),
+            Ok(ParamType::List(Box::new(ParamType::Map(Box::new(
+                ParamType::Timestamp
+            )))))
+        );
+        assert_eq!(
+            "set<int>".parse::<ParamType>(),
+            Err(ConditionError::UnknownParamType("set<int>".into()))
+        );
+    }
+
+    #[test]
+    fn checks_model_conditions() {
+        let input = "type user
+condition in_office(ip: ipaddress, cidr: string) {
+  request.ip == ip || ip.in_cidr(cidr) + 1
+}
+condition recent(t: timestamp) {
+  t >
+}";
+        let mut parser = crate::Parser::new(input);
+        let doc = parser.parse_document().unwrap();
+        let errors = check_document(&doc, parser.source_map());
+        let located: Vec<(String, String)> = errors
+            .iter()
+            .map(|e| {
+                let span = e.span().unwrap();
+                let line = span.line_col(input).0;
+                let text = input.chars().skip(span.start).take(span.end - span.start);
+                (text.collect(), format!("{line}: {e}"))
+            })
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                (
+                    "request".into(),
+                    "2: undeclared reference to 'request' at position 63".into()
+                ),
+                (
+                    "ip.in_cidr(cidr) + 1".into(),
+                    "2: found no matching overload for '_+_' applied to (bool, int) at position 83"
+                        .into()
+                ),
+                (
+                    "".into(),
+                    "5: unexpected end of expression at position 144".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluation_errors() {
+        let condition = compile("condition c(x: int) { x + 'a' == y }");
+        assert_eq!(
+            eval(&condition, json!({"x": 1})).unwrap_err().to_string(),
+            "found no matching overload for '_+_' applied to (int, string) at position 0"
+        );
+
+        let condition = compile("condition c(x: int) { x / 0 == 1 }");
+        assert_eq!(
+            eval(&condition, json!({"x": 1})).unwrap_err().to_string(),
+            "division by zero at position 0"
+        );
+        // durations are bounded to 10000 years either way
+        for expression in ["d + d > d", "d - -d > d", "-d - d < d"] {
+            let condition = compile(&format!("condition c(d: duration) {{ {expression} }}"));
+            assert_eq!(
+                eval(&condition, json!({"d": "87660000h"}))
+                    .unwrap_err()
+                    .to_string(),
+                "integer overflow at position 0",
+                "{expression}"
+            );
+        }
+        let condition = compile("condition c(d: duration) { d > duration('0s') }");
+        assert_eq!(
+            eval(&condition, json!({"d": "87660001h"})),
+            Err(ConditionError::InvalidParameter {
+                name: "d".into(),
+                message: "duration '87660001h' out of range".into(),
+            })
+        );
+        let condition = compile("condition c(x: int) { x + 1 }");
+        assert_eq!(
+            eval(&condition, json!({"x": 1})),
+            Err(ConditionError::NotBoolean(Value::Int(2)))
+        );
+    }
+}
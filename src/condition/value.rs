@@ -0,0 +1,524 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::net::IpAddr;
+
+use serde_json::Value as Json;
+
+use super::ParamType;
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+const SECONDS_PER_DAY: i128 = 86_400;
+/// Seconds from the Unix epoch to 0001-01-01 and 10000-01-01, the
+/// range of CEL timestamps.
+const MIN_TIMESTAMP: i128 = -62_135_596_800;
+const MAX_TIMESTAMP: i128 = 253_402_300_800;
+/// Seconds in 10000 years, the bound of CEL durations either way.
+const MAX_DURATION: i128 = 315_576_000_000;
+
+/// A value of the CEL subset supported in conditions.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Double(f64),
+    String(String),
+    /// Nanoseconds.
+    Duration(i128),
+    /// Nanoseconds since the Unix epoch.
+    Timestamp(i128),
+    IpAddress(IpAddr),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Uint(_) => "uint",
+            Value::Double(_) => "double",
+            Value::String(_) => "string",
+            Value::Duration(_) => "duration",
+            Value::Timestamp(_) => "timestamp",
+            Value::IpAddress(_) => "ipaddress",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+
+    /// Converts a JSON context value to a value of type `ty`, the
+    /// way OpenFGA reads condition parameters.
+    pub fn from_json(json: &Json, ty: &ParamType) -> Result<Value, String> {
+        let mismatch = || format!("expected {ty}, got {json}");
+        Ok(match (ty, json) {
+            (ParamType::Any, json) => Value::from_any(json),
+            (ParamType::Bool, Json::Bool(b)) => Value::Bool(*b),
+            (ParamType::String, Json::String(s)) => Value::String(s.clone()),
+            (ParamType::Int, Json::Number(n)) => Value::Int(n.as_i64().ok_or_else(mismatch)?),
+            (ParamType::Uint, Json::Number(n)) => Value::Uint(n.as_u64().ok_or_else(mismatch)?),
+            (ParamType::Double, Json::Number(n)) => Value::Double(n.as_f64().ok_or_else(mismatch)?),
+            (ParamType::Duration, Json::String(s)) => Value::Duration(parse_duration(s)?),
+            (ParamType::Timestamp, Json::String(s)) => Value::Timestamp(parse_timestamp(s)?),
+            (ParamType::IpAddress, Json::String(s)) => Value::IpAddress(parse_ip(s)?),
+            (ParamType::List(item), Json::Array(items)) => Value::List(
+                items
+                    .iter()
+                    .map(|i| Value::from_json(i, item))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (ParamType::Map(item), Json::Object(entries)) => Value::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| Ok((k.clone(), Value::from_json(v, item)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            _ => return Err(mismatch()),
+        })
+    }
+
+    fn from_any(json: &Json) -> Value {
+        match json {
+            Json::Null => Value::Null,
+            Json::Bool(b) => Value::Bool(*b),
+            Json::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => Value::Int(i),
+                (None, Some(u)) => Value::Uint(u),
+                _ => Value::Double(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Json::String(s) => Value::String(s.clone()),
+            Json::Array(items) => Value::List(items.iter().map(Value::from_any).collect()),
+            Json::Object(entries) => Value::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.clone(), Value::from_any(v)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// CEL equality, under which numbers of different types compare
+    /// by value and values of different types are unequal.
+    pub(crate) fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.equals(b))
+            }
+            (Value::Map(a), Value::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(k, v)| b.get(k).is_some_and(|other| v.equals(other)))
+            }
+            (a, b) => a.compare(b) == Some(Ordering::Equal),
+        }
+    }
+
+    /// Ordering of comparable values, or `None` when the types
+    /// cannot be compared.
+    pub(crate) fn compare(&self, other: &Value) -> Option<Ordering> {
+        use Value::*;
+        match (self, other) {
+            (Null, Null) => Some(Ordering::Equal),
+            (Bool(a), Bool(b)) => Some(a.cmp(b)),
+            (Int(a), Int(b)) => Some(a.cmp(b)),
+            (Uint(a), Uint(b)) => Some(a.cmp(b)),
+            (Int(a), Uint(b)) => Some(i128::from(*a).cmp(&i128::from(*b))),
+            (Uint(a), Int(b)) => Some(i128::from(*a).cmp(&i128::from(*b))),
+            (Double(a), Double(b)) => a.partial_cmp(b),
+            (Double(a), Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Double(a), Uint(b)) => a.partial_cmp(&(*b as f64)),
+            (Int(a), Double(b)) => (*a as f64).partial_cmp(b),
+            (Uint(a), Double(b)) => (*a as f64).partial_cmp(b),
+            (String(a), String(b)) => Some(a.cmp(b)),
+            (Duration(a), Duration(b)) | (Timestamp(a), Timestamp(b)) => Some(a.cmp(b)),
+            (IpAddress(a), IpAddress(b)) => (a == b).then_some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a duration like `1h30m`, `1.5s` or `-300ms` into nanoseconds.
+pub(crate) fn parse_duration(s: &str) -> Result<i128, String> {
+    let invalid = || format!("invalid duration '{s}'");
+    let (negative, mut rest) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if rest == "0" {
+        return Ok(0);
+    }
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut total: i128 = 0;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let (number, tail) = rest.split_at(digits);
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let unit: i128 = match unit {
+            "ns" => 1,
+            "us" | "µs" => 1_000,
+            "ms" => 1_000_000,
+            "s" => NANOS_PER_SECOND,
+            "m" => 60 * NANOS_PER_SECOND,
+            "h" => 3_600 * NANOS_PER_SECOND,
+            _ => return Err(invalid()),
+        };
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if whole.is_empty() && fraction.is_empty() || fraction.len() > 18 {
+            return Err(invalid());
+        }
+        let whole: i128 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| invalid())?
+        };
+        let mut nanos = whole.checked_mul(unit).ok_or_else(invalid)?;
+        if !fraction.is_empty() {
+            let scale = 10i128.pow(fraction.len() as u32);
+            let fraction: i128 = fraction.parse().map_err(|_| invalid())?;
+            nanos = fraction
+                .checked_mul(unit)
+                .and_then(|f| nanos.checked_add(f / scale))
+                .ok_or_else(invalid)?;
+        }
+        total = total.checked_add(nanos).ok_or_else(invalid)?;
+        rest = tail;
+    }
+    checked_duration(if negative { -total } else { total })
+        .ok_or_else(|| format!("duration '{s}' out of range"))
+}
+
+/// Formats a duration as CEL does, in seconds, e.g. `5400s` or `1.5s`.
+pub(crate) fn format_duration(nanos: i128) -> String {
+    let seconds = nanos / NANOS_PER_SECOND;
+    let fraction = (nanos % NANOS_PER_SECOND).abs();
+    if fraction == 0 {
+        return format!("{seconds}s");
+    }
+    let sign = if nanos < 0 && seconds == 0 { "-" } else { "" };
+    let fraction = format!("{fraction:09}");
+    format!("{sign}{seconds}.{}s", fraction.trim_end_matches('0'))
+}
+
+/// Parses an RFC 3339 timestamp into nanoseconds since the Unix epoch.
+pub(crate) fn parse_timestamp(s: &str) -> Result<i128, String> {
+    let invalid = || format!("invalid timestamp '{s}'");
+    let bytes = s.as_bytes();
+    let number = |range: std::ops::Range<usize>| -> Result<i128, String> {
+        let part = s.get(range).ok_or_else(invalid)?;
+        if !part.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        part.parse().map_err(|_| invalid())
+    };
+    let separated = |i: usize, seps: &[u8]| bytes.get(i).is_some_and(|b| seps.contains(b));
+    if s.len() < 20
+        || !separated(4, b"-")
+        || !separated(7, b"-")
+        || !separated(10, b"Tt")
+        || !separated(13, b":")
+        || !separated(16, b":")
+    {
+        return Err(invalid());
+    }
+
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(invalid());
+    }
+
+    let mut pos = 19;
+    let mut nanos = 0;
+    if bytes[pos] == b'.' {
+        let digits = bytes[pos + 1..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits == 0 || digits > 9 {
+            return Err(invalid());
+        }
+        nanos = number(pos + 1..pos + 1 + digits)? * 10i128.pow(9 - digits as u32);
+        pos += 1 + digits;
+    }
+
+    let offset = match &s[pos..] {
+        "Z" | "z" => 0,
+        tz if tz.len() == 6 && separated(pos + 3, b":") => {
+            let sign = match bytes[pos] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(invalid()),
+            };
+            let (hours, minutes) = (number(pos + 1..pos + 3)?, number(pos + 4..pos + 6)?);
+            if hours > 23 || minutes > 59 {
+                return Err(invalid());
+            }
+            sign * (hours * 3_600 + minutes * 60)
+        }
+        _ => return Err(invalid()),
+    };
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * SECONDS_PER_DAY + hour * 3_600 + minute * 60 + second - offset;
+    checked_timestamp(seconds * NANOS_PER_SECOND + nanos).ok_or_else(invalid)
+}
+
+/// Formats a timestamp as RFC 3339 in UTC.
+pub(crate) fn format_timestamp(nanos: i128) -> String {
+    let parts = TimeParts::of(nanos);
+    let mut s = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        parts.year, parts.month, parts.day, parts.hour, parts.minute, parts.second
+    );
+    if parts.nanos > 0 {
+        let fraction = format!("{:09}", parts.nanos);
+        s.push('.');
+        s.push_str(fraction.trim_end_matches('0'));
+    }
+    s.push('Z');
+    s
+}
+
+/// `nanos` if it is within the range of CEL durations.
+pub(crate) fn checked_duration(nanos: i128) -> Option<i128> {
+    (nanos.abs() <= MAX_DURATION * NANOS_PER_SECOND).then_some(nanos)
+}
+
+/// `nanos` if it is within the range of CEL timestamps.
+pub(crate) fn checked_timestamp(nanos: i128) -> Option<i128> {
+    let seconds = nanos.div_euclid(NANOS_PER_SECOND);
+    (MIN_TIMESTAMP..MAX_TIMESTAMP)
+        .contains(&seconds)
+        .then_some(nanos)
+}
+
+pub(crate) fn parse_ip(s: &str) -> Result<IpAddr, String> {
+    s.parse().map_err(|_| format!("invalid ipaddress '{s}'"))
+}
+
+/// Whether `ip` is within the CIDR block `cidr`, e.g. `10.0.0.0/8`.
+pub(crate) fn in_cidr(ip: &IpAddr, cidr: &str) -> Result<bool, String> {
+    let invalid = || format!("invalid CIDR '{cidr}'");
+    let (network, prefix) = cidr.split_once('/').ok_or_else(invalid)?;
+    let network: IpAddr = network.parse().map_err(|_| invalid())?;
+    let prefix: u32 = prefix.parse().map_err(|_| invalid())?;
+    let (ip, network, bits) = match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            (u32::from(*ip) as u128, u32::from(network) as u128, 32)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => (u128::from(*ip), u128::from(network), 128),
+        _ => return Ok(false),
+    };
+    if prefix > bits {
+        return Err(invalid());
+    }
+    let shift = bits - prefix;
+    Ok(prefix == 0 || ip >> shift == network >> shift)
+}
+
+/// The calendar fields of a timestamp in UTC.
+pub(crate) struct TimeParts {
+    pub(crate) year: i128,
+    pub(crate) month: i128,
+    pub(crate) day: i128,
+    pub(crate) hour: i128,
+    pub(crate) minute: i128,
+    pub(crate) second: i128,
+    pub(crate) nanos: i128,
+    /// Days since the Unix epoch.
+    pub(crate) days: i128,
+}
+
+impl TimeParts {
+    pub(crate) fn of(nanos: i128) -> Self {
+        let seconds = nanos.div_euclid(NANOS_PER_SECOND);
+        let days = seconds.div_euclid(SECONDS_PER_DAY);
+        let of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        TimeParts {
+            year,
+            month,
+            day,
+            hour: of_day / 3_600,
+            minute: of_day % 3_600 / 60,
+            second: of_day % 60,
+            nanos: nanos.rem_euclid(NANOS_PER_SECOND),
+            days,
+        }
+    }
+
+    /// Zero-based day of the year.
+    pub(crate) fn day_of_year(&self) -> i128 {
+        self.days - days_from_civil(self.year, 1, 1)
+    }
+}
+
+fn is_leap_year(year: i128) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i128, month: i128) -> i128 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the Unix epoch of a proleptic Gregorian date.
+fn days_from_civil(year: i128, month: i128, day: i128) -> i128 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i128) -> (i128, i128, i128) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Int(i) => write!(f, "{i}"),
+            Value::Uint(u) => write!(f, "{u}u"),
+            Value::Double(d) => write!(f, "{d:?}"),
+            Value::String(s) => write!(f, "{s:?}"),
+            Value::Duration(d) => write!(f, "duration({:?})", format_duration(*d)),
+            Value::Timestamp(t) => write!(f, "timestamp({:?})", format_timestamp(*t)),
+            Value::IpAddress(ip) => write!(f, "ipaddress({:?})", ip.to_string()),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{k:?}: {v}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1h30m"), Ok(5_400 * NANOS_PER_SECOND));
+        assert_eq!(parse_duration("1.5s"), Ok(1_500_000_000));
+        assert_eq!(parse_duration("-300ms"), Ok(-300_000_000));
+        assert_eq!(parse_duration("0"), Ok(0));
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("1d").is_err());
+        // CEL bounds durations to 10000 years either way
+        assert_eq!(
+            parse_duration("-87660000h"),
+            Ok(-MAX_DURATION * NANOS_PER_SECOND)
+        );
+        assert_eq!(
+            parse_duration("87660000h1ns"),
+            Err("duration '87660000h1ns' out of range".into())
+        );
+        assert!(parse_duration("99999999999999999999999999999999999999h").is_err());
+        assert_eq!(format_duration(5_400 * NANOS_PER_SECOND), "5400s");
+        assert_eq!(format_duration(-500_000_000), "-0.5s");
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Ok(0));
+        let t = parse_timestamp("2024-02-29T13:45:10.25+02:00").unwrap();
+        assert_eq!(format_timestamp(t), "2024-02-29T11:45:10.25Z");
+        let parts = TimeParts::of(t);
+        assert_eq!(parts.day_of_year(), 59);
+        assert_eq!(
+            format_timestamp(parse_timestamp("1969-12-31T23:59:59Z").unwrap()),
+            "1969-12-31T23:59:59Z"
+        );
+        assert!(parse_timestamp("2023-02-29T00:00:00Z").is_err());
+        assert!(parse_timestamp("2023-01-01 00:00:00Z").is_err());
+        assert!(parse_timestamp("2023-01-01T00:00:00").is_err());
+    }
+
+    #[test]
+    fn cidr() {
+        let ip = parse_ip("10.1.2.3").unwrap();
+        assert_eq!(in_cidr(&ip, "10.0.0.0/8"), Ok(true));
+        assert_eq!(in_cidr(&ip, "10.2.0.0/16"), Ok(false));
+        assert_eq!(in_cidr(&ip, "::/0"), Ok(false));
+        assert!(in_cidr(&ip, "10.0.0.0/33").is_err());
+        let ip = parse_ip("2001:db8::1").unwrap();
+        assert_eq!(in_cidr(&ip, "2001:db8::/32"), Ok(true));
+    }
+
+    #[test]
+    fn typed_json() {
+        let ty: ParamType = "map<list<int>>".parse().unwrap();
+        let value = Value::from_json(&json!({"a": [1, 2]}), &ty).unwrap();
+        assert_eq!(
+            value,
+            Value::Map([("a".into(), Value::List(vec![Value::Int(1), Value::Int(2)]))].into())
+        );
+        assert_eq!(
+            Value::from_json(&json!([1, "2"]), &"list<int>".parse().unwrap()),
+            Err("expected int, got \"2\"".into())
+        );
+        assert_eq!(
+            Value::from_json(&json!(1.5), &ParamType::Int),
+            Err("expected int, got 1.5".into())
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use serde_json::{json, Value};
+
+use crate::ast::{Alias, AliasKind, Document, Relation, Type};
+use crate::validate::{DirectlyRelated, RelatedType};
+
+/// How a [Change](crate::diff::Change) affects existing access.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
+pub enum Impact {
+    /// Only grants access, never revokes it.
+    Additive,
+    /// May revoke access, but existing tuples and queries stay valid.
+    Narrowing,
+    /// Invalidates existing tuples or queries.
+    Breaking,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ChangeKind {
+    TypeAdded,
+    TypeRemoved,
+    RelationAdded,
+    RelationRemoved,
+    /// The definition of a relation changed, as written in the DSL.
+    RewriteChanged {
+        old: String,
+        new: String,
+    },
+    RelatedTypeAdded(RelatedType),
+    RelatedTypeRemoved(RelatedType),
+}
+
+/// A single difference between two models.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Change {
+    pub ty: String,
+    pub relation: Option<String>,
+    pub kind: ChangeKind,
+    pub impact: Impact,
+}
+
+/// Every [Change](crate::diff::Change) between two models.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+/// Compares two [Documents](crate::ast::Document) semantically, ignoring
+/// the order of types, relations and aliases.
+pub struct Differ<'d> {
+    old: &'d Document,
+    new: &'d Document,
+    related: Option<(&'d DirectlyRelated, &'d DirectlyRelated)>,
+}
+
+impl<'d> Differ<'d> {
+    pub fn new(old: &'d Document, new: &'d Document) -> Self {
+        Self {
+            old,
+            new,
+            related: None,
+        }
+    }
+
+    /// Also compare the directly related types of each relation.
+    pub fn with_related_types(
+        mut self,
+        old: &'d DirectlyRelated,
+        new: &'d DirectlyRelated,
+    ) -> Self {
+        self.related = Some((old, new));
+        self
+    }
+
+    pub fn diff(&self) -> Diff {
+        let mut changes = Vec::new();
+
+        for old_ty in &self.old.types {
+            match self.new.get_type(&old_ty.kind) {
+                Some(new_ty) => self.diff_type(old_ty, new_ty, &mut changes),
+                None => changes.push(Change {
+                    ty: old_ty.kind.clone(),
+                    relation: None,
+                    kind: ChangeKind::TypeRemoved,
+                    impact: Impact::Breaking,
+                }),
+            }
+        }
+
+        for new_ty in &self.new.types {
+            if self.old.get_type(&new_ty.kind).is_none() {
+                changes.push(Change {
+                    ty: new_ty.kind.clone(),
+                    relation: None,
+                    kind: ChangeKind::TypeAdded,
+                    impact: Impact::Additive,
+                });
+            }
+        }
+
+        Diff { changes }
+    }
+
+    fn diff_type(&self, old_ty: &Type, new_ty: &Type, changes: &mut Vec<Change>) {
+        for old_rel in &old_ty.relations {
+            let change = |kind, impact| Change {
+                ty: old_ty.kind.clone(),
+                relation: Some(old_rel.kind.clone()),
+                kind,
+                impact,
+            };
+
+            let Some(new_rel) = new_ty.get_relation(&old_rel.kind) else {
+                changes.push(change(ChangeKind::RelationRemoved, Impact::Breaking));
+                continue;
+            };
+
+            if let Some(impact) = rewrite_impact(old_rel, new_rel) {
+                let kind = ChangeKind::RewriteChanged {
+                    old: old_rel.to_string(),
+                    new: new_rel.to_string(),
+                };
+                changes.push(change(kind, impact));
+            }
+
+            let Some((old_related, new_related)) = self.related else {
+                continue;
+            };
+            let (Some(old_related), Some(new_related)) = (
+                old_related.get(&old_ty.kind, &old_rel.kind),
+                new_related.get(&new_ty.kind, &new_rel.kind),
+            ) else {
+                continue;
+            };
+            for related in old_related {
+                if !new_related.contains(related) {
+                    let kind = ChangeKind::RelatedTypeRemoved(related.clone());
+                    changes.push(change(kind, Impact::Breaking));
+                }
+            }
+            for related in new_related {
+                if !old_related.contains(related) {
+                    let kind = ChangeKind::RelatedTypeAdded(related.clone());
+                    changes.push(change(kind, Impact::Additive));
+                }
+            }
+        }
+
+        for new_rel in &new_ty.relations {
+            if old_ty.get_relation(&new_rel.kind).is_none() {
+                changes.push(Change {
+                    ty: new_ty.kind.clone(),
+                    relation: Some(new_rel.kind.clone()),
+                    kind: ChangeKind::RelationAdded,
+                    impact: Impact::Additive,
+                });
+            }
+        }
+    }
+}
+
+/// Classifies a change of rewrite, or `None` if both relations
+/// grant the same access.
+fn rewrite_impact(old: &Relation, new: &Relation) -> Option<Impact> {
+    let (old_pos, old_neg) = split_aliases(old);
+    let (new_pos, new_neg) = split_aliases(new);
+    if old_pos == new_pos && old_neg == new_neg {
+        return None;
+    }
+
+    let impact = if old.is_assignable() && !new.is_assignable() {
+        // stored tuples for the relation stop granting access
+        // and can no longer be written
+        Impact::Breaking
+    } else if !old_pos.is_subset(&new_pos) || !new_neg.is_subset(&old_neg) {
+        Impact::Narrowing
+    } else {
+        Impact::Additive
+    };
+    Some(impact)
+}
+
+/// Splits a relation's aliases into those granting access and those
+/// narrowing it down with `and` or `but not`.
+fn split_aliases(rel: &Relation) -> (HashSet<&Alias>, HashSet<&Alias>) {
+    rel.aliases
+        .iter()
+        .partition(|a| matches!(a.kind, AliasKind::This | AliasKind::Named(_)))
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The most severe impact of any change.
+    pub fn impact(&self) -> Option<Impact> {
+        self.changes.iter().map(|c| c.impact).max()
+    }
+
+    /// Transforms the diff into a JSON string.
+    pub fn serialize(&self) -> String {
+        let changes: Vec<Value> = self.changes.iter().map(Change::to_json).collect();
+        json!({
+            "impact": self.impact().map(|i| i.to_string()),
+            "changes": changes,
+        })
+        .to_string()
+    }
+}
+
+impl Change {
+    fn to_json(&self) -> Value {
+        let mut obj = json!({
+            "type": self.ty,
+            "relation": self.relation,
+            "change": self.kind.name(),
+            "impact": self.impact.to_string(),
+        });
+        match &self.kind {
+            ChangeKind::RewriteChanged { old, new } => {
+                obj["old"] = old.clone().into();
+                obj["new"] = new.clone().into();
+            }
+            ChangeKind::RelatedTypeAdded(related) | ChangeKind::RelatedTypeRemoved(related) => {
+                obj["related_type"] = related.to_string().into();
+            }
+            _ => {}
+        }
+        obj
+    }
+}
+
+impl ChangeKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ChangeKind::TypeAdded => "type_added",
+            ChangeKind::TypeRemoved => "type_removed",
+            ChangeKind::RelationAdded => "relation_added",
+            ChangeKind::RelationRemoved => "relation_removed",
+            ChangeKind::RewriteChanged { .. } => "rewrite_changed",
+            ChangeKind::RelatedTypeAdded(_) => "related_type_added",
+            ChangeKind::RelatedTypeRemoved(_) => "related_type_removed",
+        }
+    }
+}
+
+impl Display for Impact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Impact::Additive => write!(f, "additive"),
+            Impact::Narrowing => write!(f, "narrowing"),
+            Impact::Breaking => write!(f, "breaking"),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let target = match &self.relation {
+            Some(rel) => format!("{}#{rel}", self.ty),
+            None => self.ty.clone(),
+        };
+        write!(f, "[{}] ", self.impact)?;
+        match &self.kind {
+            ChangeKind::TypeAdded => write!(f, "added type {target}"),
+            ChangeKind::TypeRemoved => write!(f, "removed type {target}"),
+            ChangeKind::RelationAdded => write!(f, "added relation {target}"),
+            ChangeKind::RelationRemoved => write!(f, "removed relation {target}"),
+            ChangeKind::RewriteChanged { old, new } => {
+                write!(f, "changed {target}: `{old}` -> `{new}`")
+            }
+            ChangeKind::RelatedTypeAdded(related) => {
+                write!(f, "added directly related type {related} to {target}")
+            }
+            ChangeKind::RelatedTypeRemoved(related) => {
+                write!(f, "removed directly related type {related} from {target}")
+            }
+        }
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn diff(old: &str, new: &str) -> Diff {
+        let old = Parser::new(old).parse_document().unwrap();
+        let new = Parser::new(new).parse_document().unwrap();
+        Differ::new(&old, &new).diff()
+    }
+
+    fn impacts(diff: &Diff) -> Vec<(&str, Impact)> {
+        diff.changes
+            .iter()
+            .map(|c| (c.kind.name(), c.impact))
+            .collect()
+    }
+
+    const MODEL: &str = "type user
+type document
+  relations
+    define owner as self
+    define editor as self or owner
+    define viewer as self or editor but not blocked
+    define blocked as self";
+
+    #[test]
+    fn identical_models_ignoring_order() {
+        let reordered = "type document
+  relations
+    define blocked as self
+    define viewer as editor or self but not blocked
+    define editor as owner or self
+    define owner as self
+type user";
+        assert!(diff(MODEL, reordered).is_empty());
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let new = "type document
+  relations
+    define owner as self
+    define editor as self or owner
+    define viewer as self or editor but not blocked
+    define blocked as self
+    define commenter as self
+type team";
+        let d = diff(MODEL, new);
+        assert_eq!(
+            impacts(&d),
+            vec![
+                ("type_removed", Impact::Breaking),
+                ("relation_added", Impact::Additive),
+                ("type_added", Impact::Additive),
+            ]
+        );
+        assert_eq!(d.impact(), Some(Impact::Breaking));
+    }
+
+    #[test]
+    fn classifies_rewrites() {
+        let new = "type user
+type document
+  relations
+    define owner as self
+    define editor as owner
+    define viewer as self or editor or owner
+    define blocked as self but not owner";
+        let d = diff(MODEL, new);
+        assert_eq!(
+            impacts(&d),
+            vec![
+                ("rewrite_changed", Impact::Breaking),
+                ("rewrite_changed", Impact::Additive),
+                ("rewrite_changed", Impact::Narrowing),
+            ]
+        );
+        assert_eq!(
+            d.changes[1].kind,
+            ChangeKind::RewriteChanged {
+                old: "define viewer as self or editor but not blocked".into(),
+                new: "define viewer as self or editor or owner".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn intersections_narrow() {
+        let new = MODEL.replace(
+            "define editor as self or owner",
+            "define editor as self and owner",
+        );
+        assert_eq!(
+            impacts(&diff(MODEL, &new)),
+            vec![("rewrite_changed", Impact::Narrowing)]
+        );
+        assert_eq!(
+            impacts(&diff(&new, MODEL)),
+            vec![("rewrite_changed", Impact::Additive)]
+        );
+    }
+
+    #[test]
+    fn removed_related_types() {
+        let old = Parser::new(MODEL).parse_document().unwrap();
+        let mut old_related = DirectlyRelated::new();
+        old_related.insert("document", "owner", ["user", "team#member"]);
+        let mut new_related = DirectlyRelated::new();
+        new_related.insert("document", "owner", ["user", "user:*"]);
+
+        let d = Differ::new(&old, &old)
+            .with_related_types(&old_related, &new_related)
+            .diff();
+        assert_eq!(
+            impacts(&d),
+            vec![
+                ("related_type_removed", Impact::Breaking),
+                ("related_type_added", Impact::Additive),
+            ]
+        );
+        assert_eq!(
+            d.to_string(),
+            "[breaking] removed directly related type team#member from document#owner
+[additive] added directly related type user:* to document#owner
+"
+        );
+    }
+
+    #[test]
+    fn json_report() {
+        let d = diff(MODEL, &MODEL.replace("or owner", ""));
+        let res: Value = serde_json::from_str(&d.serialize()).unwrap();
+        let exp = json!({
+            "impact": "narrowing",
+            "changes": [{
+                "type": "document",
+                "relation": "editor",
+                "change": "rewrite_changed",
+                "impact": "narrowing",
+                "old": "define editor as self or owner",
+                "new": "define editor as self",
+            }]
+        });
+        assert_eq!(exp, res);
+    }
+}
@@ -0,0 +1,311 @@
+use serde_json::{json, Value};
+
+use super::{parse_user, Checker, EvalResult, User};
+use crate::ast::{Alias, AliasKind};
+
+/// The userset tree of a relation on an object, as returned by
+/// [Checker::expand](crate::eval::Checker::expand).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UsersetTree {
+    pub root: Node,
+}
+
+/// A node of a [UsersetTree](crate::eval::UsersetTree), named
+/// after the `object#relation` it expands.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Node {
+    pub name: String,
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum NodeKind {
+    Leaf(Leaf),
+    Union(Vec<Node>),
+    Intersection(Vec<Node>),
+    Difference {
+        base: Box<Node>,
+        subtract: Box<Node>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Leaf {
+    /// Users directly related through tuples.
+    Users(Vec<String>),
+    /// Another relation on the same object, e.g. `document:1#editor`.
+    Computed(String),
+    /// A relation on the objects related through a tupleset.
+    TupleToUserset {
+        tupleset: String,
+        computed: Vec<String>,
+    },
+}
+
+impl<'d> Checker<'d> {
+    /// Expands `object#relation` one level into the tree of its rewrite,
+    /// like OpenFGA's Expand endpoint. The tree has the shape of the
+    /// rewrite in the model's JSON.
+    pub fn expand(&self, object: &str, relation: &str) -> EvalResult<UsersetTree> {
+        self.validate_contextual()?;
+        let rel = self.relation(object, relation)?;
+        let name = format!("{object}#{relation}");
+        let node = |kind| Node {
+            name: name.clone(),
+            kind,
+        };
+
+        let mut positive = Vec::new();
+        let mut intersection = Vec::new();
+        let mut negative = Vec::new();
+        for alias in &rel.aliases {
+            let leaf = node(NodeKind::Leaf(self.expand_alias(object, relation, alias)));
+            match alias.kind {
+                AliasKind::This | AliasKind::Named(_) => positive.push(leaf),
+                AliasKind::Intersection(_) => intersection.push(leaf),
+                AliasKind::Negative(_) => negative.push(leaf),
+            }
+        }
+
+        let mut root = match positive.len() {
+            1 => positive.remove(0),
+            _ => node(NodeKind::Union(positive)),
+        };
+        if !intersection.is_empty() {
+            intersection.insert(0, root);
+            root = node(NodeKind::Intersection(intersection));
+        }
+        for subtract in negative {
+            root = node(NodeKind::Difference {
+                base: Box::new(root),
+                subtract: Box::new(subtract),
+            });
+        }
+        Ok(UsersetTree { root })
+    }
+
+    fn expand_alias(&self, object: &str, relation: &str, alias: &Alias) -> Leaf {
+        match (alias.kind.relation(), &alias.parent) {
+            (None, _) => Leaf::Users(self.users(object, relation).cloned().collect()),
+            (Some(name), None) => Leaf::Computed(format!("{object}#{name}")),
+            (Some(name), Some(parent)) => {
+                let computed = self
+                    .users(object, parent)
+                    .filter_map(|user| match parse_user(user) {
+                        Ok(User::Object(parent_object)) => Some(format!("{parent_object}#{name}")),
+                        _ => None,
+                    })
+                    .collect();
+                Leaf::TupleToUserset {
+                    tupleset: format!("{object}#{parent}"),
+                    computed,
+                }
+            }
+        }
+    }
+}
+
+impl UsersetTree {
+    /// Transforms the tree into the JSON returned by OpenFGA's Expand endpoint.
+    pub fn serialize(&self) -> String {
+        json!({ "tree": { "root": self.root.to_json() } }).to_string()
+    }
+}
+
+impl Node {
+    fn to_json(&self) -> Value {
+        let mut obj = json!({ "name": self.name });
+        match &self.kind {
+            NodeKind::Leaf(leaf) => obj["leaf"] = leaf.to_json(),
+            NodeKind::Union(nodes) => {
+                let nodes: Vec<Value> = nodes.iter().map(Node::to_json).collect();
+                obj["union"] = json!({ "nodes": nodes });
+            }
+            NodeKind::Intersection(nodes) => {
+                let nodes: Vec<Value> = nodes.iter().map(Node::to_json).collect();
+                obj["intersection"] = json!({ "nodes": nodes });
+            }
+            NodeKind::Difference { base, subtract } => {
+                obj["difference"] = json!({
+                    "base": base.to_json(),
+                    "subtract": subtract.to_json(),
+                });
+            }
+        }
+        obj
+    }
+}
+
+impl Leaf {
+    fn to_json(&self) -> Value {
+        match self {
+            Leaf::Users(users) => json!({ "users": { "users": users } }),
+            Leaf::Computed(userset) => json!({ "computed": { "userset": userset } }),
+            Leaf::TupleToUserset { tupleset, computed } => {
+                let computed: Vec<Value> = computed
+                    .iter()
+                    .map(|userset| json!({ "userset": userset }))
+                    .collect();
+                json!({
+                    "tupleToUserset": {
+                        "tupleset": tupleset,
+                        "computed": computed,
+                    }
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::TupleStore;
+    use crate::Parser;
+
+    const MODEL: &str = "type user
+type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define blocked as self
+    define editor as self
+    define viewer as self or editor or viewer from parent but not blocked";
+
+    fn store() -> TupleStore {
+        let mut tuples = TupleStore::new();
+        tuples.add("document:1", "parent", "folder:x");
+        tuples.add("document:1", "editor", "user:dan");
+        tuples.add("document:1", "viewer", "user:anne");
+        tuples.add("document:1", "viewer", "group:eng#member");
+        tuples
+    }
+
+    #[test]
+    fn expands_single_leaf() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let tree = Checker::new(&doc, &tuples)
+            .expand("document:1", "editor")
+            .unwrap();
+        assert_eq!(
+            tree.root,
+            Node {
+                name: "document:1#editor".into(),
+                kind: NodeKind::Leaf(Leaf::Users(vec!["user:dan".into()])),
+            }
+        );
+    }
+
+    #[test]
+    fn serializes_like_openfga() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let tree = Checker::new(&doc, &tuples)
+            .expand("document:1", "viewer")
+            .unwrap();
+
+        let exp = json!({
+            "tree": {
+                "root": {
+                    "name": "document:1#viewer",
+                    "difference": {
+                        "base": {
+                            "name": "document:1#viewer",
+                            "union": {
+                                "nodes": [
+                                    {
+                                        "name": "document:1#viewer",
+                                        "leaf": {
+                                            "users": {
+                                                "users": ["user:anne", "group:eng#member"]
+                                            }
+                                        }
+                                    },
+                                    {
+                                        "name": "document:1#viewer",
+                                        "leaf": {
+                                            "computed": { "userset": "document:1#editor" }
+                                        }
+                                    },
+                                    {
+                                        "name": "document:1#viewer",
+                                        "leaf": {
+                                            "tupleToUserset": {
+                                                "tupleset": "document:1#parent",
+                                                "computed": [{ "userset": "folder:x#viewer" }]
+                                            }
+                                        }
+                                    }
+                                ]
+                            }
+                        },
+                        "subtract": {
+                            "name": "document:1#viewer",
+                            "leaf": {
+                                "computed": { "userset": "document:1#blocked" }
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        let res: Value = serde_json::from_str(&tree.serialize()).unwrap();
+        assert_eq!(exp, res);
+    }
+
+    /// The operators of a userset tree or of a rewrite in the model's
+    /// JSON, with every leaf as `leaf`.
+    fn shape(value: &Value) -> Value {
+        let obj = value.as_object().unwrap();
+        for op in ["union", "intersection"] {
+            if let Some(children) = obj.get(op) {
+                let children = children.get("nodes").or(children.get("child")).unwrap();
+                let children: Vec<Value> = children.as_array().unwrap().iter().map(shape).collect();
+                return json!({ op: children });
+            }
+        }
+        match obj.get("difference") {
+            Some(diff) => json!({ "difference": [shape(&diff["base"]), shape(&diff["subtract"])] }),
+            None => json!("leaf"),
+        }
+    }
+
+    #[test]
+    fn mirrors_model_json() {
+        let doc = Parser::new(
+            "type user
+type document
+  relations
+    define parent as self
+    define allowed as self
+    define blocked as self
+    define editor as self
+    define viewer as self and editor and allowed but not blocked but not blocked from parent",
+        )
+        .parse_document()
+        .unwrap();
+        let tuples = store();
+        let tree = Checker::new(&doc, &tuples)
+            .expand("document:1", "viewer")
+            .unwrap();
+        let tree: Value = serde_json::from_str(&tree.serialize()).unwrap();
+        let model: Value =
+            serde_json::from_str(&crate::json::JsonTransformer::new(&doc).serialize()).unwrap();
+        let rewrite = &model["type_definitions"][1]["relations"]["viewer"];
+
+        assert_eq!(shape(&tree["tree"]["root"]), shape(rewrite));
+        assert_eq!(
+            shape(rewrite),
+            json!({ "difference": [
+                { "difference": [
+                    { "intersection": ["leaf", "leaf", "leaf"] },
+                    "leaf"
+                ] },
+                "leaf"
+            ] })
+        );
+    }
+}
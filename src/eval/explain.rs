@@ -0,0 +1,317 @@
+use std::fmt::Display;
+
+use serde_json::{json, Value};
+
+use super::{Checker, EvalResult};
+
+/// Why a check was allowed or denied, as returned by
+/// [Checker::explain](crate::eval::Checker::explain).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Explanation {
+    pub allowed: bool,
+    /// Every relation, rewrite and tuple consulted, in the order tried.
+    pub trace: Step,
+}
+
+/// A single step of resolving a check. `allowed` is whether the step
+/// matched the user; for a `but not` rewrite that means the user is excluded.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Step {
+    pub kind: StepKind,
+    pub allowed: bool,
+    pub children: Vec<Step>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StepKind {
+    /// Resolving `object#relation`.
+    Relation { object: String, relation: String },
+    /// Trying one alias of a relation, as written in the DSL.
+    Rewrite {
+        rewrite: String,
+        tupleset: Option<String>,
+        negative: bool,
+    },
+    /// Following the tuple `object#relation@user`.
+    Tuple {
+        object: String,
+        relation: String,
+        user: String,
+    },
+}
+
+/// Records [Steps](crate::eval::Step) while resolving, when enabled.
+#[derive(Debug, Default)]
+pub(super) struct Trace {
+    enabled: bool,
+    stack: Vec<Step>,
+    root: Option<Step>,
+}
+
+impl Trace {
+    fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    pub(super) fn enter(&mut self, kind: impl FnOnce() -> StepKind) {
+        if self.enabled {
+            self.stack.push(Step {
+                kind: kind(),
+                allowed: false,
+                children: Vec::new(),
+            });
+        }
+    }
+
+    /// Finishes the current step with its result, passing the result on.
+    pub(super) fn exit(&mut self, res: EvalResult<bool>) -> EvalResult<bool> {
+        if let Some(mut step) = self.stack.pop().filter(|_| self.enabled) {
+            step.allowed = res == Ok(true);
+            match self.stack.last_mut() {
+                Some(parent) => parent.children.push(step),
+                None => self.root = Some(step),
+            }
+        }
+        res
+    }
+}
+
+impl StepKind {
+    pub(super) fn tuple(object: &str, relation: &str, user: &str) -> Self {
+        StepKind::Tuple {
+            object: object.to_string(),
+            relation: relation.to_string(),
+            user: user.to_string(),
+        }
+    }
+}
+
+impl<'d> Checker<'d> {
+    /// Checks whether `user` has `relation` with `object`, recording
+    /// every step taken.
+    pub fn explain(&self, object: &str, relation: &str, user: &str) -> EvalResult<Explanation> {
+        let mut query = self.query(object, relation, user, Trace::enabled())?;
+        let allowed = self.resolve(object, relation, 0, &mut query)?;
+        let allowed = query.finish(allowed)?;
+        let trace = query
+            .trace
+            .root
+            .expect("a traced query records its root step");
+        Ok(Explanation { allowed, trace })
+    }
+}
+
+impl Explanation {
+    /// The derivation of an allowed check, from the user to the checked
+    /// relation, e.g. `user:anne -> group:eng#member -> document:1#viewer`.
+    pub fn path(&self) -> Option<Vec<String>> {
+        if !self.allowed {
+            return None;
+        }
+        let mut path = Vec::new();
+        collect_path(&self.trace, None, &mut path);
+        path.reverse();
+        Some(path)
+    }
+
+    /// Transforms the explanation into a JSON string.
+    pub fn serialize(&self) -> String {
+        json!({
+            "allowed": self.allowed,
+            "path": self.path(),
+            "trace": self.trace.to_json(),
+        })
+        .to_string()
+    }
+}
+
+fn collect_path(step: &Step, via: Option<&str>, path: &mut Vec<String>) {
+    let mut next = step
+        .children
+        .iter()
+        .filter(|c| c.allowed && !matches!(c.kind, StepKind::Rewrite { negative: true, .. }));
+    match &step.kind {
+        StepKind::Relation { object, relation } => {
+            match via {
+                Some(tupleset) => path.push(format!("{object}#{relation} (via {tupleset})")),
+                None => path.push(format!("{object}#{relation}")),
+            }
+            if let Some(child) = next.next() {
+                collect_path(child, None, path);
+            }
+        }
+        StepKind::Rewrite { tupleset, .. } => {
+            if let Some(child) = next.next() {
+                collect_path(child, tupleset.as_deref(), path);
+            }
+        }
+        StepKind::Tuple { user, .. } => match next.next() {
+            Some(child) => collect_path(child, via, path),
+            None => path.push(user.clone()),
+        },
+    }
+}
+
+impl Step {
+    fn to_json(&self) -> Value {
+        let mut obj = match &self.kind {
+            StepKind::Relation { object, relation } => json!({
+                "type": "relation",
+                "object": object,
+                "relation": relation,
+            }),
+            StepKind::Rewrite {
+                rewrite,
+                tupleset,
+                negative,
+            } => json!({
+                "type": "rewrite",
+                "rewrite": rewrite,
+                "tupleset": tupleset,
+                "negative": negative,
+            }),
+            StepKind::Tuple { .. } => json!({
+                "type": "tuple",
+                "tuple": self.kind.to_string(),
+            }),
+        };
+        let children: Vec<Value> = self.children.iter().map(Step::to_json).collect();
+        obj["allowed"] = self.allowed.into();
+        obj["children"] = children.into();
+        obj
+    }
+
+    fn render(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
+        let mark = if self.allowed { '✓' } else { '✗' };
+        writeln!(f, "{:indent$}{mark} {}", "", self.kind)?;
+        for child in &self.children {
+            child.render(f, indent + 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for StepKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepKind::Relation { object, relation } => write!(f, "{object}#{relation}"),
+            StepKind::Rewrite {
+                rewrite,
+                negative: true,
+                ..
+            } => write!(f, "but not {rewrite}"),
+            StepKind::Rewrite { rewrite, .. } => write!(f, "{rewrite}"),
+            StepKind::Tuple {
+                object,
+                relation,
+                user,
+            } => write!(f, "{object}#{relation}@{user}"),
+        }
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path() {
+            Some(path) => writeln!(f, "allowed: {}", path.join(" -> "))?,
+            None => writeln!(f, "denied: {}", self.trace.kind)?,
+        }
+        self.trace.render(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::TupleStore;
+    use crate::Parser;
+
+    const MODEL: &str = "type user
+type group
+  relations
+    define member as self
+type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define blocked as self
+    define viewer as self or viewer from parent but not blocked";
+
+    fn store() -> TupleStore {
+        let mut tuples = TupleStore::new();
+        tuples.add("group:eng", "member", "user:anne");
+        tuples.add("group:eng", "member", "user:bob");
+        tuples.add("folder:x", "viewer", "group:eng#member");
+        tuples.add("document:y", "parent", "folder:x");
+        tuples.add("document:y", "blocked", "user:bob");
+        tuples
+    }
+
+    #[test]
+    fn explains_allowed_path() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let explanation = Checker::new(&doc, &tuples)
+            .explain("document:y", "viewer", "user:anne")
+            .unwrap();
+
+        assert!(explanation.allowed);
+        assert_eq!(
+            explanation.path().unwrap(),
+            vec![
+                "user:anne",
+                "group:eng#member",
+                "folder:x#viewer (via parent)",
+                "document:y#viewer",
+            ]
+        );
+        assert_eq!(
+            explanation.to_string(),
+            "allowed: user:anne -> group:eng#member -> folder:x#viewer (via parent) -> document:y#viewer
+✓ document:y#viewer
+  ✗ self
+  ✓ viewer from parent
+    ✓ document:y#parent@folder:x
+      ✓ folder:x#viewer
+        ✓ self
+          ✓ folder:x#viewer@group:eng#member
+            ✓ group:eng#member
+              ✓ self
+                ✓ group:eng#member@user:anne
+  ✗ but not blocked
+    ✗ document:y#blocked
+      ✗ self
+        ✗ document:y#blocked@user:bob
+"
+        );
+    }
+
+    #[test]
+    fn explains_denied_branches() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let explanation = Checker::new(&doc, &tuples)
+            .explain("document:y", "viewer", "user:bob")
+            .unwrap();
+
+        assert!(!explanation.allowed);
+        assert_eq!(explanation.path(), None);
+
+        let res: Value = serde_json::from_str(&explanation.serialize()).unwrap();
+        assert_eq!(res["allowed"], json!(false));
+        assert_eq!(res["path"], Value::Null);
+        let excluded = &res["trace"]["children"][2];
+        assert_eq!(excluded["rewrite"], json!("blocked"));
+        assert_eq!(excluded["negative"], json!(true));
+        assert_eq!(excluded["allowed"], json!(true));
+        assert_eq!(
+            excluded["children"][0]["children"][0]["children"][0]["tuple"],
+            json!("document:y#blocked@user:bob")
+        );
+    }
+}
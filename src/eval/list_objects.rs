@@ -0,0 +1,285 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Instant;
+
+use super::{object_type, parse_user, Checker, EvalResult, User};
+use crate::ast::AliasKind;
+
+/// Iterator over the objects of a type a user has a relation with,
+/// created by [Checker::list_objects](crate::eval::Checker::list_objects).
+///
+/// Candidates are found by walking rewrites in reverse from the user,
+/// then confirmed with a check so `but not` exclusions are honored.
+pub struct ListObjects<'c, 'd> {
+    checker: &'c Checker<'d>,
+    ty: String,
+    relation: String,
+    user: String,
+    /// Usersets `(object, relation)` the user is known to be part of.
+    pending: VecDeque<(String, String)>,
+    visited: HashSet<(String, String)>,
+    candidates: VecDeque<String>,
+    seen: HashSet<String>,
+    found: usize,
+    max_results: Option<usize>,
+    deadline: Option<Instant>,
+}
+
+impl<'d> Checker<'d> {
+    /// Lists the objects of type `ty` that `user` has `relation` with.
+    pub fn list_objects(
+        &self,
+        ty: &str,
+        relation: &str,
+        user: &str,
+    ) -> EvalResult<ListObjects<'_, 'd>> {
+        self.validate_contextual()?;
+        let parsed = parse_user(user)?;
+        self.type_relation(ty, relation)?;
+
+        let mut lister = ListObjects {
+            checker: self,
+            ty: ty.to_string(),
+            relation: relation.to_string(),
+            user: user.to_string(),
+            pending: VecDeque::new(),
+            visited: HashSet::new(),
+            candidates: VecDeque::new(),
+            seen: HashSet::new(),
+            found: 0,
+            max_results: None,
+            deadline: None,
+        };
+
+        if let User::Userset(object, rel) = parsed {
+            lister.reach(object, rel);
+        }
+        lister.reach_direct(user);
+        if let User::Object(object) = parsed {
+            let wildcard = format!("{}:*", object_type(object)?);
+            lister.reach_direct(&wildcard);
+        }
+        Ok(lister)
+    }
+}
+
+impl<'c, 'd> ListObjects<'c, 'd> {
+    /// Stops after yielding `max_results` objects.
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Stops yielding objects once `deadline` has passed.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    fn reach(&mut self, object: &str, relation: &str) {
+        let key = (object.to_string(), relation.to_string());
+        if self.visited.insert(key.clone()) {
+            self.pending.push_back(key);
+        }
+    }
+
+    /// Reaches the assignable relations `user` is directly related through.
+    fn reach_direct(&mut self, user: &str) {
+        let checker = self.checker;
+        for (object, relation) in checker.objects(user) {
+            let assignable = checker
+                .relation(object, relation)
+                .is_ok_and(|r| r.is_assignable());
+            if assignable {
+                self.reach(object, relation);
+            }
+        }
+    }
+
+    /// Follows every rewrite that grants access through `object#relation`.
+    fn expand(&mut self, object: &str, relation: &str) {
+        let Ok(object_ty) = object_type(object) else {
+            return;
+        };
+        if object_ty == self.ty && relation == self.relation && self.seen.insert(object.into()) {
+            self.candidates.push_back(object.to_string());
+        }
+
+        self.reach_direct(&format!("{object}#{relation}"));
+
+        let checker = self.checker;
+        let doc = checker.doc;
+        for ty in &doc.types {
+            for rel in &ty.relations {
+                for alias in &rel.aliases {
+                    let AliasKind::Named(name) = &alias.kind else {
+                        continue;
+                    };
+                    if name != relation {
+                        continue;
+                    }
+                    match &alias.parent {
+                        None if ty.kind == object_ty => self.reach(object, &rel.kind),
+                        None => {}
+                        Some(parent) => {
+                            for (child, tupleset) in checker.objects(object) {
+                                let is_child = object_type(child).is_ok_and(|t| t == ty.kind);
+                                if tupleset == parent && is_child {
+                                    self.reach(child, &rel.kind);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.max_results.is_some_and(|max| self.found >= max)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+impl Iterator for ListObjects<'_, '_> {
+    type Item = EvalResult<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.is_done() {
+            if let Some(candidate) = self.candidates.pop_front() {
+                match self.checker.check(&candidate, &self.relation, &self.user) {
+                    Ok(true) => {
+                        self.found += 1;
+                        return Some(Ok(candidate));
+                    }
+                    Ok(false) => continue,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            let (object, relation) = self.pending.pop_front()?;
+            self.expand(&object, &relation);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{EvalError, TupleStore};
+    use crate::Parser;
+    use std::time::Duration;
+
+    const MODEL: &str = "type user
+type group
+  relations
+    define member as self
+type folder
+  relations
+    define parent as self
+    define viewer as self or viewer from parent
+type document
+  relations
+    define parent as self
+    define blocked as self
+    define viewer as self or viewer from parent but not blocked";
+
+    fn store() -> TupleStore {
+        let mut tuples = TupleStore::new();
+        tuples.add("group:eng", "member", "user:anne");
+        tuples.add("folder:root", "viewer", "group:eng#member");
+        tuples.add("folder:x", "parent", "folder:root");
+        tuples.add("document:1", "parent", "folder:x");
+        tuples.add("document:2", "parent", "folder:root");
+        tuples.add("document:3", "viewer", "user:anne");
+        tuples.add("document:4", "viewer", "user:*");
+        tuples.add("document:5", "parent", "folder:x");
+        tuples.add("document:5", "blocked", "user:anne");
+        tuples
+    }
+
+    fn sorted(objects: impl Iterator<Item = EvalResult<String>>) -> Vec<String> {
+        let mut objects: Vec<String> = objects.map(Result::unwrap).collect();
+        objects.sort();
+        objects
+    }
+
+    #[test]
+    fn lists_through_rewrites() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples);
+
+        let res = sorted(
+            checker
+                .list_objects("document", "viewer", "user:anne")
+                .unwrap(),
+        );
+        assert_eq!(
+            res,
+            vec!["document:1", "document:2", "document:3", "document:4"]
+        );
+
+        let res = sorted(
+            checker
+                .list_objects("folder", "viewer", "user:anne")
+                .unwrap(),
+        );
+        assert_eq!(res, vec!["folder:root", "folder:x"]);
+
+        let res = sorted(
+            checker
+                .list_objects("document", "viewer", "user:bob")
+                .unwrap(),
+        );
+        assert_eq!(res, vec!["document:4"]);
+    }
+
+    #[test]
+    fn lists_for_usersets() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples);
+
+        let res = sorted(
+            checker
+                .list_objects("folder", "viewer", "group:eng#member")
+                .unwrap(),
+        );
+        assert_eq!(res, vec!["folder:root", "folder:x"]);
+    }
+
+    #[test]
+    fn limits_results() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples);
+
+        let res = checker
+            .list_objects("document", "viewer", "user:anne")
+            .unwrap()
+            .with_max_results(2);
+        assert_eq!(res.count(), 2);
+
+        let res = checker
+            .list_objects("document", "viewer", "user:anne")
+            .unwrap()
+            .with_deadline(Instant::now() - Duration::from_secs(1));
+        assert_eq!(res.count(), 0);
+    }
+
+    #[test]
+    fn unknown_relation() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples);
+        assert_eq!(
+            checker.list_objects("document", "owner", "user:anne").err(),
+            Some(EvalError::UnknownRelation(
+                "document".into(),
+                "owner".into()
+            ))
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::{BTreeSet, HashSet};
+
+use super::{object_type, parse_user, Checker, EvalError, EvalResult, User};
+use crate::ast::AliasKind;
+
+/// Restricts [Checker::list_users](crate::eval::Checker::list_users) to users
+/// of a type (`user`) or to usersets of a type and relation (`group#member`).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UserFilter {
+    pub ty: String,
+    pub relation: Option<String>,
+}
+
+/// Users with a relation on an object, as returned by
+/// [Checker::list_users](crate::eval::Checker::list_users).
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Users {
+    /// Concrete users, e.g. `user:anne`.
+    pub objects: Vec<String>,
+    /// Usersets, e.g. `group:eng#member`.
+    pub usersets: Vec<String>,
+    /// Wildcards, e.g. `user:*`.
+    pub wildcards: Vec<String>,
+    /// Candidates that could not be checked, e.g. for missing context.
+    pub errors: Vec<(String, EvalError)>,
+}
+
+impl From<&str> for UserFilter {
+    fn from(s: &str) -> Self {
+        match s.split_once('#') {
+            Some((ty, relation)) => UserFilter {
+                ty: ty.into(),
+                relation: Some(relation.into()),
+            },
+            None => UserFilter {
+                ty: s.into(),
+                relation: None,
+            },
+        }
+    }
+}
+
+impl UserFilter {
+    fn matches(&self, user: &User) -> bool {
+        match (user, &self.relation) {
+            (User::Object(object), None) => object_type(object).is_ok_and(|t| t == self.ty),
+            (User::Wildcard(ty), None) => *ty == self.ty,
+            (User::Userset(object, rel), Some(relation)) => {
+                rel == relation && object_type(object).is_ok_and(|t| t == self.ty)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'d> Checker<'d> {
+    /// Lists the users matching any of `filters` that have `relation`
+    /// with `object`. A candidate that fails to check is reported in
+    /// [errors](crate::eval::Users::errors) without failing the others.
+    pub fn list_users(
+        &self,
+        object: &str,
+        relation: &str,
+        filters: &[UserFilter],
+    ) -> EvalResult<Users> {
+        self.validate_contextual()?;
+        self.relation(object, relation)?;
+
+        let mut candidates = BTreeSet::new();
+        let mut visited = HashSet::new();
+        self.collect_users(object, relation, filters, &mut candidates, &mut visited);
+
+        let mut users = Users::default();
+        for candidate in candidates {
+            match self.check(object, relation, &candidate) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    users.errors.push((candidate, e));
+                    continue;
+                }
+            }
+            match parse_user(&candidate)? {
+                User::Object(_) => users.objects.push(candidate),
+                User::Userset(..) => users.usersets.push(candidate),
+                User::Wildcard(_) => users.wildcards.push(candidate),
+            }
+        }
+        Ok(users)
+    }
+
+    /// Collects every user reachable through the rewrites of
+    /// `object#relation` that matches a filter, ignoring `and` and
+    /// `but not`, which only narrow the users down.
+    fn collect_users(
+        &self,
+        object: &str,
+        relation: &str,
+        filters: &[UserFilter],
+        candidates: &mut BTreeSet<String>,
+        visited: &mut HashSet<(String, String)>,
+    ) {
+        if !visited.insert((object.to_string(), relation.to_string())) {
+            return;
+        }
+        let Ok(rel) = self.relation(object, relation) else {
+            return;
+        };
+
+        for alias in &rel.aliases {
+            match (&alias.kind, &alias.parent) {
+                (AliasKind::This, _) => {
+                    for direct in self.users(object, relation) {
+                        let Ok(user) = parse_user(direct) else {
+                            continue;
+                        };
+                        if filters.iter().any(|f| f.matches(&user)) {
+                            candidates.insert(direct.clone());
+                        }
+                        if let User::Userset(set_object, set_relation) = user {
+                            self.collect_users(
+                                set_object,
+                                set_relation,
+                                filters,
+                                candidates,
+                                visited,
+                            );
+                        }
+                    }
+                }
+                (AliasKind::Named(name), None) => {
+                    self.collect_users(object, name, filters, candidates, visited);
+                }
+                (AliasKind::Named(name), Some(parent)) => {
+                    for parent_object in self.users(object, parent) {
+                        if let Ok(User::Object(parent_object)) = parse_user(parent_object) {
+                            self.collect_users(parent_object, name, filters, candidates, visited);
+                        }
+                    }
+                }
+                (AliasKind::Intersection(_) | AliasKind::Negative(_), _) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::TupleStore;
+    use crate::tuple::TupleKey;
+    use crate::Parser;
+
+    const MODEL: &str = "type user
+type group
+  relations
+    define member as self
+type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define blocked as self
+    define editor as self
+    define viewer as self or editor or viewer from parent but not blocked";
+
+    fn store() -> TupleStore {
+        let mut tuples = TupleStore::new();
+        tuples.add("group:eng", "member", "user:anne");
+        tuples.add("group:eng", "member", "user:bob");
+        tuples.add("folder:x", "viewer", "group:eng#member");
+        tuples.add("folder:x", "viewer", "user:carl");
+        tuples.add("document:roadmap", "parent", "folder:x");
+        tuples.add("document:roadmap", "editor", "user:dan");
+        tuples.add("document:roadmap", "viewer", "user:*");
+        tuples.add("document:roadmap", "blocked", "user:bob");
+        tuples
+    }
+
+    #[test]
+    fn lists_users_by_kind() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples);
+
+        let users = checker
+            .list_users("document:roadmap", "viewer", &["user".into()])
+            .unwrap();
+        assert_eq!(
+            users,
+            Users {
+                objects: vec!["user:anne".into(), "user:carl".into(), "user:dan".into()],
+                usersets: Vec::new(),
+                wildcards: vec!["user:*".into()],
+                errors: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn lists_usersets() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples);
+
+        let users = checker
+            .list_users("document:roadmap", "viewer", &["group#member".into()])
+            .unwrap();
+        assert_eq!(users.usersets, vec!["group:eng#member".to_string()]);
+        assert!(users.objects.is_empty());
+
+        let users = checker
+            .list_users("document:roadmap", "editor", &["user".into()])
+            .unwrap();
+        assert_eq!(users.objects, vec!["user:dan".to_string()]);
+    }
+
+    #[test]
+    fn reports_candidates_that_fail() {
+        let model = format!(
+            "{MODEL}\ncondition in_office(ip: ipaddress, cidr: string) {{ ip.in_cidr(cidr) }}"
+        );
+        let doc = Parser::new(&model).parse_document().unwrap();
+        let mut tuples = store();
+        tuples
+            .insert(&TupleKey::parse("document:roadmap#editor@user:erin with in_office").unwrap());
+        let checker = Checker::new(&doc, &tuples);
+
+        let users = checker
+            .list_users("document:roadmap", "editor", &["user".into()])
+            .unwrap();
+        assert_eq!(users.objects, vec!["user:dan".to_string()]);
+        assert_eq!(
+            users.errors,
+            vec![(
+                "user:erin".to_string(),
+                EvalError::MissingContext(vec!["cidr".into(), "ip".into()])
+            )]
+        );
+    }
+
+    #[test]
+    fn unknown_relation() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples);
+        assert_eq!(
+            checker.list_users("folder:x", "editor", &["user".into()]),
+            Err(EvalError::UnknownRelation("folder".into(), "editor".into()))
+        );
+    }
+}
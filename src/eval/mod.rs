@@ -0,0 +1,949 @@
+use std::cell::OnceCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+
+use serde_json::{Map, Value};
+
+use crate::ast::{Alias, AliasKind, Document, Relation};
+use crate::condition::{ConditionOutcome, Conditions};
+use crate::tuple::{TupleCondition, TupleKey, User};
+use crate::validate::{DirectlyRelated, TupleValidationError, Validator};
+
+mod expand;
+mod explain;
+mod list_objects;
+mod list_users;
+
+use explain::Trace;
+
+pub use expand::{Leaf, Node, NodeKind, UsersetTree};
+pub use explain::{Explanation, Step, StepKind};
+pub use list_objects::ListObjects;
+pub use list_users::{UserFilter, Users};
+
+/// Result type for evaluating a model.
+pub type EvalResult<T> = Result<T, EvalError>;
+
+/// Default limit on how many rewrites a single query may follow,
+/// matching OpenFGA's default resolve depth.
+pub const DEFAULT_MAX_DEPTH: usize = 25;
+
+/// Enumerated error type for evaluating a model.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EvalError {
+    /// An object or user that is not of the form `type:id`.
+    InvalidObject(String),
+    UnknownType(String),
+    UnknownRelation(String, String),
+    /// Resolution followed more rewrites than the configured limit.
+    DepthExceeded,
+    /// A contextual tuple that does not fit the model.
+    InvalidContextualTuple(String, Box<TupleValidationError>),
+    /// A tuple refers to a condition the model does not declare.
+    UnknownCondition(String),
+    /// Evaluating the named condition failed.
+    Condition(String, String),
+    /// The query was denied, but conditional tuples lacked these context
+    /// parameters and could have allowed it.
+    MissingContext(Vec<String>),
+    /// The query was denied, but the relation `object#relation` depends
+    /// on itself through a `but not`, which has no answer.
+    Cycle(String, String),
+}
+
+/// In-memory set of relationship tuples, each relating a `user`
+/// to an `object` through a `relation`.
+///
+/// Users are objects (`user:anne`), usersets (`group:eng#member`)
+/// or wildcards (`user:*`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TupleStore {
+    tuples: HashMap<(String, String), Vec<String>>,
+    by_user: HashMap<String, Vec<(String, String)>>,
+    conditions: HashMap<(String, String, String), TupleCondition>,
+}
+
+/// State of a single query against a [Checker](crate::eval::Checker).
+struct Query<'q> {
+    user: &'q str,
+    /// Relations being resolved on the current path, to break cycles.
+    visited: HashSet<(String, String)>,
+    trace: Trace,
+    /// Whether the current rewrite is under an odd number of `but not`s.
+    negated: bool,
+    /// Context parameters missing to evaluate conditional tuples.
+    missing: BTreeSet<String>,
+    /// A relation reached again under `but not` while resolving itself.
+    cycle: Option<(String, String)>,
+}
+
+/// Answers whether a user has a relation with an object, by
+/// interpreting the rewrites of a [Document](crate::ast::Document)
+/// over a [TupleStore](crate::eval::TupleStore).
+pub struct Checker<'d> {
+    doc: &'d Document,
+    tuples: &'d TupleStore,
+    /// Tuples overlaid on `tuples` for the queries of this checker only.
+    contextual: TupleStore,
+    contextual_keys: Vec<TupleKey>,
+    /// Whether the contextual tuples fit the model, checked on first use.
+    validated: OnceCell<EvalResult<()>>,
+    related: Option<&'d DirectlyRelated>,
+    /// The conditions declared in the model.
+    conditions: Conditions,
+    /// Request context, merged under the context of conditional tuples.
+    context: Map<String, Value>,
+    max_depth: usize,
+}
+
+impl TupleStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the tuple `object#relation@user`.
+    pub fn add(&mut self, object: &str, relation: &str, user: &str) {
+        let users = self
+            .tuples
+            .entry((object.to_string(), relation.to_string()))
+            .or_default();
+        if !users.iter().any(|u| u == user) {
+            users.push(user.to_string());
+            self.by_user
+                .entry(user.to_string())
+                .or_default()
+                .push((object.to_string(), relation.to_string()));
+        }
+    }
+
+    /// Adds the tuple `object#relation@user`, which only applies
+    /// when `condition` is met.
+    pub fn add_with_condition(
+        &mut self,
+        object: &str,
+        relation: &str,
+        user: &str,
+        condition: TupleCondition,
+    ) {
+        self.add(object, relation, user);
+        self.conditions.insert(
+            (object.to_string(), relation.to_string(), user.to_string()),
+            condition,
+        );
+    }
+
+    /// Adds `tuple`, with its condition if it has one.
+    pub fn insert(&mut self, tuple: &TupleKey) {
+        match &tuple.condition {
+            Some(condition) => self.add_with_condition(
+                &tuple.object,
+                &tuple.relation,
+                &tuple.user,
+                condition.clone(),
+            ),
+            None => self.add(&tuple.object, &tuple.relation, &tuple.user),
+        }
+    }
+
+    /// The condition the tuple `object#relation@user` is subject to.
+    pub fn condition(&self, object: &str, relation: &str, user: &str) -> Option<&TupleCondition> {
+        self.conditions
+            .get(&(object.to_string(), relation.to_string(), user.to_string()))
+    }
+
+    /// Users directly related to `object` through `relation`.
+    pub fn users(&self, object: &str, relation: &str) -> &[String] {
+        self.tuples
+            .get(&(object.to_string(), relation.to_string()))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Objects and relations `user` is directly related to.
+    pub fn objects(&self, user: &str) -> &[(String, String)] {
+        self.by_user
+            .get(user)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Iterates every tuple as `(object, relation, user)`.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        self.tuples.iter().flat_map(|((object, relation), users)| {
+            users
+                .iter()
+                .map(move |user| (object.as_str(), relation.as_str(), user.as_str()))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tuples.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Parses the user of a tuple or query.
+pub(crate) fn parse_user(user: &str) -> EvalResult<User<'_>> {
+    User::parse(user).map_err(|_| EvalError::InvalidObject(user.to_string()))
+}
+
+/// The type of an object of the form `type:id`.
+pub(crate) fn object_type(object: &str) -> EvalResult<&str> {
+    match object.split_once(':') {
+        Some((ty, id)) if !ty.is_empty() && !id.is_empty() => Ok(ty),
+        _ => Err(EvalError::InvalidObject(object.to_string())),
+    }
+}
+
+impl<'d> Checker<'d> {
+    pub fn new(doc: &'d Document, tuples: &'d TupleStore) -> Self {
+        Self {
+            doc,
+            tuples,
+            contextual: TupleStore::new(),
+            contextual_keys: Vec::new(),
+            validated: OnceCell::new(),
+            related: None,
+            conditions: Conditions::from_document(doc),
+            context: Map::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Sets the directly related types contextual tuples are validated
+    /// against, as the model itself does not declare them.
+    pub fn with_related_types(mut self, related: &'d DirectlyRelated) -> Self {
+        self.related = Some(related);
+        self
+    }
+
+    /// Overlays `tuples` on the stored tuples for every query of this
+    /// checker, without adding them to the store.
+    ///
+    /// Each tuple is validated against the model and any
+    /// [related types](crate::eval::Checker::with_related_types) when the
+    /// checker is first queried, failing every query with
+    /// [InvalidContextualTuple](crate::eval::EvalError::InvalidContextualTuple)
+    /// if one does not fit.
+    pub fn with_contextual_tuples<'t>(
+        mut self,
+        tuples: impl IntoIterator<Item = &'t TupleKey>,
+    ) -> Self {
+        for tuple in tuples {
+            self.contextual.insert(tuple);
+            self.contextual_keys.push(tuple.clone());
+        }
+        self.validated = OnceCell::new();
+        self
+    }
+
+    /// Sets the request context conditions are evaluated with. Values
+    /// in the context of a conditional tuple take precedence.
+    pub fn with_context(mut self, context: Map<String, Value>) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Sets how many rewrites a single check may follow before
+    /// failing with [DepthExceeded](crate::eval::EvalError::DepthExceeded).
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Whether `user` has `relation` with `object`.
+    pub fn check(&self, object: &str, relation: &str, user: &str) -> EvalResult<bool> {
+        let mut query = self.query(object, relation, user, Trace::default())?;
+        let allowed = self.resolve(object, relation, 0, &mut query)?;
+        query.finish(allowed)
+    }
+
+    /// Users directly related to `object` through `relation`, stored
+    /// or contextual.
+    fn users(&self, object: &str, relation: &str) -> impl Iterator<Item = &String> {
+        let stored = self.tuples.users(object, relation);
+        let contextual = self.contextual.users(object, relation);
+        stored
+            .iter()
+            .chain(contextual.iter().filter(move |u| !stored.contains(u)))
+    }
+
+    /// Objects and relations `user` is directly related to, stored
+    /// or contextual.
+    fn objects(&self, user: &str) -> impl Iterator<Item = &(String, String)> {
+        let stored = self.tuples.objects(user);
+        let contextual = self.contextual.objects(user);
+        stored
+            .iter()
+            .chain(contextual.iter().filter(move |o| !stored.contains(o)))
+    }
+
+    /// Fails if a contextual tuple does not fit the model.
+    fn validate_contextual(&self) -> EvalResult<()> {
+        self.validated
+            .get_or_init(|| {
+                let mut validator = Validator::new(self.doc);
+                if let Some(related) = self.related {
+                    validator = validator.with_related_types(related);
+                }
+                for tuple in &self.contextual_keys {
+                    validator.validate_tuple(tuple).map_err(|e| {
+                        EvalError::InvalidContextualTuple(tuple.to_string(), Box::new(e))
+                    })?;
+                }
+                Ok(())
+            })
+            .clone()
+    }
+
+    fn query<'q>(
+        &self,
+        object: &str,
+        relation: &str,
+        user: &'q str,
+        trace: Trace,
+    ) -> EvalResult<Query<'q>> {
+        self.validate_contextual()?;
+        parse_user(user)?;
+        self.relation(object, relation)?;
+        Ok(Query {
+            user,
+            visited: HashSet::new(),
+            trace,
+            negated: false,
+            missing: BTreeSet::new(),
+            cycle: None,
+        })
+    }
+
+    /// The definition of `relation` on the type of `object`.
+    fn relation(&self, object: &str, relation: &str) -> EvalResult<&'d Relation> {
+        self.type_relation(object_type(object)?, relation)
+    }
+
+    /// The definition of `relation` on the type `ty`.
+    fn type_relation(&self, ty: &str, relation: &str) -> EvalResult<&'d Relation> {
+        self.doc
+            .get_type(ty)
+            .ok_or_else(|| EvalError::UnknownType(ty.to_string()))?
+            .get_relation(relation)
+            .ok_or_else(|| EvalError::UnknownRelation(ty.to_string(), relation.to_string()))
+    }
+
+    fn resolve(
+        &self,
+        object: &str,
+        relation: &str,
+        depth: usize,
+        query: &mut Query,
+    ) -> EvalResult<bool> {
+        query.trace.enter(|| StepKind::Relation {
+            object: object.to_string(),
+            relation: relation.to_string(),
+        });
+        let res = self.resolve_relation(object, relation, depth, query);
+        query.trace.exit(res)
+    }
+
+    fn resolve_relation(
+        &self,
+        object: &str,
+        relation: &str,
+        depth: usize,
+        query: &mut Query,
+    ) -> EvalResult<bool> {
+        if depth >= self.max_depth {
+            return Err(EvalError::DepthExceeded);
+        }
+        let rel = match self.relation(object, relation) {
+            Ok(rel) => rel,
+            // tuple-to-userset targets of other types may not define the relation
+            Err(EvalError::UnknownRelation(..)) if depth > 0 => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        // a relation reached again while resolving itself grants nothing
+        // new, but cannot be assumed not to apply under `but not`
+        let key = (object.to_string(), relation.to_string());
+        if query.visited.contains(&key) {
+            if query.negated {
+                query.cycle.get_or_insert(key);
+            }
+            return Ok(query.negated);
+        }
+        query.visited.insert(key.clone());
+
+        // the union of the aliases, then every `and`, then no `but not`
+        let mut allowed = false;
+        for alias in rel.aliases.iter().filter(|a| is_union(a)) {
+            if self.resolve_alias(object, relation, alias, depth, query)? {
+                allowed = true;
+                break;
+            }
+        }
+        for alias in rel.aliases.iter().filter(|a| is_intersection(a)) {
+            if !allowed {
+                break;
+            }
+            allowed = self.resolve_alias(object, relation, alias, depth, query)?;
+        }
+        for alias in rel.aliases.iter().filter(|a| is_negative(a)) {
+            if !allowed {
+                break;
+            }
+            allowed = !self.resolve_alias(object, relation, alias, depth, query)?;
+        }
+
+        query.visited.remove(&key);
+        Ok(allowed)
+    }
+
+    fn resolve_alias(
+        &self,
+        object: &str,
+        relation: &str,
+        alias: &Alias,
+        depth: usize,
+        query: &mut Query,
+    ) -> EvalResult<bool> {
+        let negative = is_negative(alias);
+        query.trace.enter(|| StepKind::Rewrite {
+            rewrite: alias.to_string(),
+            tupleset: alias.parent.clone(),
+            negative,
+        });
+        query.negated ^= negative;
+        let res = match (alias.kind.relation(), &alias.parent) {
+            (None, _) => self.resolve_direct(object, relation, depth, query),
+            (Some(name), None) => self.resolve(object, name, depth + 1, query),
+            (Some(name), Some(parent)) => self.resolve_tupleset(object, parent, name, depth, query),
+        };
+        query.negated ^= negative;
+        query.trace.exit(res)
+    }
+
+    fn resolve_tupleset(
+        &self,
+        object: &str,
+        tupleset: &str,
+        computed: &str,
+        depth: usize,
+        query: &mut Query,
+    ) -> EvalResult<bool> {
+        for tupleset_user in self.users(object, tupleset) {
+            // only objects can be followed through a tupleset
+            let Ok(User::Object(parent_object)) = parse_user(tupleset_user) else {
+                continue;
+            };
+            query
+                .trace
+                .enter(|| StepKind::tuple(object, tupleset, tupleset_user));
+            let res = match self.tuple_applies(object, tupleset, tupleset_user, query) {
+                Ok(true) => self.resolve(parent_object, computed, depth + 1, query),
+                res => res,
+            };
+            if query.trace.exit(res)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn resolve_direct(
+        &self,
+        object: &str,
+        relation: &str,
+        depth: usize,
+        query: &mut Query,
+    ) -> EvalResult<bool> {
+        let user = query.user;
+        let user_type = object_type(user)?;
+        for direct in self.users(object, relation) {
+            query
+                .trace
+                .enter(|| StepKind::tuple(object, relation, direct));
+            let res = match self.tuple_applies(object, relation, direct, query) {
+                Ok(true) if direct == user => Ok(true),
+                Ok(true) => self.resolve_user(direct, user, user_type, depth, query),
+                res => res,
+            };
+            if query.trace.exit(res)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether the direct user of a tuple grants access to `user`.
+    fn resolve_user(
+        &self,
+        direct: &str,
+        user: &str,
+        user_type: &str,
+        depth: usize,
+        query: &mut Query,
+    ) -> EvalResult<bool> {
+        match parse_user(direct) {
+            Ok(User::Wildcard(ty)) => Ok(ty == user_type && !user.contains('#')),
+            Ok(User::Userset(set_object, set_relation)) => {
+                self.resolve(set_object, set_relation, depth + 1, query)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Whether the tuple `object#relation@user` applies: in the store
+    /// or among the contextual tuples, with its condition, if any, met.
+    fn tuple_applies(
+        &self,
+        object: &str,
+        relation: &str,
+        user: &str,
+        query: &mut Query,
+    ) -> EvalResult<bool> {
+        let mut error = None;
+        for tuples in [self.tuples, &self.contextual] {
+            if !tuples.users(object, relation).iter().any(|u| u == user) {
+                continue;
+            }
+            match self.condition_met(tuples.condition(object, relation, user), query) {
+                Ok(true) => return Ok(true),
+                Ok(false) => {}
+                Err(e) => error = Some(e),
+            }
+        }
+        error.map_or(Ok(false), Err)
+    }
+
+    /// Whether `condition`, if any, is met. A condition missing context
+    /// is taken to deny access, which under `but not` means it applies.
+    fn condition_met(
+        &self,
+        condition: Option<&TupleCondition>,
+        query: &mut Query,
+    ) -> EvalResult<bool> {
+        let Some(condition) = condition else {
+            return Ok(true);
+        };
+        let declared = match self.conditions.get(&condition.name) {
+            Some(Ok(declared)) => declared,
+            Some(Err(e)) => {
+                return Err(EvalError::Condition(condition.name.clone(), e.to_string()))
+            }
+            None => return Err(EvalError::UnknownCondition(condition.name.clone())),
+        };
+
+        let mut context = self.context.clone();
+        context.extend(condition.context.clone());
+        let outcome = declared
+            .evaluate(&context)
+            .map_err(|e| EvalError::Condition(condition.name.clone(), e.to_string()))?;
+        match outcome {
+            ConditionOutcome::Met => Ok(true),
+            ConditionOutcome::NotMet => Ok(false),
+            ConditionOutcome::MissingContext(names) => {
+                query.missing.extend(names);
+                Ok(query.negated)
+            }
+        }
+    }
+}
+
+impl Query<'_> {
+    /// The result of a query, failing with
+    /// [Cycle](crate::eval::EvalError::Cycle) or
+    /// [MissingContext](crate::eval::EvalError::MissingContext) when a
+    /// cycle or missing context could have allowed it.
+    fn finish(&self, allowed: bool) -> EvalResult<bool> {
+        if let (false, Some((object, relation))) = (allowed, &self.cycle) {
+            return Err(EvalError::Cycle(object.clone(), relation.clone()));
+        }
+        if !allowed && !self.missing.is_empty() {
+            return Err(EvalError::MissingContext(
+                self.missing.iter().cloned().collect(),
+            ));
+        }
+        Ok(allowed)
+    }
+}
+
+fn is_union(alias: &Alias) -> bool {
+    matches!(alias.kind, AliasKind::This | AliasKind::Named(_))
+}
+
+fn is_intersection(alias: &Alias) -> bool {
+    matches!(alias.kind, AliasKind::Intersection(_))
+}
+
+fn is_negative(alias: &Alias) -> bool {
+    matches!(alias.kind, AliasKind::Negative(_))
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use EvalError::*;
+        match self {
+            InvalidObject(object) => write!(f, "invalid object '{object}', expected type:id"),
+            UnknownType(ty) => write!(f, "type '{ty}' is not defined"),
+            UnknownRelation(ty, rel) => write!(f, "relation '{rel}' is not defined on type '{ty}'"),
+            DepthExceeded => write!(f, "resolution depth exceeded"),
+            InvalidContextualTuple(tuple, e) => write!(f, "invalid contextual tuple {tuple}: {e}"),
+            UnknownCondition(name) => write!(f, "condition '{name}' is not defined"),
+            Condition(name, e) => write!(f, "failed to evaluate condition '{name}': {e}"),
+            MissingContext(names) => {
+                write!(f, "missing context parameters: {}", names.join(", "))
+            }
+            Cycle(object, relation) => {
+                write!(f, "{object}#{relation} depends on itself through 'but not'")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+    use serde_json::json;
+
+    const MODEL: &str = "type user
+type group
+  relations
+    define member as self
+type folder
+  relations
+    define owner as self
+    define parent as self
+    define viewer as self or owner or viewer from parent
+type document
+  relations
+    define parent as self
+    define blocked as self
+    define editor as self
+    define viewer as self or editor or viewer from parent but not blocked";
+
+    fn store() -> TupleStore {
+        let mut tuples = TupleStore::new();
+        tuples.add("group:eng", "member", "user:anne");
+        tuples.add("folder:root", "owner", "user:bob");
+        tuples.add("folder:x", "parent", "folder:root");
+        tuples.add("folder:x", "viewer", "group:eng#member");
+        tuples.add("document:y", "parent", "folder:x");
+        tuples.add("document:y", "editor", "user:carl");
+        tuples.add("document:y", "blocked", "user:bob");
+        tuples.add("document:public", "viewer", "user:*");
+        tuples
+    }
+
+    fn check(object: &str, relation: &str, user: &str) -> EvalResult<bool> {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        Checker::new(&doc, &store()).check(object, relation, user)
+    }
+
+    #[test]
+    fn direct_and_computed() {
+        assert_eq!(check("group:eng", "member", "user:anne"), Ok(true));
+        assert_eq!(check("group:eng", "member", "user:bob"), Ok(false));
+        assert_eq!(check("document:y", "viewer", "user:carl"), Ok(true));
+        assert_eq!(check("folder:root", "viewer", "user:bob"), Ok(true));
+    }
+
+    #[test]
+    fn usersets_and_tuple_to_userset() {
+        assert_eq!(check("folder:x", "viewer", "user:anne"), Ok(true));
+        assert_eq!(check("document:y", "viewer", "user:anne"), Ok(true));
+        assert_eq!(check("folder:x", "viewer", "group:eng#member"), Ok(true));
+        assert_eq!(check("folder:x", "viewer", "user:bob"), Ok(true));
+    }
+
+    #[test]
+    fn difference() {
+        assert_eq!(check("document:y", "viewer", "user:bob"), Ok(false));
+    }
+
+    #[test]
+    fn intersection() {
+        let doc = Parser::new(
+            "type user
+type org
+  relations
+    define member as self
+type document
+  relations
+    define org as self
+    define allowed as self
+    define viewer as self and allowed and member from org",
+        )
+        .parse_document()
+        .unwrap();
+        let mut tuples = TupleStore::new();
+        tuples.add("org:acme", "member", "user:anne");
+        tuples.add("org:acme", "member", "user:carl");
+        tuples.add("document:1", "org", "org:acme");
+        for user in ["user:anne", "user:bob", "user:carl"] {
+            tuples.add("document:1", "allowed", user);
+        }
+        tuples.add("document:1", "viewer", "user:anne");
+        tuples.add("document:1", "viewer", "user:bob");
+
+        let checker = Checker::new(&doc, &tuples);
+        assert_eq!(checker.check("document:1", "viewer", "user:anne"), Ok(true));
+        assert_eq!(checker.check("document:1", "viewer", "user:bob"), Ok(false));
+        assert_eq!(
+            checker.check("document:1", "viewer", "user:carl"),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        assert_eq!(check("document:public", "viewer", "user:zed"), Ok(true));
+        assert_eq!(check("document:public", "viewer", "group:eng"), Ok(false));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            check("page:1", "viewer", "user:anne"),
+            Err(EvalError::UnknownType("page".into()))
+        );
+        assert_eq!(
+            check("document:y", "owner", "user:anne"),
+            Err(EvalError::UnknownRelation(
+                "document".into(),
+                "owner".into()
+            ))
+        );
+        assert_eq!(
+            check("document", "viewer", "user:anne"),
+            Err(EvalError::InvalidObject("document".into()))
+        );
+    }
+
+    #[test]
+    fn cycles_terminate() {
+        let doc = Parser::new(
+            "type user
+type group
+  relations
+    define member as self",
+        )
+        .parse_document()
+        .unwrap();
+        let mut tuples = TupleStore::new();
+        tuples.add("group:a", "member", "group:b#member");
+        tuples.add("group:b", "member", "group:a#member");
+        tuples.add("group:b", "member", "user:anne");
+
+        let checker = Checker::new(&doc, &tuples);
+        assert_eq!(checker.check("group:a", "member", "user:anne"), Ok(true));
+        assert_eq!(checker.check("group:a", "member", "user:bob"), Ok(false));
+    }
+
+    #[test]
+    fn cycles_through_but_not_are_unresolved() {
+        let doc = Parser::new(
+            "type user
+type document
+  relations
+    define viewer as self but not editor
+    define editor as viewer",
+        )
+        .parse_document()
+        .unwrap();
+        let mut tuples = TupleStore::new();
+        tuples.add("document:1", "viewer", "user:anne");
+
+        let checker = Checker::new(&doc, &tuples);
+        assert_eq!(
+            checker.check("document:1", "viewer", "user:anne"),
+            Err(EvalError::Cycle("document:1".into(), "viewer".into()))
+        );
+        assert_eq!(checker.check("document:1", "viewer", "user:bob"), Ok(false));
+    }
+
+    #[test]
+    fn depth_limit() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples).with_max_depth(2);
+        assert_eq!(
+            checker.check("document:y", "viewer", "user:anne"),
+            Err(EvalError::DepthExceeded)
+        );
+    }
+
+    #[test]
+    fn contextual_tuples_overlay_store() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let contextual = [
+            TupleKey::new("group:eng", "member", "user:dan"),
+            TupleKey::new("document:z", "parent", "folder:x"),
+        ];
+        let checker = Checker::new(&doc, &tuples).with_contextual_tuples(&contextual);
+
+        assert_eq!(checker.check("document:y", "viewer", "user:dan"), Ok(true));
+        assert_eq!(checker.check("document:z", "viewer", "user:anne"), Ok(true));
+        let mut objects: Vec<String> = checker
+            .list_objects("document", "viewer", "user:dan")
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        objects.sort();
+        assert_eq!(objects, vec!["document:public", "document:y", "document:z"]);
+        let users = checker
+            .list_users("group:eng", "member", &["user".into()])
+            .unwrap();
+        assert_eq!(users.objects, vec!["user:anne", "user:dan"]);
+
+        assert_eq!(tuples.len(), store().len());
+        let checker = Checker::new(&doc, &tuples);
+        assert_eq!(checker.check("document:y", "viewer", "user:dan"), Ok(false));
+    }
+
+    #[test]
+    fn contextual_tuples_are_validated() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = store();
+        let checker = Checker::new(&doc, &tuples).with_contextual_tuples(&[TupleKey::new(
+            "group:eng",
+            "owner",
+            "user:dan",
+        )]);
+        assert_eq!(
+            checker.check("document:y", "viewer", "user:anne"),
+            Err(EvalError::InvalidContextualTuple(
+                "group:eng#owner@user:dan".into(),
+                Box::new(TupleValidationError::UnknownRelation(
+                    "group".into(),
+                    "owner".into()
+                ))
+            ))
+        );
+
+        // related types apply whichever is set first
+        let mut related = DirectlyRelated::new();
+        related.insert("group", "member", ["user"]);
+        let wildcard = [TupleKey::new("group:eng", "member", "user:*")];
+        let checkers = [
+            Checker::new(&doc, &tuples)
+                .with_related_types(&related)
+                .with_contextual_tuples(&wildcard),
+            Checker::new(&doc, &tuples)
+                .with_contextual_tuples(&wildcard)
+                .with_related_types(&related),
+        ];
+        for checker in checkers {
+            let Err(EvalError::InvalidContextualTuple(_, e)) =
+                checker.list_users("group:eng", "member", &["user".into()])
+            else {
+                panic!("expected an invalid contextual tuple");
+            };
+            assert!(matches!(
+                *e,
+                TupleValidationError::WildcardNotAllowed { .. }
+            ));
+        }
+    }
+
+    /// The model declaring `in_office` with `body`, and tuples using it.
+    fn conditional_store(body: &str) -> (Document, TupleStore) {
+        let model =
+            format!("{MODEL}\ncondition in_office(ip: ipaddress, cidr: string) {{ {body} }}");
+        let doc = Parser::new(&model).parse_document().unwrap();
+        let mut tuples = store();
+        let office =
+            TupleKey::parse(r#"document:z#editor@user:dan with in_office {"cidr": "10.0.0.0/8"}"#)
+                .unwrap();
+        tuples.insert(&office);
+        let blocked = TupleKey::parse("document:z#blocked@user:carl with in_office").unwrap();
+        tuples.insert(&blocked);
+        tuples.add("document:z", "viewer", "user:carl");
+        (doc, tuples)
+    }
+
+    fn context(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn conditional_tuples() {
+        let (doc, tuples) = conditional_store("ip.in_cidr(cidr)");
+        let checker = |ctx: Value| Checker::new(&doc, &tuples).with_context(context(ctx));
+
+        let allowed = checker(json!({"ip": "10.1.1.1"})).check("document:z", "viewer", "user:dan");
+        assert_eq!(allowed, Ok(true));
+        let allowed =
+            checker(json!({"ip": "192.168.1.1"})).check("document:z", "viewer", "user:dan");
+        assert_eq!(allowed, Ok(false));
+        // the tuple's context takes precedence over the request's
+        let allowed = checker(json!({"ip": "10.1.1.1", "cidr": "192.168.0.0/16"})).check(
+            "document:z",
+            "viewer",
+            "user:dan",
+        );
+        assert_eq!(allowed, Ok(true));
+
+        assert_eq!(
+            checker(json!({})).check("document:z", "viewer", "user:dan"),
+            Err(EvalError::MissingContext(vec!["ip".into()]))
+        );
+        // a missing exclusion cannot be assumed not to apply
+        assert_eq!(
+            checker(json!({})).check("document:z", "viewer", "user:carl"),
+            Err(EvalError::MissingContext(vec!["cidr".into(), "ip".into()]))
+        );
+        let allowed = checker(json!({"ip": "10.0.0.1", "cidr": "172.16.0.0/12"})).check(
+            "document:z",
+            "viewer",
+            "user:carl",
+        );
+        assert_eq!(allowed, Ok(true));
+    }
+
+    #[test]
+    fn conditions_apply_per_tuple_source() {
+        let (doc, mut tuples) = conditional_store("ip.in_cidr(cidr)");
+        tuples.add("document:z", "editor", "user:erin");
+        let contextual = [
+            TupleKey::new("document:z", "editor", "user:dan"),
+            TupleKey::parse(r#"document:z#editor@user:erin with in_office {"cidr": "10.0.0.0/8"}"#)
+                .unwrap(),
+        ];
+        let checker = Checker::new(&doc, &tuples)
+            .with_contextual_tuples(&contextual)
+            .with_context(context(json!({"ip": "192.168.1.1"})));
+        // either tuple for the same key is enough
+        assert_eq!(checker.check("document:z", "editor", "user:dan"), Ok(true));
+        assert_eq!(checker.check("document:z", "editor", "user:erin"), Ok(true));
+    }
+
+    #[test]
+    fn undeclared_conditions() {
+        let (_, tuples) = conditional_store("ip.in_cidr(cidr)");
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        assert_eq!(
+            Checker::new(&doc, &tuples).check("document:z", "editor", "user:dan"),
+            Err(EvalError::UnknownCondition("in_office".into()))
+        );
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        let (doc, tuples) = conditional_store("ip.in_cidr(");
+        let checker = Checker::new(&doc, &tuples);
+        assert_eq!(
+            checker.check("document:z", "editor", "user:dan"),
+            Err(EvalError::Condition(
+                "in_office".into(),
+                "unexpected end of expression at position 11".into()
+            ))
+        );
+        // tuples without the condition are unaffected
+        assert_eq!(checker.check("document:y", "editor", "user:carl"), Ok(true));
+    }
+}
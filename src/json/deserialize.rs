@@ -0,0 +1,421 @@
+use std::fmt::Display;
+
+use serde_json::{Map, Value};
+
+use crate::ast::*;
+use crate::condition::ParamType;
+
+/// Error returned by [deserialize].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JsonError {
+    /// The input is not valid JSON. Line and column are one-based.
+    Syntax {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    /// The value at `path` has no DSL equivalent.
+    Unsupported { path: String, message: String },
+}
+
+/// Reads a model in the JSON format produced by
+/// [JsonTransformer](super::JsonTransformer) back into a
+/// [Document](crate::ast::Document).
+///
+/// ```
+/// use openfga_dsl_parser::json;
+///
+/// let doc = json::deserialize(r#"{"type_definitions": [
+///     {"type": "document", "relations": {"viewer": {"this": {}}}}
+/// ]}"#).unwrap();
+/// assert_eq!(doc.to_string(), "type document\n  relations\n    define viewer as self\n");
+/// ```
+pub fn deserialize(input: &str) -> Result<Document, JsonError> {
+    let value: Value = serde_json::from_str(input).map_err(|e| JsonError::Syntax {
+        message: e.to_string(),
+        line: e.line(),
+        column: e.column(),
+    })?;
+    let root = object(&value, "")?;
+    let types = match root.get("type_definitions") {
+        None => Vec::new(),
+        Some(Value::Array(types)) => types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| deserialize_type(ty, &format!("type_definitions[{i}]")))
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(unsupported("type_definitions", "expected an array")),
+    };
+    let mut conditions = Vec::new();
+    if let Some(value) = root.get("conditions") {
+        for (name, condition) in object(value, "conditions")? {
+            let path = format!("conditions.{name}");
+            conditions.push(deserialize_condition(name, condition, &path)?);
+        }
+    }
+    Ok(Document { types, conditions })
+}
+
+fn deserialize_condition(name: &str, value: &Value, path: &str) -> Result<Condition, JsonError> {
+    let obj = object(value, path)?;
+    let expression = string(obj.get("expression"), &format!("{path}.expression"))?;
+    let mut params = Vec::new();
+    if let Some(value) = obj.get("parameters") {
+        let path = format!("{path}.parameters");
+        for (name, ty) in object(value, &path)? {
+            let ty = deserialize_param_type(ty, &format!("{path}.{name}"))?;
+            params.push(Parameter {
+                name: name.clone(),
+                ty,
+            });
+        }
+    }
+    Ok(Condition {
+        name: name.to_string(),
+        params,
+        expression,
+    })
+}
+
+fn deserialize_param_type(value: &Value, path: &str) -> Result<ParamType, JsonError> {
+    let obj = object(value, path)?;
+    let type_name = string(obj.get("type_name"), &format!("{path}.type_name"))?;
+    let generic = || {
+        let path = format!("{path}.generic_types");
+        match obj.get("generic_types") {
+            Some(Value::Array(types)) if types.len() == 1 => {
+                deserialize_param_type(&types[0], &format!("{path}[0]")).map(Box::new)
+            }
+            _ => Err(unsupported(&path, "expected one generic type")),
+        }
+    };
+    match type_name.strip_prefix("TYPE_NAME_") {
+        Some("LIST") => Ok(ParamType::List(generic()?)),
+        Some("MAP") => Ok(ParamType::Map(generic()?)),
+        Some(name) if name.chars().all(|c| c.is_ascii_uppercase()) => name
+            .to_lowercase()
+            .parse()
+            .map_err(|_| unsupported(path, "unknown parameter type")),
+        _ => Err(unsupported(path, "unknown parameter type")),
+    }
+}
+
+fn deserialize_type(value: &Value, path: &str) -> Result<Type, JsonError> {
+    let obj = object(value, path)?;
+    let kind = string(obj.get("type"), &format!("{path}.type"))?;
+    let mut relations = Vec::new();
+    if let Some(rels) = obj.get("relations") {
+        let path = format!("{path}.relations");
+        for (name, rel) in object(rels, &path)? {
+            let path = format!("{path}.{name}");
+            let aliases = deserialize_userset(object(rel, &path)?, &path, true)?;
+            relations.push(Relation {
+                kind: name.clone(),
+                aliases,
+            });
+        }
+    }
+    Ok(Type { kind, relations })
+}
+
+/// Reads a userset into aliases. Nested unions flatten into one, while
+/// an intersection or difference is only read around the rest of the
+/// rewrite, as `and` and `but not` apply to the whole definition. An
+/// intersection of unions has no DSL equivalent, as `or` and `and`
+/// cannot be mixed.
+fn deserialize_userset(
+    obj: &Map<String, Value>,
+    path: &str,
+    top: bool,
+) -> Result<Vec<Alias>, JsonError> {
+    let mut keys = obj.iter();
+    let (Some((key, value)), None) = (keys.next(), keys.next()) else {
+        return Err(unsupported(path, "expected exactly one userset"));
+    };
+    let path = format!("{path}.{key}");
+    let alias = |kind, parent| vec![Alias { kind, parent }];
+    match key.as_str() {
+        "this" => Ok(alias(AliasKind::This, None)),
+        "computedUserset" => {
+            let name = relation(value, &path)?;
+            Ok(alias(AliasKind::Named(name), None))
+        }
+        "tupleToUserset" => {
+            let (name, parent) = tuple_to_userset(value, &path)?;
+            Ok(alias(AliasKind::Named(name), Some(parent)))
+        }
+        "union" => {
+            let obj = object(value, &path)?;
+            let path = format!("{path}.child");
+            let Some(Value::Array(children)) = obj.get("child") else {
+                return Err(unsupported(&path, "expected an array"));
+            };
+            let mut aliases = Vec::new();
+            for (i, child) in children.iter().enumerate() {
+                let path = format!("{path}[{i}]");
+                aliases.extend(deserialize_userset(object(child, &path)?, &path, false)?);
+            }
+            Ok(aliases)
+        }
+        "intersection" | "difference" if !top => Err(unsupported(
+            &path,
+            &format!("a nested {key} has no DSL equivalent"),
+        )),
+        "intersection" => {
+            let obj = object(value, &path)?;
+            let path = format!("{path}.child");
+            let Some(Value::Array(children)) = obj.get("child") else {
+                return Err(unsupported(&path, "expected an array"));
+            };
+            let mut children = children
+                .iter()
+                .enumerate()
+                .map(|(i, child)| {
+                    let path = format!("{path}[{i}]");
+                    deserialize_userset(object(child, &path)?, &path, false)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            // `and` is followed by a relation, so `self` becomes the
+            // start of the definition; `or` cannot be mixed with `and`
+            let is_relation = |aliases: &Vec<Alias>| {
+                matches!(
+                    aliases.as_slice(),
+                    [Alias {
+                        kind: AliasKind::Named(_),
+                        ..
+                    }]
+                )
+            };
+            let base = children.iter().position(|c| !is_relation(c)).unwrap_or(0);
+            if children.is_empty()
+                || children.iter().any(|c| c.len() != 1)
+                || children.iter().filter(|c| !is_relation(c)).count() > 1
+            {
+                return Err(unsupported(
+                    &path,
+                    "expected relations besides at most one `this`",
+                ));
+            }
+            let mut aliases = children.remove(base);
+            for child in children {
+                aliases.extend(child.into_iter().map(|a| match a.kind {
+                    AliasKind::Named(name) => Alias {
+                        kind: AliasKind::Intersection(name),
+                        parent: a.parent,
+                    },
+                    _ => a,
+                }));
+            }
+            Ok(aliases)
+        }
+        "difference" => {
+            let obj = object(value, &path)?;
+            let base_path = format!("{path}.base");
+            let base = deserialize_userset(
+                object(field(obj, "base", &path)?, &base_path)?,
+                &base_path,
+                true,
+            )?;
+            let sub_path = format!("{path}.subtract");
+            let subtract = object(field(obj, "subtract", &path)?, &sub_path)?;
+            let negative = match deserialize_userset(subtract, &sub_path, false)?.as_slice() {
+                [Alias {
+                    kind: AliasKind::Named(name),
+                    parent,
+                }] => alias(AliasKind::Negative(name.clone()), parent.clone()),
+                _ => return Err(unsupported(&sub_path, "expected a relation to subtract")),
+            };
+            Ok(base.into_iter().chain(negative).collect())
+        }
+        _ => Err(unsupported(&path, "unknown userset")),
+    }
+}
+
+fn tuple_to_userset(value: &Value, path: &str) -> Result<(String, String), JsonError> {
+    let obj = object(value, path)?;
+    let parent = relation(field(obj, "tupleset", path)?, &format!("{path}.tupleset"))?;
+    let name = relation(
+        field(obj, "computedUserset", path)?,
+        &format!("{path}.computedUserset"),
+    )?;
+    Ok((name, parent))
+}
+
+fn relation(value: &Value, path: &str) -> Result<String, JsonError> {
+    let obj = object(value, path)?;
+    string(obj.get("relation"), &format!("{path}.relation"))
+}
+
+fn field<'v>(obj: &'v Map<String, Value>, key: &str, path: &str) -> Result<&'v Value, JsonError> {
+    obj.get(key)
+        .ok_or_else(|| unsupported(&format!("{path}.{key}"), "missing"))
+}
+
+fn object<'v>(value: &'v Value, path: &str) -> Result<&'v Map<String, Value>, JsonError> {
+    value
+        .as_object()
+        .ok_or_else(|| unsupported(path, "expected an object"))
+}
+
+fn string(value: Option<&Value>, path: &str) -> Result<String, JsonError> {
+    match value {
+        Some(Value::String(s)) if !s.is_empty() => Ok(s.clone()),
+        _ => Err(unsupported(path, "expected a name")),
+    }
+}
+
+fn unsupported(path: &str, message: &str) -> JsonError {
+    JsonError::Unsupported {
+        path: if path.is_empty() {
+            "$".into()
+        } else {
+            path.into()
+        },
+        message: message.into(),
+    }
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Syntax { message, .. } => write!(f, "invalid JSON: {message}"),
+            JsonError::Unsupported { path, message } => write!(f, "{path}: {message}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::JsonTransformer;
+    use crate::Parser;
+
+    #[test]
+    fn round_trips_through_json() {
+        let input = "type folder
+  relations
+    define owner as self
+    define parent as self
+    define viewer as self or owner or viewer from parent
+type document
+  relations
+    define blocked as self
+    define owner as self but not blocked
+    define parent as self
+    define editor as self or owner but not blocked from parent
+    define commenter as self and viewer and owner from parent but not blocked
+    define viewer as owner from parent
+";
+        let doc = Parser::new(input).parse_document().unwrap();
+        let json = JsonTransformer::new(&doc).serialize();
+        let back = deserialize(&json).unwrap();
+        // relations come back in the JSON's key order
+        for ty in &doc.types {
+            let other = back.get_type(&ty.kind).unwrap();
+            for rel in &ty.relations {
+                assert_eq!(other.get_relation(&rel.kind), Some(rel));
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_conditions() {
+        let input = "type user
+condition in_office(cidrs: map<list<string>>, ip: ipaddress) {
+  ip.in_cidr(cidrs['eu'][0])
+}
+";
+        let doc = Parser::new(input).parse_document().unwrap();
+        let json = JsonTransformer::new(&doc).serialize();
+        assert_eq!(deserialize(&json).unwrap().to_string(), input);
+    }
+
+    #[test]
+    fn reads_top_level_difference() {
+        let json = r#"{"type_definitions": [{"type": "doc", "relations": {"viewer": {
+            "difference": {
+                "base": {"computedUserset": {"object": "", "relation": "owner"}},
+                "subtract": {"computedUserset": {"object": "", "relation": "blocked"}}
+            }
+        }}}]}"#;
+        let doc = deserialize(json).unwrap();
+        assert_eq!(
+            doc.types[0].relations[0].to_string(),
+            "define viewer as owner but not blocked"
+        );
+    }
+
+    #[test]
+    fn reads_intersection_starting_with_relation() {
+        let json = r#"{"type_definitions": [{"type": "doc", "relations": {"viewer": {
+            "intersection": {"child": [
+                {"computedUserset": {"object": "", "relation": "allowed"}},
+                {"this": {}}
+            ]}
+        }}}]}"#;
+        let doc = deserialize(json).unwrap();
+        assert_eq!(
+            doc.types[0].relations[0].to_string(),
+            "define viewer as self and allowed"
+        );
+    }
+
+    #[test]
+    fn rejects_union_in_intersection() {
+        let json = r#"{"type_definitions": [{"type": "doc", "relations": {"viewer": {
+            "intersection": {"child": [
+                {"union": {"child": [
+                    {"this": {}},
+                    {"computedUserset": {"object": "", "relation": "owner"}}
+                ]}},
+                {"computedUserset": {"object": "", "relation": "allowed"}}
+            ]}
+        }}}]}"#;
+        let err = deserialize(json).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "type_definitions[0].relations.viewer.intersection.child: \
+             expected relations besides at most one `this`"
+        );
+    }
+
+    #[test]
+    fn rejects_difference_in_union() {
+        let json = r#"{"type_definitions": [{"type": "doc", "relations": {"viewer": {
+            "union": {"child": [
+                {"this": {}},
+                {"difference": {
+                    "base": {"this": {}},
+                    "subtract": {"computedUserset": {"object": "", "relation": "blocked"}}
+                }}
+            ]}
+        }}}]}"#;
+        let err = deserialize(json).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "type_definitions[0].relations.viewer.union.child[1].difference: \
+             a nested difference has no DSL equivalent"
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(
+            deserialize("{\n  \"type_definitions\": [\n}"),
+            Err(JsonError::Syntax {
+                message: "expected value at line 3 column 1".into(),
+                line: 3,
+                column: 1,
+            })
+        );
+
+        let json = r#"{"type_definitions": [{"type": "doc", "relations": {"viewer": {
+            "exclusion": {"child": []}
+        }}}]}"#;
+        let err = deserialize(json).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "type_definitions[0].relations.viewer.exclusion: unknown userset"
+        );
+    }
+}
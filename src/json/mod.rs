@@ -1,6 +1,11 @@
 use crate::ast::*;
+use crate::condition::ParamType;
 use serde_json::{json, Map, Value};
 
+mod deserialize;
+
+pub use deserialize::{deserialize, JsonError};
+
 /// Transformer type for turning [Documents](crate::ast::Document)
 /// into JSON.
 pub struct JsonTransformer<'d> {
@@ -18,7 +23,7 @@ impl<'d> JsonTransformer<'d> {
         json!(map).to_string()
     }
 
-    fn to_json_map(self) -> Map<String, Value> {
+    fn to_json_map(&self) -> Map<String, Value> {
         let mut root = Map::new();
 
         let mut types: Vec<Map<String, Value>> = Vec::new();
@@ -30,10 +35,49 @@ impl<'d> JsonTransformer<'d> {
         }
 
         root.insert("type_definitions".into(), types.into());
+        if !self.doc.conditions.is_empty() {
+            let conditions: Map<String, Value> = self
+                .doc
+                .conditions
+                .iter()
+                .map(|c| (c.name.clone(), serialize_condition_obj(c)))
+                .collect();
+            root.insert("conditions".into(), conditions.into());
+        }
         root
     }
 }
 
+fn serialize_condition_obj(condition: &Condition) -> Value {
+    let parameters: Map<String, Value> = condition
+        .params
+        .iter()
+        .map(|p| (p.name.clone(), serialize_param_type(&p.ty)))
+        .collect();
+    json!({
+        "name": condition.name,
+        "expression": condition.expression,
+        "parameters": parameters
+    })
+}
+
+/// A parameter type as OpenFGA writes it, e.g. `list<string>` is
+/// `TYPE_NAME_LIST` with the generic type `TYPE_NAME_STRING`.
+fn serialize_param_type(ty: &ParamType) -> Value {
+    let type_name = |ty: &ParamType| match ty {
+        ParamType::List(_) => "TYPE_NAME_LIST".to_string(),
+        ParamType::Map(_) => "TYPE_NAME_MAP".to_string(),
+        other => format!("TYPE_NAME_{}", other.to_string().to_uppercase()),
+    };
+    match ty {
+        ParamType::List(item) | ParamType::Map(item) => json!({
+            "type_name": type_name(ty),
+            "generic_types": [serialize_param_type(item)]
+        }),
+        _ => json!({ "type_name": type_name(ty) }),
+    }
+}
+
 fn serialize_type_obj(ty: &Type) -> Map<String, Value> {
     let mut type_obj = Map::new();
     type_obj.insert("type".into(), ty.kind.clone().into());
@@ -46,70 +90,75 @@ fn serialize_type_obj(ty: &Type) -> Map<String, Value> {
 fn serialize_relations_obj(relations: &[Relation]) -> Map<String, Value> {
     let mut rel_obj = Map::new();
     for rel in relations {
-        if rel.aliases.len() <= 1 {
-            let mut rel_content = Map::new();
-            for alias in &rel.aliases {
-                let (key, obj) = serialize_alias_obj(&alias);
-                rel_content.insert(key, obj);
+        rel_obj.insert(rel.kind.clone(), serialize_rewrite(&rel.aliases));
+    }
+    rel_obj
+}
+
+/// The userset rewrite of a relation: the union of its aliases,
+/// intersected with each `and` alias, less each `but not` alias.
+fn serialize_rewrite(aliases: &[Alias]) -> Value {
+    let mut positive = Vec::new();
+    let mut intersection = Vec::new();
+    let mut negative = Vec::new();
+    for alias in aliases {
+        match alias.kind {
+            AliasKind::This | AliasKind::Named(_) => positive.push(serialize_alias_obj(alias)),
+            AliasKind::Intersection(_) => intersection.push(serialize_alias_obj(alias)),
+            AliasKind::Negative(_) => negative.push(serialize_alias_obj(alias)),
+        }
+    }
+    let mut rewrite = match positive.len() {
+        0 => json!({}),
+        1 => positive.remove(0),
+        _ => json!({
+            "union": {
+                "child": positive
             }
-            rel_obj.insert(rel.kind.clone().into(), json!(rel_content));
-        } else {
-            let mut children = Vec::new();
-            for alias in &rel.aliases {
-                let (key, obj) = serialize_alias_obj(&alias);
-                let out = json!({ key: obj });
-                children.push(out);
+        }),
+    };
+    if !intersection.is_empty() {
+        intersection.insert(0, rewrite);
+        rewrite = json!({
+            "intersection": {
+                "child": intersection
             }
-            let obj = json!({
-                "union": {
-                    "child": children
-                }
-            });
-            rel_obj.insert(rel.kind.clone().into(), obj);
-        }
+        });
     }
-    rel_obj
+    for subtract in negative {
+        rewrite = json!({
+            "difference": {
+                "base": rewrite,
+                "subtract": subtract
+            }
+        });
+    }
+    rewrite
 }
 
-fn serialize_alias_obj(alias: &Alias) -> (String, Value) {
-    match &alias.kind {
-        AliasKind::This => ("this".into(), json!({})),
-        AliasKind::Negative(not) => (
-            "difference".into(),
-            json!({
-                "base": {
-                    "this": {}
+fn serialize_alias_obj(alias: &Alias) -> Value {
+    let Some(name) = alias.kind.relation() else {
+        return json!({ "this": {} });
+    };
+    match &alias.parent {
+        Some(parent) => json!({
+            "tupleToUserset": {
+                "tupleset": {
+                    "object": "",
+                    "relation": parent
                 },
-                "subtract": {
-                    "computedUserset": {
-                        "object": "",
-                        "relation": not
-                    }
-                }
-            }),
-        ),
-        AliasKind::Named(name) => match &alias.parent {
-            Some(parent) => (
-                "tupleToUserset".into(),
-                json!({
-                    "tupleset": {
-                        "object": "",
-                        "relation": parent
-                    },
-                    "computedUserset": {
-                        "object": "",
-                        "relation": name
-                    }
-                }),
-            ),
-            None => (
-                "computedUserset".into(),
-                json!({
+                "computedUserset": {
                     "object": "",
                     "relation": name
-                }),
-            ),
-        },
+                }
+            }
+        }),
+        None => json!({
+            "computedUserset": {
+                "object": "",
+                "relation": name
+            }
+        }),
     }
 }
 
@@ -124,6 +173,7 @@ mod tests {
                 kind: String::from("foo"),
                 relations: Vec::new(),
             }],
+            conditions: Vec::new(),
         };
         let exp = json!({
             "type_definitions": [
@@ -239,6 +289,114 @@ mod tests {
         assert_eq!(exp, json!(res));
     }
 
+    #[test]
+    fn but_not_subtracts_from_whole_rewrite() {
+        let i = vec![Relation {
+            kind: "foo".into(),
+            aliases: vec![
+                Alias {
+                    kind: AliasKind::This,
+                    parent: None,
+                },
+                Alias {
+                    kind: AliasKind::Named("bar".into()),
+                    parent: None,
+                },
+                Alias {
+                    kind: AliasKind::Negative("baz".into()),
+                    parent: Some("parent".into()),
+                },
+            ],
+        }];
+        let exp = json!({
+            "foo": {
+                "difference": {
+                    "base": {
+                        "union": {
+                            "child": [
+                                {
+                                    "this": {}
+                                },
+                                {
+                                    "computedUserset": {
+                                        "object": "",
+                                        "relation": "bar"
+                                    }
+                                }
+                            ]
+                        }
+                    },
+                    "subtract": {
+                        "tupleToUserset": {
+                            "tupleset": {
+                                "object": "",
+                                "relation": "parent"
+                            },
+                            "computedUserset": {
+                                "object": "",
+                                "relation": "baz"
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        let res = serialize_relations_obj(&i);
+        assert_eq!(exp, json!(res));
+    }
+
+    #[test]
+    fn and_intersects_whole_union() {
+        let i = vec![Relation {
+            kind: "foo".into(),
+            aliases: vec![
+                Alias {
+                    kind: AliasKind::This,
+                    parent: None,
+                },
+                Alias {
+                    kind: AliasKind::Named("bar".into()),
+                    parent: None,
+                },
+                Alias {
+                    kind: AliasKind::Intersection("baz".into()),
+                    parent: None,
+                },
+            ],
+        }];
+        let exp = json!({
+            "foo": {
+                "intersection": {
+                    "child": [
+                        {
+                            "union": {
+                                "child": [
+                                    {
+                                        "this": {}
+                                    },
+                                    {
+                                        "computedUserset": {
+                                            "object": "",
+                                            "relation": "bar"
+                                        }
+                                    }
+                                ]
+                            }
+                        },
+                        {
+                            "computedUserset": {
+                                "object": "",
+                                "relation": "baz"
+                            }
+                        }
+                    ]
+                }
+            }
+        });
+        let res = serialize_relations_obj(&i);
+        assert_eq!(exp, json!(res));
+    }
+
     #[test]
     fn big_one() {
         let i = Document {
@@ -279,6 +437,7 @@ mod tests {
                     ],
                 },
             ],
+            conditions: Vec::new(),
         };
 
         let exp = json!({
@@ -327,4 +486,31 @@ mod tests {
         let res = JsonTransformer::new(&i).to_json_map();
         assert_eq!(exp, json!(res));
     }
+
+    #[test]
+    fn conditions() {
+        let i = crate::Parser::new(
+            "type user
+condition in_office(ip: ipaddress, cidrs: list<string>) {
+  cidrs.exists(c, ip.in_cidr(c))
+}",
+        )
+        .parse_document()
+        .unwrap();
+        let exp = json!({
+            "in_office": {
+                "name": "in_office",
+                "expression": "cidrs.exists(c, ip.in_cidr(c))",
+                "parameters": {
+                    "ip": {"type_name": "TYPE_NAME_IPADDRESS"},
+                    "cidrs": {
+                        "type_name": "TYPE_NAME_LIST",
+                        "generic_types": [{"type_name": "TYPE_NAME_STRING"}]
+                    }
+                }
+            }
+        });
+        let res = JsonTransformer::new(&i).to_json_map();
+        assert_eq!(res["conditions"], exp);
+    }
 }
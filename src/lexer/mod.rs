@@ -7,6 +7,7 @@ pub struct Lexer {
     input: Vec<char>, // todo: make this an iterable so we dont have to collect
     pos: usize,
     read_pos: usize,
+    span: Span,
 }
 
 impl Lexer {
@@ -18,22 +19,45 @@ impl Lexer {
     }
 
     pub fn next_token(&mut self) -> Token {
-        match self.next() {
+        let start = self.read_pos.min(self.input.len());
+        let tok = match self.next() {
             Some(c) => {
                 if c.is_whitespace() {
-                    self.next_token()
-                } else if is_valid_text(&c) {
+                    return self.next_token();
+                } else if *c == '#' {
+                    self.skip_comment();
+                    return self.next_token();
+                } else if is_valid_text(c) {
                     let lit = self.read_text();
                     match TokenKind::is_to_keyword(&lit) {
                         Some(keyword) => Token::new(lit, keyword),
                         None => Token::new(lit, TokenKind::Text),
                     }
+                } else if *c == '{' {
+                    self.read_expression()
                 } else {
-                    Token::new(c.to_string(), TokenKind::Illegal)
+                    let kind = match c {
+                        '(' => TokenKind::LParen,
+                        ')' => TokenKind::RParen,
+                        ':' => TokenKind::Colon,
+                        ',' => TokenKind::Comma,
+                        '<' => TokenKind::LAngle,
+                        '>' => TokenKind::RAngle,
+                        _ => TokenKind::Illegal,
+                    };
+                    Token::new(c.to_string(), kind)
                 }
             }
             None => Token::new("".into(), TokenKind::EOF),
-        }
+        };
+        let end = self.read_pos.min(self.input.len());
+        self.span = Span::new(start, end);
+        tok
+    }
+
+    /// Location of the last token returned by [next_token](Lexer::next_token).
+    pub fn span(&self) -> Span {
+        self.span
     }
 
     fn next(&mut self) -> Option<&char> {
@@ -47,6 +71,12 @@ impl Lexer {
         self.input.get(self.read_pos)
     }
 
+    fn skip_comment(&mut self) {
+        while self.peek().is_some_and(|c| *c != '\n') {
+            self.next();
+        }
+    }
+
     fn read_text(&mut self) -> String {
         let start = self.pos;
         let mut i = vec![self.input[start]];
@@ -55,6 +85,36 @@ impl Lexer {
         }
         i.iter().collect()
     }
+
+    /// Reads a condition body up to the `}` matching the `{` just read,
+    /// skipping braces in string literals. Unterminated bodies are
+    /// illegal.
+    fn read_expression(&mut self) -> Token {
+        let start = self.read_pos;
+        let mut depth = 1;
+        let mut quote = None;
+        while let Some(&c) = self.next() {
+            match (quote, c) {
+                (Some(_), '\\') => {
+                    self.next();
+                }
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '{') => depth += 1,
+                (None, '}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let lit = self.input[start..self.pos].iter().collect();
+                        return Token::new(lit, TokenKind::Expression);
+                    }
+                }
+                (None, _) => {}
+            }
+        }
+        let lit = self.input[start - 1..].iter().collect();
+        Token::new(lit, TokenKind::Illegal)
+    }
 }
 
 fn is_valid_text(c: &char) -> bool {
@@ -205,4 +265,69 @@ mod tests {
 
         assert_eq!(l.next_token(), Token::new("".into(), TokenKind::EOF));
     }
+
+    #[test]
+    fn skips_comments() {
+        let i = "# a model
+type document # lint:allow(snake-case)
+";
+        let mut l = Lexer::new(i);
+        assert_eq!(l.next_token(), Token::new("type".into(), TokenKind::Type));
+        assert_eq!(
+            l.next_token(),
+            Token::new("document".into(), TokenKind::Text)
+        );
+        assert_eq!(l.next_token(), Token::new("".into(), TokenKind::EOF));
+    }
+
+    #[test]
+    fn reads_conditions() {
+        let i = "condition in_office(ip: ipaddress, cidrs: list<string>) {
+  cidrs.exists(c, ip.in_cidr(c)) && {'}': 1}['}'] == 1
+}
+condition broken(x: int) { x > 1";
+        let mut l = Lexer::new(i);
+        let mut tokens = Vec::new();
+        loop {
+            let tok = l.next_token();
+            if tok.kind() == TokenKind::EOF {
+                break;
+            }
+            tokens.push(tok.kind());
+        }
+        use TokenKind::*;
+        assert_eq!(
+            tokens,
+            vec![
+                Condition, Text, LParen, Text, Colon, Text, Comma, Text, Colon, Text, LAngle, Text,
+                RAngle, RParen, Expression, Condition, Text, LParen, Text, Colon, Text, RParen,
+                Illegal
+            ]
+        );
+
+        let mut l = Lexer::new(i);
+        while l.next_token().kind() != Expression {}
+        assert_eq!(l.span(), Span::new(56, 114));
+        let body: String = i.chars().skip(57).take(56).collect();
+        assert_eq!(
+            body,
+            "\n  cidrs.exists(c, ip.in_cidr(c)) && {'}': 1}['}'] == 1\n"
+        );
+    }
+
+    #[test]
+    fn token_spans() {
+        let i = "type document
+  relations";
+        let mut l = Lexer::new(i);
+        l.next_token();
+        assert_eq!(l.span(), Span::new(0, 4));
+        l.next_token();
+        assert_eq!(l.span(), Span::new(5, 13));
+        l.next_token();
+        assert_eq!(l.span(), Span::new(16, 25));
+        assert_eq!(l.span().line_col(i), (1, 2));
+        l.next_token();
+        assert_eq!(l.span(), Span::new(25, 25));
+    }
 }
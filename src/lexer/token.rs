@@ -11,8 +11,19 @@ pub enum TokenKind {
     From,
     But,
     Not,
+    Condition,
 
     Text,
+    /// The body of a condition, from `{` to the matching `}`. The
+    /// literal is the text between the braces.
+    Expression,
+
+    LParen,
+    RParen,
+    Colon,
+    Comma,
+    LAngle,
+    RAngle,
 
     Newline,
     EOF,
@@ -24,22 +35,18 @@ pub enum TokenKind {
 pub struct Token {
     lit: String,
     kind: TokenKind,
-    // span: Span,
 }
 
-// #[derive(Debug, PartialEq, Eq, Clone)]
-// pub struct Span {
-//     start: usize,
-//     end: usize,
-// }
+/// Location of a piece of the input, as character offsets.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
 
 impl Token {
     pub fn new(lit: String, kind: TokenKind) -> Self {
-        Self {
-            lit,
-            kind,
-            // span: Span { start, end },
-        }
+        Self { lit, kind }
     }
 
     pub fn literal(&self) -> &str {
@@ -51,6 +58,32 @@ impl Token {
     }
 }
 
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Smallest span covering both spans.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// Zero based line and column of the start of the span.
+    pub fn line_col(&self, input: &str) -> (usize, usize) {
+        let mut line = 0;
+        let mut col = 0;
+        for c in input.chars().take(self.start) {
+            if c == '\n' {
+                line += 1;
+                col = 0;
+            } else {
+                col += 1;
+            }
+        }
+        (line, col)
+    }
+}
+
 impl TokenKind {
     pub fn is_to_keyword(literal: &str) -> Option<Self> {
         match literal {
@@ -64,6 +97,7 @@ impl TokenKind {
             "from" => Some(Self::From),
             "but" => Some(Self::But),
             "not" => Some(Self::Not),
+            "condition" => Some(Self::Condition),
             _ => None,
         }
     }
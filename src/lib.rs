@@ -26,9 +26,22 @@
 //! # }
 //! ```
 
+pub mod analysis;
 pub mod ast;
+pub mod build;
+pub mod condition;
+pub mod diff;
+pub mod eval;
 pub mod json;
 pub mod lexer;
+pub mod lint;
+pub mod migrate;
 mod parser;
+#[cfg(feature = "store")]
+pub mod store;
+pub mod tuple;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub use parser::*;
@@ -0,0 +1,410 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::ast::{AliasKind, Document, SourceMap};
+use crate::lexer::token::{Span, TokenKind};
+
+/// Named lint rules that can be configured individually.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Rule {
+    /// A relation that no other relation refers to. Relations that
+    /// are only checked by applications trigger this, so it is off
+    /// by default.
+    UnusedRelation,
+    /// A relation only referred to through `but not`.
+    OnlyExcluded,
+    /// A type or relation name that is not snake_case.
+    SnakeCase,
+    /// A type with relations, none of which can be directly assigned.
+    NoAssignableRelation,
+    /// A type or relation named like a keyword, e.g. `Self` or `this`.
+    KeywordShadowing,
+}
+
+/// How a [Rule](crate::lint::Rule) is reported.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Allow,
+    Warning,
+    Error,
+}
+
+/// Per-rule [Severity](crate::lint::Severity) configuration.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LintConfig {
+    severities: HashMap<Rule, Severity>,
+}
+
+/// A single lint finding.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Lint {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+}
+
+/// Linter for reporting style and safety issues in a
+/// [Document](crate::ast::Document).
+///
+/// Findings can be suppressed with a `# lint:allow(rule)` comment on
+/// the offending line or on its own line directly above it.
+pub struct Linter<'d> {
+    input: &'d str,
+    doc: &'d Document,
+    source: &'d SourceMap,
+    config: LintConfig,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 5] = [
+        Rule::UnusedRelation,
+        Rule::OnlyExcluded,
+        Rule::SnakeCase,
+        Rule::NoAssignableRelation,
+        Rule::KeywordShadowing,
+    ];
+
+    /// The name used for this rule in configuration and comments.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::UnusedRelation => "unused-relation",
+            Rule::OnlyExcluded => "only-excluded",
+            Rule::SnakeCase => "snake-case",
+            Rule::NoAssignableRelation => "no-assignable-relation",
+            Rule::KeywordShadowing => "keyword-shadowing",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|r| r.name() == name)
+    }
+
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Rule::UnusedRelation => Severity::Allow,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the severity of a rule.
+    pub fn set(&mut self, rule: Rule, severity: Severity) -> &mut Self {
+        self.severities.insert(rule, severity);
+        self
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+}
+
+impl<'d> Linter<'d> {
+    /// Create a new [Linter](crate::lint::Linter) for a document, the
+    /// input it was parsed from and its [SourceMap](crate::ast::SourceMap).
+    pub fn new(input: &'d str, doc: &'d Document, source: &'d SourceMap) -> Self {
+        Self {
+            input,
+            doc,
+            source,
+            config: LintConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: LintConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Runs every enabled rule, returning findings in source order.
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        self.lint_names(&mut lints);
+        self.lint_references(&mut lints);
+        self.lint_assignable(&mut lints);
+
+        let allowed = allowed_rules(self.input);
+        lints.retain(|lint| {
+            let (line, _) = lint.span.line_col(self.input);
+            let allows = |l: usize| {
+                allowed
+                    .get(&l)
+                    .is_some_and(|(rules, _)| rules.contains(lint.rule.name()))
+            };
+            let above = line > 0
+                && allowed
+                    .get(&(line - 1))
+                    .is_some_and(|(_, own_line)| *own_line)
+                && allows(line - 1);
+            !allows(line) && !above
+        });
+        lints.sort_by_key(|lint| lint.span.start);
+        lints
+    }
+
+    fn report(&self, lints: &mut Vec<Lint>, rule: Rule, span: Span, message: String) {
+        let severity = self.config.severity(rule);
+        if severity != Severity::Allow {
+            lints.push(Lint {
+                rule,
+                severity,
+                message,
+                span,
+            });
+        }
+    }
+
+    fn lint_names(&self, lints: &mut Vec<Lint>) {
+        for (ty, ty_src) in self.doc.types.iter().zip(&self.source.types) {
+            let mut names = vec![("type", &ty.kind, ty_src.name)];
+            for (rel, rel_src) in ty.relations.iter().zip(&ty_src.relations) {
+                names.push(("relation", &rel.kind, rel_src.name));
+            }
+
+            for (what, name, span) in names {
+                if !is_snake_case(name) {
+                    self.report(
+                        lints,
+                        Rule::SnakeCase,
+                        span,
+                        format!("{what} '{name}' is not snake_case"),
+                    );
+                }
+                let lower = name.to_lowercase();
+                if lower == "this" || TokenKind::is_to_keyword(&lower).is_some() {
+                    self.report(
+                        lints,
+                        Rule::KeywordShadowing,
+                        span,
+                        format!("{what} '{name}' shadows the keyword '{lower}'"),
+                    );
+                }
+            }
+        }
+    }
+
+    fn lint_references(&self, lints: &mut Vec<Lint>) {
+        // Relations referred to on a known type, and by name from `from`
+        // aliases whose target types are unknown.
+        let mut positive: HashSet<(&str, &str)> = HashSet::new();
+        let mut negative: HashSet<(&str, &str)> = HashSet::new();
+        let mut positive_any: HashSet<&str> = HashSet::new();
+        let mut negative_any: HashSet<&str> = HashSet::new();
+
+        for ty in &self.doc.types {
+            for rel in &ty.relations {
+                for alias in &rel.aliases {
+                    let Some(name) = alias.kind.relation() else {
+                        continue;
+                    };
+                    let is_negative = matches!(alias.kind, AliasKind::Negative(_));
+                    match &alias.parent {
+                        Some(parent) => {
+                            positive.insert((&ty.kind, parent));
+                            if is_negative {
+                                negative_any.insert(name);
+                            } else {
+                                positive_any.insert(name);
+                            }
+                        }
+                        None if is_negative => {
+                            negative.insert((&ty.kind, name));
+                        }
+                        None => {
+                            positive.insert((&ty.kind, name));
+                        }
+                    }
+                }
+            }
+        }
+
+        for (ty, ty_src) in self.doc.types.iter().zip(&self.source.types) {
+            for (rel, rel_src) in ty.relations.iter().zip(&ty_src.relations) {
+                let key = (ty.kind.as_str(), rel.kind.as_str());
+                let is_positive = positive.contains(&key) || positive_any.contains(key.1);
+                let is_negative = negative.contains(&key) || negative_any.contains(key.1);
+                if is_negative && !is_positive {
+                    self.report(
+                        lints,
+                        Rule::OnlyExcluded,
+                        rel_src.name,
+                        format!(
+                            "relation '{}#{}' is only referred to through 'but not'",
+                            ty.kind, rel.kind
+                        ),
+                    );
+                } else if !is_negative && !is_positive {
+                    self.report(
+                        lints,
+                        Rule::UnusedRelation,
+                        rel_src.name,
+                        format!("relation '{}#{}' is never referred to", ty.kind, rel.kind),
+                    );
+                }
+            }
+        }
+    }
+
+    fn lint_assignable(&self, lints: &mut Vec<Lint>) {
+        for (ty, ty_src) in self.doc.types.iter().zip(&self.source.types) {
+            if !ty.relations.is_empty() && !ty.relations.iter().any(|r| r.is_assignable()) {
+                self.report(
+                    lints,
+                    Rule::NoAssignableRelation,
+                    ty_src.name,
+                    format!("type '{}' has no directly assignable relation", ty.kind),
+                );
+            }
+        }
+    }
+}
+
+/// Rules allowed by `lint:allow` comments, keyed by zero based line, along
+/// with whether the comment is the only thing on its line.
+fn allowed_rules(input: &str) -> HashMap<usize, (HashSet<&str>, bool)> {
+    let mut allowed = HashMap::new();
+    for (i, line) in input.lines().enumerate() {
+        let Some((code, comment)) = line.split_once('#') else {
+            continue;
+        };
+        let Some(rest) = comment.trim().strip_prefix("lint:allow(") else {
+            continue;
+        };
+        let Some((rules, _)) = rest.split_once(')') else {
+            continue;
+        };
+        let rules = rules.split(',').map(str::trim).collect();
+        allowed.insert(i, (rules, code.trim().is_empty()));
+    }
+    allowed
+}
+
+fn is_snake_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Allow => write!(f, "allow"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} [{}]",
+            self.severity,
+            self.message,
+            self.rule.name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn lint_with(i: &str, config: LintConfig) -> Vec<(Rule, String)> {
+        let mut parser = Parser::new(i);
+        let doc = parser.parse_document().unwrap();
+        Linter::new(i, &doc, parser.source_map())
+            .with_config(config)
+            .lint()
+            .into_iter()
+            .map(|l| (l.rule, i[l.span.start..l.span.end].to_string()))
+            .collect()
+    }
+
+    fn lint(i: &str) -> Vec<(Rule, String)> {
+        lint_with(i, LintConfig::new())
+    }
+
+    #[test]
+    fn rule_names_round_trip() {
+        for rule in Rule::ALL {
+            assert_eq!(Rule::from_name(rule.name()), Some(rule));
+        }
+    }
+
+    #[test]
+    fn snake_case_and_keywords() {
+        let i = "type Document
+  relations
+    define this as self
+    define canView as this";
+        assert_eq!(
+            lint(i),
+            vec![
+                (Rule::SnakeCase, "Document".into()),
+                (Rule::KeywordShadowing, "this".into()),
+                (Rule::SnakeCase, "canView".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_excluded() {
+        let i = "type document
+  relations
+    define blocked as self
+    define viewer as self but not blocked";
+        assert_eq!(lint(i), vec![(Rule::OnlyExcluded, "blocked".into())]);
+    }
+
+    #[test]
+    fn no_assignable_relation() {
+        let i = "type user
+type document
+  relations
+    define viewer as editor
+    define editor as viewer";
+        assert_eq!(
+            lint(i),
+            vec![(Rule::NoAssignableRelation, "document".into())]
+        );
+    }
+
+    #[test]
+    fn unused_relation_when_enabled() {
+        let i = "type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define owner as self
+    define viewer as owner or viewer from parent
+    define can_share as owner";
+        let mut config = LintConfig::new();
+        config.set(Rule::UnusedRelation, Severity::Error);
+        let lints = lint_with(i, config);
+        assert_eq!(lints, vec![(Rule::UnusedRelation, "can_share".into())]);
+    }
+
+    #[test]
+    fn allow_comments_suppress() {
+        let i = "type Document # lint:allow(snake-case)
+  relations
+    # lint:allow(keyword-shadowing, snake-case)
+    define This as self
+    define canView as This";
+        assert_eq!(lint(i), vec![(Rule::SnakeCase, "canView".into())]);
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Write};
+
+use crate::ast::{Alias, AliasKind, Document, Relation};
+use crate::validate::{DirectlyRelated, RelatedType};
+
+/// Upgrades a schema 1.0 [Document](crate::ast::Document) to schema 1.1
+/// DSL, replacing each `self` with the types directly related to the
+/// relation. Those are supplied by the caller, or inferred from a tuple
+/// sample with [DirectlyRelated::from_tuples]. A relation left with
+/// nothing to grant is restricted to [PLACEHOLDER_TYPE] and reviewed.
+pub struct Migrator<'d> {
+    doc: &'d Document,
+    related: Option<&'d DirectlyRelated>,
+}
+
+/// A type without relations added to the upgraded model, standing in for
+/// the users of relations that have nothing to grant, so that the
+/// relations referencing them stay valid.
+pub const PLACEHOLDER_TYPE: &str = "migration_placeholder";
+
+/// The upgraded model and the relations it could not upgrade faithfully.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Migration {
+    pub dsl: String,
+    pub reviews: Vec<Review>,
+}
+
+/// A relation of the upgraded model that needs manual review.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Review {
+    pub ty: String,
+    pub relation: String,
+    pub reason: ReviewReason,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ReviewReason {
+    /// The relation is defined with `self` but no related types were
+    /// given, so `self` is left out of the rewrite, or replaced by
+    /// [PLACEHOLDER_TYPE] if nothing else grants the relation.
+    MissingRelatedTypes,
+    /// A related type, or the relation of a userset, that the model
+    /// does not define.
+    UndefinedRelatedType(RelatedType),
+    /// A userset or wildcard on a relation used as a tupleset, which
+    /// schema 1.1 does not allow.
+    TuplesetNotDirect(RelatedType),
+    /// Related types were given for a relation not defined with `self`.
+    UnusedRelatedTypes,
+    /// The relation only excludes users, so it has nothing to subtract
+    /// them from.
+    NoBase,
+    /// The relation is defined without `as`, so it has no rewrite.
+    NoRewrite,
+}
+
+impl<'d> Migrator<'d> {
+    pub fn new(doc: &'d Document) -> Self {
+        Self { doc, related: None }
+    }
+
+    /// Use `related` for the types directly related to each relation.
+    pub fn with_related_types(mut self, related: &'d DirectlyRelated) -> Self {
+        self.related = Some(related);
+        self
+    }
+
+    /// Writes the document as schema 1.1 DSL.
+    pub fn migrate(&self) -> Migration {
+        let mut dsl = String::from("model\n  schema 1.1\n");
+        let mut reviews = Vec::new();
+        let mut placeholder = false;
+        for ty in &self.doc.types {
+            let tuplesets: HashSet<&str> = ty
+                .relations
+                .iter()
+                .flat_map(|rel| &rel.aliases)
+                .filter_map(|alias| alias.parent.as_deref())
+                .collect();
+
+            let mut relations = String::new();
+            for rel in &ty.relations {
+                let mut review = |reason| {
+                    reviews.push(Review {
+                        ty: ty.kind.clone(),
+                        relation: rel.kind.clone(),
+                        reason,
+                    })
+                };
+                let related = self.related.and_then(|r| r.get(&ty.kind, &rel.kind));
+                let is_tupleset = tuplesets.contains(rel.kind.as_str());
+                match related {
+                    Some(related) if rel.is_assignable() => {
+                        for user in related {
+                            if !self.is_defined(user) {
+                                review(ReviewReason::UndefinedRelatedType(user.clone()));
+                            }
+                            if is_tupleset && !matches!(user, RelatedType::Direct(_)) {
+                                review(ReviewReason::TuplesetNotDirect(user.clone()));
+                            }
+                        }
+                    }
+                    Some(_) => review(ReviewReason::UnusedRelatedTypes),
+                    None if rel.is_assignable() => review(ReviewReason::MissingRelatedTypes),
+                    None => {}
+                }
+                if rel.aliases.is_empty() {
+                    review(ReviewReason::NoRewrite);
+                } else if rel.aliases.iter().all(is_negative) {
+                    review(ReviewReason::NoBase);
+                }
+
+                let (rewrite, placeholder_used) = rewrite(rel, related.unwrap_or_default());
+                placeholder |= placeholder_used;
+                let _ = writeln!(relations, "    define {}: {rewrite}", rel.kind);
+            }
+
+            let _ = write!(dsl, "\ntype {}\n", ty.kind);
+            if !relations.is_empty() {
+                dsl.push_str("  relations\n");
+                dsl.push_str(&relations);
+            }
+        }
+        if placeholder {
+            let _ = write!(dsl, "\ntype {PLACEHOLDER_TYPE}\n");
+        }
+        Migration { dsl, reviews }
+    }
+
+    fn is_defined(&self, user: &RelatedType) -> bool {
+        let ty = self.doc.get_type(user.type_name());
+        match user {
+            RelatedType::Userset(_, relation) => {
+                ty.and_then(|ty| ty.get_relation(relation)).is_some()
+            }
+            _ => ty.is_some(),
+        }
+    }
+}
+
+fn is_negative(alias: &Alias) -> bool {
+    matches!(alias.kind, AliasKind::Negative(_))
+}
+
+/// The 1.1 rewrite of a relation: the union of its aliases, intersected
+/// with its `and` aliases, minus the union of its exclusions. The `[...]`
+/// restriction leads the union, as schema 1.1 requires. A relation with
+/// nothing to grant is restricted to [PLACEHOLDER_TYPE] instead, which
+/// the returned flag reports.
+fn rewrite(rel: &Relation, related: &[RelatedType]) -> (String, bool) {
+    let group = |aliases: &[String]| match aliases.len() {
+        1 => aliases.join(""),
+        _ => format!("({})", aliases.join(" or ")),
+    };
+    let mut base = Vec::new();
+    let mut required = Vec::new();
+    let mut excluded = Vec::new();
+    for alias in &rel.aliases {
+        match alias.kind {
+            AliasKind::This if related.is_empty() => {}
+            AliasKind::This => {
+                let types: Vec<String> = related.iter().map(ToString::to_string).collect();
+                base.insert(0, format!("[{}]", types.join(", ")));
+            }
+            AliasKind::Intersection(_) => required.push(alias.to_string()),
+            AliasKind::Negative(_) => excluded.push(alias.to_string()),
+            _ => base.push(alias.to_string()),
+        }
+    }
+    let placeholder = base.is_empty();
+    if placeholder {
+        base.push(format!("[{PLACEHOLDER_TYPE}]"));
+    }
+
+    let mut out = base.join(" or ");
+    if !required.is_empty() {
+        required.insert(0, group(&base));
+        out = required.join(" and ");
+    }
+    let out = match excluded.is_empty() {
+        true => out,
+        false if base.len() > 1 || required.len() > 1 => {
+            format!("({out}) but not {}", group(&excluded))
+        }
+        false => format!("{out} but not {}", group(&excluded)),
+    };
+    (out, placeholder)
+}
+
+impl Display for Review {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}#{}: ", self.ty, self.relation)?;
+        match &self.reason {
+            ReviewReason::MissingRelatedTypes => {
+                write!(f, "no directly related types were given for 'self'")
+            }
+            ReviewReason::UndefinedRelatedType(user) => {
+                write!(f, "related type '{user}' is not defined in the model")
+            }
+            ReviewReason::TuplesetNotDirect(user) => write!(
+                f,
+                "'{user}' is not allowed on a relation used as a tupleset"
+            ),
+            ReviewReason::UnusedRelatedTypes => write!(
+                f,
+                "related types were given but the relation is not defined with 'self'"
+            ),
+            ReviewReason::NoBase => write!(f, "the relation only excludes users"),
+            ReviewReason::NoRewrite => write!(f, "the relation has no rewrite"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    const MODEL: &str = "type user
+type team
+  relations
+    define member as self
+type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define owner as self
+    define blocked as self
+    define editor as owner or self
+    define viewer as self or editor or viewer from parent but not blocked
+    define commenter as self and editor but not blocked";
+
+    #[test]
+    fn migrates_to_schema_1_1() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let mut related = DirectlyRelated::new();
+        related.insert("team", "member", ["user", "team#member"]);
+        related.insert("folder", "viewer", ["user"]);
+        related.insert("document", "parent", ["folder"]);
+        related.insert("document", "owner", ["user"]);
+        related.insert("document", "blocked", ["user"]);
+        related.insert("document", "editor", ["user", "team#member"]);
+        related.insert("document", "viewer", ["user:*", "team#member"]);
+        related.insert("document", "commenter", ["user"]);
+
+        let migration = Migrator::new(&doc).with_related_types(&related).migrate();
+        assert_eq!(
+            migration.dsl,
+            "model
+  schema 1.1
+
+type user
+
+type team
+  relations
+    define member: [user, team#member]
+
+type folder
+  relations
+    define viewer: [user]
+
+type document
+  relations
+    define parent: [folder]
+    define owner: [user]
+    define blocked: [user]
+    define editor: [user, team#member] or owner
+    define viewer: ([user:*, team#member] or editor or viewer from parent) but not blocked
+    define commenter: ([user] and editor) but not blocked
+"
+        );
+        assert_eq!(migration.reviews, vec![]);
+    }
+
+    #[test]
+    fn reports_relations_to_review() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let mut related = DirectlyRelated::new();
+        related.insert("team", "member", ["user", "team#lead"]);
+        related.insert("document", "parent", ["folder", "folder#viewer"]);
+        related.insert("document", "owner", ["user", "robot"]);
+
+        let migration = Migrator::new(&doc).with_related_types(&related).migrate();
+        assert!(migration
+            .dsl
+            .contains("define blocked: [migration_placeholder]\n"));
+        assert!(migration.dsl.contains("define editor: owner\n"));
+        let reviews: Vec<String> = migration.reviews.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            reviews,
+            vec![
+                "team#member: related type 'team#lead' is not defined in the model",
+                "folder#viewer: no directly related types were given for 'self'",
+                "document#parent: 'folder#viewer' is not allowed on a relation used as a tupleset",
+                "document#owner: related type 'robot' is not defined in the model",
+                "document#blocked: no directly related types were given for 'self'",
+                "document#editor: no directly related types were given for 'self'",
+                "document#viewer: no directly related types were given for 'self'",
+                "document#commenter: no directly related types were given for 'self'",
+            ]
+        );
+    }
+
+    #[test]
+    fn reviews_relations_without_rewrite() {
+        let doc = Parser::new(
+            "type user
+type document
+  relations
+    define blocked as self
+    define viewer as self but not blocked
+    define owner",
+        )
+        .parse_document()
+        .unwrap();
+        let mut related = DirectlyRelated::new();
+        related.insert("document", "blocked", ["user"]);
+
+        let migration = Migrator::new(&doc).with_related_types(&related).migrate();
+        assert_eq!(
+            migration.dsl,
+            "model
+  schema 1.1
+
+type user
+
+type document
+  relations
+    define blocked: [user]
+    define viewer: [migration_placeholder] but not blocked
+    define owner: [migration_placeholder]
+
+type migration_placeholder
+"
+        );
+        let reviews: Vec<String> = migration.reviews.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            reviews,
+            vec![
+                "document#viewer: no directly related types were given for 'self'",
+                "document#owner: the relation has no rewrite",
+            ]
+        );
+    }
+
+    #[test]
+    fn infers_related_types_from_tuples() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let tuples = crate::tuple::parse_tuples(
+            "document:1#owner@user:anne
+             document:1#viewer@user:*
+             document:1#viewer@team:eng#member",
+        )
+        .unwrap();
+        let related = DirectlyRelated::from_tuples(&tuples);
+
+        let migration = Migrator::new(&doc).with_related_types(&related).migrate();
+        assert!(migration.dsl.contains("define owner: [user]\n"));
+        assert!(migration
+            .dsl
+            .contains("define viewer: ([user:*, team#member] or editor or viewer from parent)"));
+    }
+
+    /// Checks that every type and relation a schema 1.1 model written by
+    /// the migrator refers to is defined.
+    fn assert_references_defined(dsl: &str) {
+        let mut types: Vec<(&str, Vec<(&str, &str)>)> = Vec::new();
+        for line in dsl.lines().map(str::trim) {
+            if let Some(ty) = line.strip_prefix("type ") {
+                types.push((ty, Vec::new()));
+            } else if let Some(define) = line.strip_prefix("define ") {
+                let (relation, rewrite) = define.split_once(": ").unwrap();
+                types.last_mut().unwrap().1.push((relation, rewrite));
+            }
+        }
+        let relation_of = |ty: &str, relation: &str| {
+            types
+                .iter()
+                .any(|(t, relations)| *t == ty && relations.iter().any(|(r, _)| *r == relation))
+        };
+
+        for (ty, relations) in &types {
+            for (relation, rewrite) in relations {
+                let (restriction, rest) = match rewrite.trim_start_matches('(').split_once(']') {
+                    Some((restriction, rest)) => (restriction.trim_start_matches('['), rest),
+                    None => ("", *rewrite),
+                };
+                for user in restriction.split(", ").filter(|u| !u.is_empty()) {
+                    let (user_ty, user_relation) = user.split_once('#').unwrap_or((user, ""));
+                    let user_ty = user_ty.trim_end_matches(":*");
+                    assert!(
+                        types.iter().any(|(t, _)| *t == user_ty),
+                        "{ty}#{relation}: undefined type in '{user}'"
+                    );
+                    assert!(
+                        user_relation.is_empty() || relation_of(user_ty, user_relation),
+                        "{ty}#{relation}: undefined relation in '{user}'"
+                    );
+                }
+                let words: Vec<&str> = rest
+                    .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                    .filter(|w| !w.is_empty())
+                    .collect();
+                for (i, word) in words.iter().enumerate() {
+                    let keyword = ["or", "and", "but", "not", "from"].contains(word);
+                    // the relation before `from` is defined on the parent's type
+                    let computed = words.get(i + 1) == Some(&"from");
+                    if !keyword && !computed {
+                        assert!(
+                            relation_of(ty, word),
+                            "{ty}#{relation}: undefined relation '{word}'"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn migrated_models_refer_to_defined_relations() {
+        let doc = Parser::new(
+            "type user
+type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define parent as self
+    define blocked as self
+    define viewer as self or owner or viewer from parent but not blocked
+    define editor as self and viewer but not blocked
+    define owner",
+        )
+        .parse_document()
+        .unwrap();
+        let mut related = DirectlyRelated::new();
+        related.insert("document", "parent", ["folder"]);
+        related.insert("document", "viewer", ["user"]);
+
+        let migration = Migrator::new(&doc).with_related_types(&related).migrate();
+        assert_references_defined(&migration.dsl);
+        assert_eq!(
+            migration.dsl,
+            "model
+  schema 1.1
+
+type user
+
+type folder
+  relations
+    define viewer: [migration_placeholder]
+
+type document
+  relations
+    define parent: [folder]
+    define blocked: [migration_placeholder]
+    define viewer: ([user] or owner or viewer from parent) but not blocked
+    define editor: ([migration_placeholder] and viewer) but not blocked
+    define owner: [migration_placeholder]
+
+type migration_placeholder
+"
+        );
+
+        let migration = Migrator::new(&Parser::new(MODEL).parse_document().unwrap()).migrate();
+        assert_references_defined(&migration.dsl);
+    }
+}
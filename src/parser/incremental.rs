@@ -0,0 +1,392 @@
+use std::ops::Range;
+
+use super::{ParseResult, Parser};
+use crate::ast::{Document, SourceMap, Type, TypeSource};
+use crate::lexer::token::Span;
+
+/// Parser that keeps the last parse of its input and, on each edit,
+/// only parses again the type blocks the edit touches.
+///
+/// ```
+/// use openfga_dsl_parser::{IncrementalParser, TextEdit};
+/// use openfga_dsl_parser::lexer::token::Span;
+///
+/// let mut parser = IncrementalParser::new("type user
+/// type document
+///   relations
+///     define viewer as self").unwrap();
+///
+/// let at = parser.input().find("self").unwrap();
+/// let reparse = parser
+///     .edit(&[TextEdit::new(Span::new(at + 4, at + 4), " or owner")])
+///     .unwrap();
+/// assert_eq!(reparse.reparsed, 1..2);
+/// ```
+pub struct IncrementalParser {
+    input: String,
+    doc: Document,
+    source: SourceMap,
+    /// Whether the input failed to parse since `doc` was parsed, so
+    /// `source` no longer matches the input.
+    stale: bool,
+    error_span: Span,
+}
+
+/// Replaces the characters in `span` with `text`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+/// The outcome of an [edit](IncrementalParser::edit).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Reparse {
+    /// Indices of the types that were parsed again. All other types
+    /// were reused from the previous parse.
+    pub reparsed: Range<usize>,
+    pub changes: Vec<Change>,
+}
+
+/// A type that differs from the previous parse.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Change {
+    /// The index of a new type.
+    Added(usize),
+    /// The name of a type that no longer exists.
+    Removed(String),
+    /// The index of a changed type and the names of its relations that
+    /// were added, removed or changed.
+    Modified(usize, Vec<String>),
+}
+
+impl TextEdit {
+    pub fn new(span: Span, text: &str) -> Self {
+        Self {
+            span,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl IncrementalParser {
+    /// Parses `input` in full.
+    pub fn new(input: &str) -> ParseResult<Self> {
+        let mut parser = Parser::new(input);
+        let doc = parser.parse_document()?;
+        Ok(Self {
+            input: input.to_string(),
+            doc,
+            source: parser.source,
+            stale: false,
+            error_span: Span::default(),
+        })
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// The last successfully parsed document. It is out of date when
+    /// the last edit returned an error.
+    pub fn document(&self) -> &Document {
+        &self.doc
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source
+    }
+
+    /// Location of the token that caused the last error.
+    pub fn error_span(&self) -> Span {
+        self.error_span
+    }
+
+    /// Applies `edits` to the input and parses the changed type
+    /// blocks. Edit spans are character offsets into the input before
+    /// any of the edits and must not overlap.
+    ///
+    /// On error the input is still edited, and the next edit parses it
+    /// in full.
+    pub fn edit(&mut self, edits: &[TextEdit]) -> ParseResult<Reparse> {
+        let len = self.input.chars().count();
+        let mut edits: Vec<&TextEdit> = edits.iter().collect();
+        edits.sort_by_key(|e| e.span.start);
+        let (Some(first), Some(last)) = (edits.first(), edits.last()) else {
+            return Ok(Reparse {
+                reparsed: 0..0,
+                changes: Vec::new(),
+            });
+        };
+        let (lo, hi) = (first.span.start.min(len), last.span.end.min(len));
+        let mut delta = 0isize;
+        for edit in edits.iter().rev() {
+            let start = byte_offset(&self.input, edit.span.start.min(len));
+            let end = byte_offset(&self.input, edit.span.end.clamp(edit.span.start, len));
+            delta += edit.text.chars().count() as isize
+                - self.input[start..end].chars().count() as isize;
+            self.input.replace_range(start..end, &edit.text);
+        }
+
+        // blocks are located by type only, so conditions between them
+        // need a full parse
+        if self.stale || !self.doc.conditions.is_empty() {
+            return self.reparse_all();
+        }
+
+        // Each block runs from its `type` keyword to the next block's,
+        // and the first one from the start of the input.
+        let starts: Vec<usize> = self.source.types.iter().map(|t| t.span.start).collect();
+        let region_end = |i: usize| starts.get(i + 1).copied().unwrap_or(len);
+        let region_start = |i: usize| if i == 0 { 0 } else { starts[i] };
+        let affected: Vec<usize> = (0..starts.len())
+            .filter(|&i| region_start(i) <= hi && lo <= region_end(i))
+            .collect();
+        let (from, to) = match (affected.first(), affected.last()) {
+            (Some(&from), Some(&to)) => (from, to + 1),
+            _ => (0, 0),
+        };
+        let start = if from == 0 { 0 } else { starts[from] };
+        let end = (starts.get(to).copied().unwrap_or(len) as isize + delta) as usize;
+        let fragment: String = self.input.chars().skip(start).take(end - start).collect();
+
+        let mut parser = Parser::new(&fragment);
+        let Ok(fragment_doc) = parser.parse_document() else {
+            // the edit may join blocks, so let a full parse decide
+            return self.reparse_all();
+        };
+        if !fragment_doc.conditions.is_empty() {
+            return self.reparse_all();
+        }
+        let mut sources = parser.source.types;
+        for source in &mut sources {
+            shift(source, start as isize);
+        }
+        for source in &mut self.source.types[to..] {
+            shift(source, delta);
+        }
+
+        let old: Vec<Type> = self
+            .doc
+            .types
+            .splice(from..to, fragment_doc.types)
+            .collect();
+        self.source.types.splice(from..to, sources);
+        let reparsed = from..self.doc.types.len() - (starts.len() - to);
+        let changes = changes(&old, &self.doc.types[reparsed.clone()], from);
+        Ok(Reparse { reparsed, changes })
+    }
+
+    fn reparse_all(&mut self) -> ParseResult<Reparse> {
+        let mut parser = Parser::new(&self.input);
+        match parser.parse_document() {
+            Ok(doc) => {
+                let old = std::mem::replace(&mut self.doc, doc);
+                self.source = parser.source;
+                self.stale = false;
+                Ok(Reparse {
+                    reparsed: 0..self.doc.types.len(),
+                    changes: changes(&old.types, &self.doc.types, 0),
+                })
+            }
+            Err(e) => {
+                self.stale = true;
+                self.error_span = parser.error_span;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn byte_offset(s: &str, chars: usize) -> usize {
+    s.char_indices().nth(chars).map_or(s.len(), |(i, _)| i)
+}
+
+fn shift(source: &mut TypeSource, by: isize) {
+    let move_span = |span: &mut Span| {
+        span.start = (span.start as isize + by) as usize;
+        span.end = (span.end as isize + by) as usize;
+    };
+    move_span(&mut source.span);
+    move_span(&mut source.name);
+    for rel in &mut source.relations {
+        move_span(&mut rel.span);
+        move_span(&mut rel.name);
+        for alias in &mut rel.aliases {
+            move_span(&mut alias.span);
+            move_span(&mut alias.name);
+            if let Some(parent) = &mut alias.parent {
+                move_span(parent);
+            }
+        }
+    }
+}
+
+/// Compares the types `old` with the types `new`, which start at index
+/// `offset` of the document.
+fn changes(old: &[Type], new: &[Type], offset: usize) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (i, ty) in new.iter().enumerate() {
+        match old.iter().find(|o| o.kind == ty.kind) {
+            None => changes.push(Change::Added(offset + i)),
+            Some(o) if o != ty => {
+                let mut relations: Vec<String> = Vec::new();
+                for rel in &ty.relations {
+                    if o.get_relation(&rel.kind) != Some(rel) {
+                        relations.push(rel.kind.clone());
+                    }
+                }
+                for rel in &o.relations {
+                    if ty.get_relation(&rel.kind).is_none() {
+                        relations.push(rel.kind.clone());
+                    }
+                }
+                changes.push(Change::Modified(offset + i, relations));
+            }
+            Some(_) => {}
+        }
+    }
+    for o in old {
+        if !new.iter().any(|ty| ty.kind == o.kind) {
+            changes.push(Change::Removed(o.kind.clone()));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = "# documents
+type user
+type folder
+  relations
+    define viewer as self
+type document
+  relations
+    define owner as self
+    define viewer as owner or viewer from parent";
+
+    fn insert(parser: &IncrementalParser, after: &str, text: &str) -> TextEdit {
+        let at = parser.input().find(after).unwrap() + after.len();
+        let at = parser.input()[..at].chars().count();
+        TextEdit::new(Span::new(at, at), text)
+    }
+
+    fn assert_matches_full_parse(parser: &IncrementalParser) {
+        let mut full = Parser::new(parser.input());
+        assert_eq!(&full.parse_document().unwrap(), parser.document());
+        assert_eq!(full.source_map(), parser.source_map());
+    }
+
+    #[test]
+    fn reparses_only_edited_blocks() {
+        let mut parser = IncrementalParser::new(MODEL).unwrap();
+        let edit = insert(&parser, "define viewer as self", " or editor");
+        let reparse = parser.edit(&[edit]).unwrap();
+        assert_eq!(reparse.reparsed, 1..2);
+        assert_eq!(
+            reparse.changes,
+            vec![Change::Modified(1, vec!["viewer".into()])]
+        );
+        assert_matches_full_parse(&parser);
+
+        // a new relation shifts the blocks after it
+        let edit = insert(
+            &parser,
+            "define owner as self",
+            "\n    define parent as self",
+        );
+        let reparse = parser.edit(&[edit]).unwrap();
+        assert_eq!(reparse.reparsed, 2..3);
+        assert_eq!(
+            reparse.changes,
+            vec![Change::Modified(2, vec!["parent".into()])]
+        );
+        assert_matches_full_parse(&parser);
+    }
+
+    #[test]
+    fn adds_and_removes_types() {
+        let mut parser = IncrementalParser::new(MODEL).unwrap();
+        let edit = insert(
+            &parser,
+            "type user",
+            "\ntype team\n  relations\n    define member as self",
+        );
+        let reparse = parser.edit(&[edit]).unwrap();
+        assert_eq!(reparse.reparsed, 0..2);
+        assert_eq!(reparse.changes, vec![Change::Added(1)]);
+        assert_matches_full_parse(&parser);
+
+        let start = parser.input().find("type folder").unwrap();
+        let end = parser.input().find("type document").unwrap();
+        let reparse = parser
+            .edit(&[TextEdit::new(Span::new(start, end), "")])
+            .unwrap();
+        assert_eq!(reparse.changes, vec![Change::Removed("folder".into())]);
+        assert_eq!(parser.document().types.len(), 3);
+        assert_matches_full_parse(&parser);
+    }
+
+    #[test]
+    fn applies_several_edits() {
+        let mut parser = IncrementalParser::new(MODEL).unwrap();
+        let edits = [
+            insert(&parser, "define owner as self", " or viewer"),
+            insert(&parser, "type user", "s"),
+        ];
+        let reparse = parser.edit(&edits).unwrap();
+        assert_eq!(reparse.reparsed, 0..3);
+        assert_eq!(
+            reparse.changes,
+            vec![
+                Change::Added(0),
+                Change::Modified(2, vec!["owner".into()]),
+                Change::Removed("user".into()),
+            ]
+        );
+        assert_matches_full_parse(&parser);
+    }
+
+    #[test]
+    fn recovers_from_errors() {
+        let mut parser = IncrementalParser::new(MODEL).unwrap();
+        let edit = insert(&parser, "define owner as", " or");
+        assert!(parser.edit(&[edit]).is_err());
+        assert_eq!(
+            parser.error_span().start,
+            parser.input().find(" or self").unwrap() + 1
+        );
+        // the last good parse is kept
+        assert_eq!(parser.document().types.len(), 3);
+
+        let at = parser.input().find(" or self").unwrap();
+        let reparse = parser
+            .edit(&[TextEdit::new(Span::new(at, at + 3), "")])
+            .unwrap();
+        assert_eq!(reparse.reparsed, 0..3);
+        assert_eq!(reparse.changes, vec![]);
+        assert_matches_full_parse(&parser);
+    }
+
+    #[test]
+    fn parses_conditions_in_full() {
+        let mut parser = IncrementalParser::new(MODEL).unwrap();
+        let edit = insert(
+            &parser,
+            "type user",
+            "\ncondition recent(t: timestamp) {\n  t > now\n}",
+        );
+        let reparse = parser.edit(&[edit]).unwrap();
+        assert_eq!(reparse.reparsed, 0..3);
+        assert_eq!(parser.document().conditions.len(), 1);
+        assert_matches_full_parse(&parser);
+
+        let edit = insert(&parser, "define owner as self", " or viewer");
+        let reparse = parser.edit(&[edit]).unwrap();
+        assert_eq!(reparse.reparsed, 0..3);
+        assert_matches_full_parse(&parser);
+    }
+}
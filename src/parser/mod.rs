@@ -1,11 +1,18 @@
 use std::fmt::Display;
 
-use crate::ast::{Alias, AliasKind, Document, Relation, Type};
+use crate::ast::{
+    Alias, AliasKind, AliasSource, Condition, ConditionSource, Document, Parameter, Relation,
+    RelationSource, SourceMap, Type, TypeSource,
+};
 use crate::lexer::{
-    token::{Token, TokenKind},
+    token::{Span, Token, TokenKind},
     Lexer,
 };
 
+mod incremental;
+
+pub use incremental::{Change, IncrementalParser, Reparse, TextEdit};
+
 /// Result type for the [Parser](crate::Parser) type.
 pub type ParseResult<T> = Result<T, ParserError>;
 
@@ -15,6 +22,12 @@ pub struct Parser {
     lex: Lexer,
     curr: Token,
     peek: Token,
+    curr_span: Span,
+    peek_span: Span,
+    prev_span: Span,
+    source: SourceMap,
+    alias_sources: Vec<AliasSource>,
+    error_span: Span,
 }
 
 /// Enumerated error type for the [Parser](crate::Parser) type.
@@ -23,49 +36,83 @@ pub enum ParserError {
     UnexpectedToken(TokenKind, TokenKind),
     UnexpectedKeyword(TokenKind),
     UnexpectedEOF,
+    UnknownParamType(String),
 }
 
 impl Parser {
     /// Create a new [Parser](crate::Parser) type.
     pub fn new(input: &str) -> Self {
-        let mut lex = Lexer::new(input);
-        let curr = lex.next_token();
-        let peek = lex.next_token();
-        Self { lex, curr, peek }
+        Self::from_lexer(Lexer::new(input))
     }
 
     /// Create a new [Parser](crate::Parser) type with
     /// a provided lexer instead of creating one.
     pub fn from_lexer(mut lex: Lexer) -> Self {
         let curr = lex.next_token();
+        let curr_span = lex.span();
         let peek = lex.next_token();
-        Self { lex, curr, peek }
+        let peek_span = lex.span();
+        Self {
+            lex,
+            curr,
+            peek,
+            curr_span,
+            peek_span,
+            prev_span: Span::default(),
+            source: SourceMap::default(),
+            alias_sources: Vec::new(),
+            error_span: Span::default(),
+        }
+    }
+
+    /// Source locations of the nodes of the last parsed
+    /// [Document](crate::ast::Document).
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source
+    }
+
+    /// Location of the token that caused the last [ParserError](crate::ParserError).
+    pub fn error_span(&self) -> Span {
+        self.error_span
     }
 
     /// Transforms the input string provided at instantiation
     /// into a [Document](crate::ast::Document).
     pub fn parse_document(&mut self) -> ParseResult<Document> {
+        self.source = SourceMap::default();
+        self.alias_sources.clear();
         let mut types = Vec::new();
+        let mut conditions = Vec::new();
         while self.curr.kind() != TokenKind::EOF {
-            if self.curr.kind() != TokenKind::Type {
-                return Err(ParserError::UnexpectedToken(
-                    TokenKind::Type,
-                    self.curr.kind(),
-                ));
+            match self.curr.kind() {
+                TokenKind::Type => types.push(self.parse_type()?),
+                TokenKind::Condition => conditions.push(self.parse_condition()?),
+                kind => {
+                    self.error_span = self.curr_span;
+                    return Err(ParserError::UnexpectedToken(TokenKind::Type, kind));
+                }
             }
-            let ty = self.parse_type()?;
-            types.push(ty);
             self.next_token();
         }
-        Ok(Document { types })
+        Ok(Document { types, conditions })
     }
 
     fn parse_type(&mut self) -> ParseResult<Type> {
-        self.expect_peek(TokenKind::Text)?;
+        let start = self.curr_span;
+        self.expect_peek_name()?;
         let kind = self.curr.literal().to_string();
+        let name = self.curr_span;
         let mut relations = Vec::new();
-
-        if self.peek.kind() != TokenKind::EOF && self.peek.kind() != TokenKind::Type {
+        self.source.types.push(TypeSource {
+            span: start.to(name),
+            name,
+            relations: Vec::new(),
+        });
+
+        if !matches!(
+            self.peek.kind(),
+            TokenKind::EOF | TokenKind::Type | TokenKind::Condition
+        ) {
             self.expect_peek(TokenKind::Relations)?;
 
             while self.peek.kind() == TokenKind::Define {
@@ -75,15 +122,86 @@ impl Parser {
             }
         }
 
+        if let Some(ty) = self.source.types.last_mut() {
+            ty.span = start.to(self.curr_span);
+        }
         Ok(Type { kind, relations })
     }
 
-    fn parse_relation(&mut self) -> ParseResult<Relation> {
+    /// Parses `condition name(param: type, ...) { expression }`.
+    fn parse_condition(&mut self) -> ParseResult<Condition> {
+        let start = self.curr_span;
+        self.expect_peek_name()?;
+        let name = self.curr.literal().to_string();
+        let name_span = self.curr_span;
+        self.expect_peek(TokenKind::LParen)?;
+
+        let mut params = Vec::new();
+        let mut param_spans = Vec::new();
+        while self.peek.kind() != TokenKind::RParen {
+            if !params.is_empty() {
+                self.expect_peek(TokenKind::Comma)?;
+            }
+            self.expect_peek_name()?;
+            let param = self.curr.literal().to_string();
+            let param_start = self.curr_span;
+            self.expect_peek(TokenKind::Colon)?;
+            let ty_start = self.peek_span;
+            let ty = self.parse_param_type()?;
+            let ty = ty.parse().map_err(|_| {
+                self.error_span = ty_start.to(self.curr_span);
+                ParserError::UnknownParamType(ty)
+            })?;
+            params.push(Parameter { name: param, ty });
+            param_spans.push(param_start.to(self.curr_span));
+        }
+        self.next_token();
+        self.expect_peek(TokenKind::Expression)?;
+
+        let body = self.curr.literal();
+        let expression = body.trim().to_string();
+        let leading = body.chars().take_while(|c| c.is_whitespace()).count();
+        let start_of_expression = self.curr_span.start + 1 + leading;
+        self.source.conditions.push(ConditionSource {
+            span: start.to(self.curr_span),
+            name: name_span,
+            params: param_spans,
+            expression: Span::new(
+                start_of_expression,
+                start_of_expression + expression.chars().count(),
+            ),
+        });
+        Ok(Condition {
+            name,
+            params,
+            expression,
+        })
+    }
+
+    /// Reads a parameter type such as `string` or `map<list<int>>` as
+    /// written, leaving the last token read as the current one.
+    fn parse_param_type(&mut self) -> ParseResult<String> {
         self.expect_peek(TokenKind::Text)?;
+        let mut ty = self.curr.literal().to_string();
+        if self.peek.kind() == TokenKind::LAngle {
+            self.next_token();
+            ty.push('<');
+            ty.push_str(&self.parse_param_type()?);
+            self.expect_peek(TokenKind::RAngle)?;
+            ty.push('>');
+        }
+        Ok(ty)
+    }
+
+    fn parse_relation(&mut self) -> ParseResult<Relation> {
+        let start = self.curr_span;
+        self.expect_peek_name()?;
         let kind = self.curr.literal().to_string();
+        let name = self.curr_span;
         self.next_token();
         if self.curr.kind() != TokenKind::As {
             // NOTE this might be invalid syntax...
+            self.push_relation_source(start.to(name), name);
             return Ok(Relation {
                 kind,
                 aliases: Vec::new(),
@@ -94,38 +212,103 @@ impl Parser {
         let mut aliases = Vec::new();
         let first_alias = self.parse_alias()?;
         aliases.push(first_alias);
-        while self.peek.kind() == TokenKind::Or || self.peek.kind() == TokenKind::But {
-            let alias = if self.peek.kind() == TokenKind::But {
-                self.next_token();
-                self.parse_but_not()?
-            } else {
-                self.next_token();
-                self.next_token();
-                self.parse_alias()?
+        // aliases are joined by one of `or` and `and`, followed by any
+        // number of `but not`, so no precedence is needed to read them
+        let mut joined_by = None;
+        let mut negated = false;
+        loop {
+            let alias = match self.peek.kind() {
+                TokenKind::But => {
+                    negated = true;
+                    self.next_token();
+                    self.parse_but_not()?
+                }
+                op @ (TokenKind::And | TokenKind::Or)
+                    if negated || joined_by.is_some_and(|joined| joined != op) =>
+                {
+                    self.error_span = self.peek_span;
+                    return Err(ParserError::UnexpectedKeyword(op));
+                }
+                TokenKind::And => {
+                    joined_by = Some(TokenKind::And);
+                    self.next_token();
+                    self.parse_and()?
+                }
+                TokenKind::Or => {
+                    joined_by = Some(TokenKind::Or);
+                    self.next_token();
+                    self.next_token();
+                    self.parse_alias()?
+                }
+                _ => break,
             };
             aliases.push(alias)
         }
 
+        self.push_relation_source(start.to(self.curr_span), name);
         Ok(Relation { kind, aliases })
     }
 
+    fn push_relation_source(&mut self, span: Span, name: Span) {
+        let aliases = std::mem::take(&mut self.alias_sources);
+        if let Some(ty) = self.source.types.last_mut() {
+            ty.relations.push(RelationSource {
+                span,
+                name,
+                aliases,
+            });
+        }
+    }
+
+    fn push_alias_source(&mut self, start: Span, name: Span, has_parent: bool) {
+        let parent = has_parent.then_some(self.curr_span);
+        self.alias_sources.push(AliasSource {
+            span: start.to(self.curr_span),
+            name,
+            parent,
+        });
+    }
+
     fn parse_alias(&mut self) -> ParseResult<Alias> {
+        let name = self.curr_span;
+        self.error_span = name;
         let kind = match self.curr.kind() {
             TokenKind::This => AliasKind::This,
-            TokenKind::Text => AliasKind::Named(self.curr.literal().to_string()),
-            TokenKind::EOF => return Err(ParserError::UnexpectedEOF),
+            TokenKind::Text | TokenKind::Condition => {
+                AliasKind::Named(self.curr.literal().to_string())
+            }
+            TokenKind::EOF => {
+                // point just past the last token rather than at trailing whitespace
+                self.error_span = Span::new(self.prev_span.end, self.prev_span.end);
+                return Err(ParserError::UnexpectedEOF);
+            }
             _ => return Err(ParserError::UnexpectedKeyword(self.curr.kind())),
         };
 
         let parent = self.parse_alias_parent()?;
+        self.push_alias_source(name, name, parent.is_some());
         Ok(Alias { kind, parent })
     }
 
     fn parse_but_not(&mut self) -> ParseResult<Alias> {
+        let start = self.curr_span;
         self.expect_peek(TokenKind::Not)?;
-        self.expect_peek(TokenKind::Text)?;
+        self.expect_peek_name()?;
         let kind = AliasKind::Negative(self.curr.literal().to_string());
+        let name = self.curr_span;
         let parent = self.parse_alias_parent()?;
+        self.push_alias_source(start, name, parent.is_some());
+
+        Ok(Alias { kind, parent })
+    }
+
+    fn parse_and(&mut self) -> ParseResult<Alias> {
+        let start = self.curr_span;
+        self.expect_peek_name()?;
+        let kind = AliasKind::Intersection(self.curr.literal().to_string());
+        let name = self.curr_span;
+        let parent = self.parse_alias_parent()?;
+        self.push_alias_source(start, name, parent.is_some());
 
         Ok(Alias { kind, parent })
     }
@@ -133,7 +316,7 @@ impl Parser {
     fn parse_alias_parent(&mut self) -> ParseResult<Option<String>> {
         if self.peek.kind() == TokenKind::From {
             self.next_token();
-            self.expect_peek(TokenKind::Text)?;
+            self.expect_peek_name()?;
             let parent = Some(self.curr.literal().to_string());
             Ok(parent)
         } else {
@@ -144,6 +327,18 @@ impl Parser {
     fn next_token(&mut self) {
         let prev = std::mem::replace(&mut self.peek, self.lex.next_token());
         self.curr = prev;
+        self.prev_span = self.curr_span;
+        self.curr_span = std::mem::replace(&mut self.peek_span, self.lex.span());
+    }
+
+    /// Like `expect_peek(TokenKind::Text)`, but also accepts `condition`,
+    /// which is a keyword only at the start of a top-level block.
+    fn expect_peek_name(&mut self) -> ParseResult<()> {
+        if self.peek.kind() == TokenKind::Condition {
+            self.next_token();
+            return Ok(());
+        }
+        self.expect_peek(TokenKind::Text)
     }
 
     fn expect_peek(&mut self, expected: TokenKind) -> ParseResult<()> {
@@ -151,6 +346,10 @@ impl Parser {
             self.next_token();
             Ok(())
         } else {
+            self.error_span = match self.peek.kind() {
+                TokenKind::EOF => Span::new(self.curr_span.end, self.curr_span.end),
+                _ => self.peek_span,
+            };
             Err(ParserError::UnexpectedToken(expected, self.peek.kind()))
         }
     }
@@ -165,6 +364,7 @@ impl Display for ParserError {
             }
             UnexpectedKeyword(got) => write!(f, "Unexpected keyword: {got:?}"),
             UnexpectedEOF => write!(f, "received an unexpected EOF"),
+            UnknownParamType(ty) => write!(f, "unknown parameter type '{ty}'"),
         }
     }
 }
@@ -172,6 +372,7 @@ impl Display for ParserError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::condition::ParamType;
 
     #[test]
     fn can_parse_types() {
@@ -190,6 +391,7 @@ type org";
                     relations: Vec::new(),
                 },
             ],
+            conditions: Vec::new(),
         };
         assert_eq!(Ok(exp), parser.parse_document());
     }
@@ -232,6 +434,62 @@ type org";
         assert_eq!(Ok(exp), parser.parse_relation());
     }
 
+    #[test]
+    fn can_parse_and_alias() {
+        let i = "define write as self and member from org but not blocked";
+        let exp = Relation {
+            kind: "write".into(),
+            aliases: vec![
+                Alias {
+                    kind: AliasKind::This,
+                    parent: None,
+                },
+                Alias {
+                    kind: AliasKind::Intersection("member".into()),
+                    parent: Some("org".into()),
+                },
+                Alias {
+                    kind: AliasKind::Negative("blocked".into()),
+                    parent: None,
+                },
+            ],
+        };
+
+        let lex = Lexer::new(i);
+        let mut parser = Parser::from_lexer(lex);
+        assert_eq!(Ok(exp), parser.parse_relation());
+
+        let i = "define write as self but not blocked but not banned";
+        assert_eq!(Parser::new(i).parse_relation().unwrap().aliases.len(), 3);
+    }
+
+    #[test]
+    fn rejects_mixed_operators() {
+        let cases = [
+            (
+                "define write as self and member or owner",
+                TokenKind::Or,
+                32,
+            ),
+            (
+                "define write as self or member and owner",
+                TokenKind::And,
+                31,
+            ),
+            ("define write as self but not x or y", TokenKind::Or, 31),
+            ("define write as self but not x and y", TokenKind::And, 31),
+        ];
+        for (i, keyword, at) in cases {
+            let mut parser = Parser::new(i);
+            assert_eq!(
+                parser.parse_relation(),
+                Err(ParserError::UnexpectedKeyword(keyword)),
+                "{i}"
+            );
+            assert_eq!(parser.error_span().start, at, "{i}");
+        }
+    }
+
     #[test]
     fn error_eof_missing_relation_type() {
         let i = "define write as";
@@ -252,6 +510,23 @@ type org";
         assert_eq!(exp, parser.parse_relation());
     }
 
+    #[test]
+    fn error_span_points_at_token() {
+        let i = "type user
+type document
+  relations
+    define viewer as self or
+";
+        let mut parser = Parser::new(i);
+        assert_eq!(parser.parse_document(), Err(ParserError::UnexpectedEOF));
+        assert_eq!(parser.error_span().line_col(i), (3, 28));
+
+        let i = "type document\n  define viewer as self";
+        let mut parser = Parser::new(i);
+        assert!(parser.parse_document().is_err());
+        assert_eq!(parser.error_span().line_col(i), (1, 2));
+    }
+
     #[test]
     fn can_parse_relation_multiple_alias() {
         let i = "define write as self or owner or thing";
@@ -355,10 +630,181 @@ type document
                     ],
                 },
             ],
+            conditions: Vec::new(),
         };
 
         let lex = Lexer::new(i);
         let mut parser = Parser::from_lexer(lex);
         assert_eq!(Ok(exp), parser.parse_document());
     }
+
+    #[test]
+    fn records_source_map() {
+        let i = "type org
+  relations
+    define member as self
+    define viewer as member but not banned from parent";
+        let mut parser = Parser::new(i);
+        parser.parse_document().unwrap();
+        let exp = SourceMap {
+            types: vec![TypeSource {
+                span: Span::new(0, 101),
+                name: Span::new(5, 8),
+                relations: vec![
+                    RelationSource {
+                        span: Span::new(25, 46),
+                        name: Span::new(32, 38),
+                        aliases: vec![AliasSource {
+                            span: Span::new(42, 46),
+                            name: Span::new(42, 46),
+                            parent: None,
+                        }],
+                    },
+                    RelationSource {
+                        span: Span::new(51, 101),
+                        name: Span::new(58, 64),
+                        aliases: vec![
+                            AliasSource {
+                                span: Span::new(68, 74),
+                                name: Span::new(68, 74),
+                                parent: None,
+                            },
+                            AliasSource {
+                                span: Span::new(75, 101),
+                                name: Span::new(83, 89),
+                                parent: Some(Span::new(95, 101)),
+                            },
+                        ],
+                    },
+                ],
+            }],
+            conditions: Vec::new(),
+        };
+        assert_eq!(&exp, parser.source_map());
+    }
+
+    #[test]
+    fn can_parse_conditions() {
+        let i = "type user
+condition in_office(ip: ipaddress, cidrs: map<list<string>>) {
+  ip.in_cidr(cidrs['eu'][0])
+}
+type document";
+        let mut parser = Parser::new(i);
+        let doc = parser.parse_document().unwrap();
+        assert_eq!(doc.types.len(), 2);
+        assert_eq!(
+            doc.conditions,
+            vec![Condition {
+                name: "in_office".into(),
+                params: vec![
+                    Parameter {
+                        name: "ip".into(),
+                        ty: ParamType::IpAddress,
+                    },
+                    Parameter {
+                        name: "cidrs".into(),
+                        ty: "map<list<string>>".parse().unwrap(),
+                    },
+                ],
+                expression: "ip.in_cidr(cidrs['eu'][0])".into(),
+            }]
+        );
+        assert_eq!(
+            parser.source_map().conditions,
+            vec![ConditionSource {
+                span: Span::new(10, 103),
+                name: Span::new(20, 29),
+                params: vec![Span::new(30, 43), Span::new(45, 69)],
+                expression: Span::new(75, 101),
+            }]
+        );
+
+        let mut parser = Parser::new("condition c(x: set<int>) { x }");
+        assert_eq!(
+            parser.parse_document(),
+            Err(ParserError::UnknownParamType("set<int>".into()))
+        );
+        assert_eq!(parser.error_span(), Span::new(15, 23));
+
+        let mut parser = Parser::new("condition c(x: int y: int) { x }");
+        assert_eq!(
+            parser.parse_document(),
+            Err(ParserError::UnexpectedToken(
+                TokenKind::Comma,
+                TokenKind::Text
+            ))
+        );
+        assert_eq!(parser.error_span(), Span::new(19, 20));
+    }
+
+    #[test]
+    fn accepts_condition_as_name() {
+        let i = "type condition
+  relations
+    define condition as self
+    define viewer as condition or condition from condition
+    define editor as self and condition but not condition
+condition condition(condition: bool) { condition }";
+        let doc = Parser::new(i).parse_document().unwrap();
+        let relations = &doc.types[0].relations;
+        assert_eq!(doc.types[0].kind, "condition");
+        assert_eq!(relations[0].kind, "condition");
+        assert_eq!(
+            relations[1].aliases,
+            vec![
+                Alias {
+                    kind: AliasKind::Named("condition".into()),
+                    parent: None,
+                },
+                Alias {
+                    kind: AliasKind::Named("condition".into()),
+                    parent: Some("condition".into()),
+                },
+            ]
+        );
+        assert_eq!(
+            relations[2].aliases[1..],
+            [
+                Alias {
+                    kind: AliasKind::Intersection("condition".into()),
+                    parent: None,
+                },
+                Alias {
+                    kind: AliasKind::Negative("condition".into()),
+                    parent: None,
+                },
+            ]
+        );
+        assert_eq!(doc.conditions[0].name, "condition");
+        assert_eq!(doc.conditions[0].params[0].name, "condition");
+        assert_eq!(Parser::new(&doc.to_string()).parse_document(), Ok(doc));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let i = "type user
+type   document
+    relations
+  define owner as self
+  define viewer   as self or owner or viewer from parent but not blocked
+  define editor as owner and  member
+condition  recent(t: timestamp,now:timestamp){t > now - duration('1h')}";
+        let doc = Parser::new(i).parse_document().unwrap();
+        let formatted = doc.to_string();
+        assert_eq!(
+            formatted,
+            "type user
+type document
+  relations
+    define owner as self
+    define viewer as self or owner or viewer from parent but not blocked
+    define editor as owner and member
+condition recent(t: timestamp, now: timestamp) {
+  t > now - duration('1h')
+}
+"
+        );
+        assert_eq!(Parser::new(&formatted).parse_document(), Ok(doc));
+    }
 }
@@ -0,0 +1,694 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value as Json};
+
+use crate::eval::UserFilter;
+use crate::lexer::token::Span;
+use crate::tuple::{self, TupleCondition, TupleKey};
+use crate::ParserError;
+
+mod report;
+mod yaml;
+
+use yaml::{schema, Yaml};
+
+pub use report::{Outcome, Report};
+
+/// Result type for loading and running store files.
+pub type StoreResult<T> = Result<T, StoreError>;
+
+/// A one-based line and column in a source file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Enumerated error type for loading and running store files.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StoreError {
+    Io {
+        path: PathBuf,
+        message: String,
+    },
+    /// The file is not valid YAML.
+    Yaml {
+        message: String,
+        location: Location,
+    },
+    /// The YAML does not describe a store, e.g. a misspelled key.
+    Schema {
+        message: String,
+        location: Location,
+    },
+    /// The model failed to parse.
+    Model {
+        error: ParserError,
+        location: Location,
+    },
+    /// A tuple is malformed or does not fit the model.
+    Tuple {
+        message: String,
+        location: Location,
+    },
+    /// An error located in a file referenced by the store file.
+    InFile(PathBuf, Box<StoreError>),
+}
+
+/// A model test file in the format of the OpenFGA CLI's `.fga.yaml`
+/// store files: a model, tuples, and tests asserting check,
+/// list_objects and list_users results.
+///
+/// Referenced `model_file` and `tuple_file` paths are relative to the
+/// store file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StoreFile {
+    pub name: Option<String>,
+    pub model: Model,
+    /// Tuples shared by every test.
+    pub tuples: Vec<StoreTuple>,
+    pub tests: Vec<Test>,
+    path: Option<PathBuf>,
+}
+
+/// The DSL source of a store's model.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Model {
+    pub source: String,
+    origin: ModelOrigin,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum ModelOrigin {
+    /// Embedded in the store file; `literal` blocks map line for line.
+    Embedded {
+        location: Location,
+        literal: bool,
+    },
+    File(PathBuf),
+}
+
+/// A tuple and where it was written.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StoreTuple {
+    pub key: TupleKey,
+    pub location: Location,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Test {
+    pub name: String,
+    /// Tuples added to the store's tuples for this test only.
+    pub tuples: Vec<StoreTuple>,
+    pub assertions: Vec<Assertion>,
+}
+
+/// An expected answer to a query.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Assertion {
+    pub query: Query,
+    pub expected: Answer,
+    /// Tuples that only exist while evaluating the query.
+    pub contextual_tuples: Vec<StoreTuple>,
+    /// Request context conditional tuples are evaluated with.
+    pub context: Map<String, Json>,
+    pub location: Location,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Query {
+    Check {
+        object: String,
+        relation: String,
+        user: String,
+    },
+    ListObjects {
+        ty: String,
+        relation: String,
+        user: String,
+    },
+    ListUsers {
+        object: String,
+        relation: String,
+        filters: Vec<UserFilter>,
+    },
+}
+
+/// The answer to a [Query](crate::store::Query). Lists are sorted.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Answer {
+    Allowed(bool),
+    Objects(Vec<String>),
+    Users(Vec<String>),
+}
+
+impl StoreFile {
+    /// Loads the store file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> StoreResult<Self> {
+        let path = path.as_ref();
+        let input = read(path)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut store = Self::parse_in(&input, dir)?;
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    /// Parses a store file, resolving referenced files relative
+    /// to the current directory.
+    pub fn parse(input: &str) -> StoreResult<Self> {
+        Self::parse_in(input, Path::new("."))
+    }
+
+    /// The path the store file was loaded from.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn parse_in(input: &str, dir: &Path) -> StoreResult<Self> {
+        const WHAT: &str = "store file";
+        let root = yaml::load(input)?;
+        let fields = root.fields(
+            WHAT,
+            &[
+                "name",
+                "model",
+                "model_file",
+                "tuples",
+                "tuple_file",
+                "tuple_files",
+                "tests",
+            ],
+        )?;
+
+        let name = match fields.get("name") {
+            Some(name) => Some(name.string("name")?.to_string()),
+            None => None,
+        };
+        let model = match (fields.get("model"), fields.get("model_file")) {
+            (Some(model), None) => Model {
+                source: model.string("model")?.to_string(),
+                origin: ModelOrigin::Embedded {
+                    location: model.location,
+                    literal: model.is_literal(),
+                },
+            },
+            (None, Some(file)) => {
+                let path = dir.join(file.string("model_file")?);
+                Model {
+                    source: read(&path)?,
+                    origin: ModelOrigin::File(path),
+                }
+            }
+            (Some(_), Some(file)) => {
+                return Err(schema(
+                    "only one of 'model' and 'model_file' may be given".into(),
+                    file.location,
+                ))
+            }
+            (None, None) => {
+                return Err(schema(
+                    "missing 'model' or 'model_file' in store file".into(),
+                    root.location,
+                ))
+            }
+        };
+        let tuples = read_tuples(&fields, dir)?;
+
+        let mut tests = Vec::new();
+        if let Some(items) = fields.get("tests") {
+            for test in items.items("tests")? {
+                tests.push(Test::parse(test, dir)?);
+            }
+        }
+
+        Ok(StoreFile {
+            name,
+            model,
+            tuples,
+            tests,
+            path: None,
+        })
+    }
+}
+
+impl Model {
+    /// Locates a parse error at `span` of the model within its file.
+    pub(crate) fn error(&self, error: ParserError, span: Span) -> StoreError {
+        let (line, col) = span.line_col(&self.source);
+        match &self.origin {
+            ModelOrigin::Embedded {
+                location,
+                literal: true,
+            } => StoreError::Model {
+                error,
+                location: Location {
+                    line: location.line + line,
+                    column: location.column + col,
+                },
+            },
+            ModelOrigin::Embedded { location, .. } => StoreError::Model {
+                error,
+                location: *location,
+            },
+            ModelOrigin::File(path) => StoreError::InFile(
+                path.clone(),
+                Box::new(StoreError::Model {
+                    error,
+                    location: Location {
+                        line: line + 1,
+                        column: col + 1,
+                    },
+                }),
+            ),
+        }
+    }
+}
+
+impl Test {
+    fn parse(node: &Yaml, dir: &Path) -> StoreResult<Self> {
+        const WHAT: &str = "test";
+        let fields = node.fields(
+            WHAT,
+            &[
+                "name",
+                "description",
+                "tuples",
+                "tuple_file",
+                "tuple_files",
+                "check",
+                "list_objects",
+                "list_users",
+            ],
+        )?;
+        let name = fields.required("name", WHAT)?.string("name")?.to_string();
+        let tuples = read_tuples(&fields, dir)?;
+
+        let mut assertions = Vec::new();
+        if let Some(checks) = fields.get("check") {
+            for check in checks.items("check")? {
+                parse_check(check, &mut assertions)?;
+            }
+        }
+        if let Some(lists) = fields.get("list_objects") {
+            for list in lists.items("list_objects")? {
+                parse_list_objects(list, &mut assertions)?;
+            }
+        }
+        if let Some(lists) = fields.get("list_users") {
+            for list in lists.items("list_users")? {
+                parse_list_users(list, &mut assertions)?;
+            }
+        }
+
+        Ok(Test {
+            name,
+            tuples,
+            assertions,
+        })
+    }
+}
+
+fn parse_check(node: &Yaml, assertions: &mut Vec<Assertion>) -> StoreResult<()> {
+    const WHAT: &str = "check";
+    let fields = node.fields(
+        WHAT,
+        &[
+            "user",
+            "object",
+            "contextual_tuples",
+            "context",
+            "assertions",
+        ],
+    )?;
+    let user = fields.required("user", WHAT)?.string("user")?;
+    let object = fields.required("object", WHAT)?.string("object")?;
+    let contextual_tuples = read_contextual_tuples(&fields)?;
+    let context = read_context(&fields)?;
+    for (relation, expected) in fields.required("assertions", WHAT)?.entries("assertions")? {
+        assertions.push(Assertion {
+            query: Query::Check {
+                object: object.to_string(),
+                relation: relation.string("relation")?.to_string(),
+                user: user.to_string(),
+            },
+            expected: Answer::Allowed(expected.boolean("check assertion")?),
+            contextual_tuples: contextual_tuples.clone(),
+            context: context.clone(),
+            location: relation.location,
+        });
+    }
+    Ok(())
+}
+
+fn parse_list_objects(node: &Yaml, assertions: &mut Vec<Assertion>) -> StoreResult<()> {
+    const WHAT: &str = "list_objects";
+    let fields = node.fields(
+        WHAT,
+        &["user", "type", "contextual_tuples", "context", "assertions"],
+    )?;
+    let user = fields.required("user", WHAT)?.string("user")?;
+    let ty = fields.required("type", WHAT)?.string("type")?;
+    let contextual_tuples = read_contextual_tuples(&fields)?;
+    let context = read_context(&fields)?;
+    for (relation, expected) in fields.required("assertions", WHAT)?.entries("assertions")? {
+        assertions.push(Assertion {
+            query: Query::ListObjects {
+                ty: ty.to_string(),
+                relation: relation.string("relation")?.to_string(),
+                user: user.to_string(),
+            },
+            expected: Answer::Objects(strings(expected, "list_objects assertion")?),
+            contextual_tuples: contextual_tuples.clone(),
+            context: context.clone(),
+            location: relation.location,
+        });
+    }
+    Ok(())
+}
+
+fn parse_list_users(node: &Yaml, assertions: &mut Vec<Assertion>) -> StoreResult<()> {
+    const WHAT: &str = "list_users";
+    let fields = node.fields(
+        WHAT,
+        &[
+            "object",
+            "user_filter",
+            "contextual_tuples",
+            "context",
+            "assertions",
+        ],
+    )?;
+    let object = fields.required("object", WHAT)?.string("object")?;
+
+    let mut filters = Vec::new();
+    for filter in fields.required("user_filter", WHAT)?.items("user_filter")? {
+        let filter = filter.fields("user_filter", &["type", "relation"])?;
+        filters.push(UserFilter {
+            ty: filter
+                .required("type", "user_filter")?
+                .string("type")?
+                .into(),
+            relation: match filter.get("relation") {
+                Some(relation) => Some(relation.string("relation")?.into()),
+                None => None,
+            },
+        });
+    }
+
+    let contextual_tuples = read_contextual_tuples(&fields)?;
+    let context = read_context(&fields)?;
+    for (relation, expected) in fields.required("assertions", WHAT)?.entries("assertions")? {
+        let expected = expected.fields("list_users assertion", &["users"])?;
+        let users = expected.required("users", "list_users assertion")?;
+        assertions.push(Assertion {
+            query: Query::ListUsers {
+                object: object.to_string(),
+                relation: relation.string("relation")?.to_string(),
+                filters: filters.clone(),
+            },
+            expected: Answer::Users(strings(users, "users")?),
+            contextual_tuples: contextual_tuples.clone(),
+            context: context.clone(),
+            location: relation.location,
+        });
+    }
+    Ok(())
+}
+
+/// A sorted list of strings.
+fn strings(node: &Yaml, what: &str) -> StoreResult<Vec<String>> {
+    let mut strings = node
+        .items(what)?
+        .iter()
+        .map(|item| item.string(what).map(str::to_string))
+        .collect::<StoreResult<Vec<_>>>()?;
+    strings.sort();
+    Ok(strings)
+}
+
+/// Reads the `tuples`, `tuple_file` and `tuple_files` of a store or test.
+fn read_tuples(fields: &yaml::Fields, dir: &Path) -> StoreResult<Vec<StoreTuple>> {
+    let mut tuples = Vec::new();
+    if let Some(items) = fields.get("tuples") {
+        for item in items.items("tuples")? {
+            tuples.push(parse_tuple(item)?);
+        }
+    }
+
+    let mut files = Vec::new();
+    if let Some(file) = fields.get("tuple_file") {
+        files.push(file);
+    }
+    if let Some(items) = fields.get("tuple_files") {
+        files.extend(items.items("tuple_files")?);
+    }
+    for file in files {
+        let path = dir.join(file.string("tuple_file")?);
+        let keys =
+            read_tuple_file(&path).map_err(|e| StoreError::InFile(path.clone(), Box::new(e)))?;
+        // tuples from other files are located at their reference
+        tuples.extend(keys.into_iter().map(|key| StoreTuple {
+            key,
+            location: file.location,
+        }));
+    }
+    Ok(tuples)
+}
+
+fn read_contextual_tuples(fields: &yaml::Fields) -> StoreResult<Vec<StoreTuple>> {
+    match fields.get("contextual_tuples") {
+        Some(items) => items
+            .items("contextual_tuples")?
+            .iter()
+            .map(parse_tuple)
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn read_context(fields: &yaml::Fields) -> StoreResult<Map<String, Json>> {
+    match fields.get("context") {
+        Some(context) => context.object("context"),
+        None => Ok(Map::new()),
+    }
+}
+
+/// Reads a YAML or JSON list of tuples, or one `object#relation@user`
+/// tuple per line.
+fn read_tuple_file(path: &Path) -> StoreResult<Vec<TupleKey>> {
+    let input = read(path)?;
+    let structured = path
+        .extension()
+        .is_some_and(|ext| ext == "yaml" || ext == "yml" || ext == "json");
+    if !structured {
+        return tuple::parse_tuples(&input).map_err(|e| {
+            let (line, col) = e.span.line_col(&input);
+            StoreError::Tuple {
+                message: e.to_string(),
+                location: Location {
+                    line: line + 1,
+                    column: col + 1,
+                },
+            }
+        });
+    }
+
+    let root = yaml::load(&input)?;
+    root.items("tuple file")?
+        .iter()
+        .map(|item| parse_tuple(item).map(|tuple| tuple.key))
+        .collect()
+}
+
+fn parse_tuple(node: &Yaml) -> StoreResult<StoreTuple> {
+    const WHAT: &str = "tuple";
+    let fields = node.fields(WHAT, &["user", "relation", "object", "condition"])?;
+    let mut key = TupleKey::new(
+        fields.required("object", WHAT)?.string("object")?,
+        fields.required("relation", WHAT)?.string("relation")?,
+        fields.required("user", WHAT)?.string("user")?,
+    );
+    if let Some(condition) = fields.get("condition") {
+        let condition = condition.fields("condition", &["name", "context"])?;
+        key.condition = Some(TupleCondition {
+            name: condition
+                .required("name", "condition")?
+                .string("name")?
+                .to_string(),
+            context: read_context(&condition)?,
+        });
+    }
+    Ok(StoreTuple {
+        key,
+        location: node.location,
+    })
+}
+
+fn read(path: &Path) -> StoreResult<String> {
+    fs::read_to_string(path).map_err(|e| StoreError::Io {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Query::Check {
+                object,
+                relation,
+                user,
+            } => write!(f, "check {object}#{relation}@{user}"),
+            Query::ListObjects { ty, relation, user } => {
+                write!(f, "list_objects {ty}#{relation}@{user}")
+            }
+            Query::ListUsers {
+                object,
+                relation,
+                filters,
+            } => {
+                let filters: Vec<String> = filters
+                    .iter()
+                    .map(|filter| match &filter.relation {
+                        Some(relation) => format!("{}#{relation}", filter.ty),
+                        None => filter.ty.clone(),
+                    })
+                    .collect();
+                write!(f, "list_users {object}#{relation}@{}", filters.join(","))
+            }
+        }
+    }
+}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use StoreError::*;
+        match self {
+            Io { path, message } => write!(f, "{}: {message}", path.display()),
+            Yaml { message, location } => write!(f, "{location}: invalid YAML: {message}"),
+            Schema { message, location } => write!(f, "{location}: {message}"),
+            Model { error, location } => write!(f, "{location}: invalid model: {error}"),
+            Tuple { message, location } => write!(f, "{location}: invalid tuple: {message}"),
+            InFile(path, error) => write!(f, "{}:{error}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORE: &str = "name: docs
+model: |
+  type user
+  type document
+    relations
+      define viewer as self
+tuples:
+  - user: user:anne
+    relation: viewer
+    object: document:1
+tests:
+  - name: viewers
+    check:
+      - user: user:anne
+        object: document:1
+        assertions:
+          viewer: true
+    list_users:
+      - object: document:1
+        user_filter:
+          - type: user
+        assertions:
+          viewer:
+            users: [user:anne]
+";
+
+    #[test]
+    fn parses_store_file() {
+        let store = StoreFile::parse(STORE).unwrap();
+        assert_eq!(store.name.as_deref(), Some("docs"));
+        assert_eq!(
+            store.tuples,
+            vec![StoreTuple {
+                key: TupleKey::new("document:1", "viewer", "user:anne"),
+                location: Location { line: 8, column: 5 },
+            }]
+        );
+        let test = &store.tests[0];
+        assert_eq!(test.name, "viewers");
+        assert_eq!(
+            test.assertions,
+            vec![
+                Assertion {
+                    query: Query::Check {
+                        object: "document:1".into(),
+                        relation: "viewer".into(),
+                        user: "user:anne".into(),
+                    },
+                    expected: Answer::Allowed(true),
+                    contextual_tuples: Vec::new(),
+                    context: Map::new(),
+                    location: Location {
+                        line: 17,
+                        column: 11,
+                    },
+                },
+                Assertion {
+                    query: Query::ListUsers {
+                        object: "document:1".into(),
+                        relation: "viewer".into(),
+                        filters: vec!["user".into()],
+                    },
+                    expected: Answer::Users(vec!["user:anne".into()]),
+                    contextual_tuples: Vec::new(),
+                    context: Map::new(),
+                    location: Location {
+                        line: 23,
+                        column: 11,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let input = STORE.replace("    check:", "    checks:");
+        assert_eq!(
+            StoreFile::parse(&input),
+            Err(StoreError::Schema {
+                message: "unknown key 'checks' in test".into(),
+                location: Location {
+                    line: 13,
+                    column: 5
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn locates_model_errors() {
+        let input = STORE.replace("define viewer as self", "define viewer as");
+        let store = StoreFile::parse(&input).unwrap();
+        let err = store.run().unwrap_err();
+        assert_eq!(
+            err,
+            StoreError::Model {
+                error: ParserError::UnexpectedEOF,
+                location: Location {
+                    line: 6,
+                    column: 23
+                },
+            }
+        );
+    }
+}
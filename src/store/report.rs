@@ -0,0 +1,344 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use super::{Answer, Assertion, Query, StoreError, StoreFile, StoreResult, StoreTuple};
+use crate::eval::{Checker, EvalResult, TupleStore};
+use crate::validate::Validator;
+use crate::Parser;
+
+/// The results of running the tests of a
+/// [StoreFile](crate::store::StoreFile).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Report {
+    pub outcomes: Vec<Outcome>,
+    path: Option<PathBuf>,
+}
+
+/// The result of evaluating one [Assertion](crate::store::Assertion).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Outcome {
+    pub test: String,
+    pub assertion: Assertion,
+    pub actual: EvalResult<Answer>,
+}
+
+impl StoreFile {
+    /// Parses the model and evaluates every assertion of every test,
+    /// each test against the store's tuples plus its own.
+    pub fn run(&self) -> StoreResult<Report> {
+        let mut parser = Parser::new(&self.model.source);
+        let doc = parser
+            .parse_document()
+            .map_err(|e| self.model.error(e, parser.error_span()))?;
+        let validator = Validator::new(&doc);
+        let validate = |tuples: &[StoreTuple]| -> StoreResult<()> {
+            for tuple in tuples {
+                validator
+                    .validate_tuple(&tuple.key)
+                    .map_err(|e| StoreError::Tuple {
+                        message: format!("{}: {e}", tuple.key),
+                        location: tuple.location,
+                    })?;
+            }
+            Ok(())
+        };
+        validate(&self.tuples)?;
+
+        let mut outcomes = Vec::new();
+        for test in &self.tests {
+            validate(&test.tuples)?;
+            let mut tuples = TupleStore::new();
+            for tuple in self.tuples.iter().chain(&test.tuples) {
+                tuples.insert(&tuple.key);
+            }
+            for assertion in &test.assertions {
+                validate(&assertion.contextual_tuples)?;
+                let contextual = assertion.contextual_tuples.iter().map(|t| &t.key);
+                let checker = Checker::new(&doc, &tuples)
+                    .with_context(assertion.context.clone())
+                    .with_contextual_tuples(contextual);
+                let actual = evaluate(&checker, &assertion.query);
+                outcomes.push(Outcome {
+                    test: test.name.clone(),
+                    assertion: assertion.clone(),
+                    actual,
+                });
+            }
+        }
+
+        Ok(Report {
+            outcomes,
+            path: self.path.clone(),
+        })
+    }
+}
+
+fn evaluate(checker: &Checker, query: &Query) -> EvalResult<Answer> {
+    match query {
+        Query::Check {
+            object,
+            relation,
+            user,
+        } => checker.check(object, relation, user).map(Answer::Allowed),
+        Query::ListObjects { ty, relation, user } => {
+            let mut objects = checker
+                .list_objects(ty, relation, user)?
+                .collect::<EvalResult<Vec<_>>>()?;
+            objects.sort();
+            Ok(Answer::Objects(objects))
+        }
+        Query::ListUsers {
+            object,
+            relation,
+            filters,
+        } => {
+            let users = checker.list_users(object, relation, filters)?;
+            if let Some((_, e)) = users.errors.into_iter().next() {
+                return Err(e);
+            }
+            let mut users: Vec<String> = users
+                .objects
+                .into_iter()
+                .chain(users.usersets)
+                .chain(users.wildcards)
+                .collect();
+            users.sort();
+            Ok(Answer::Users(users))
+        }
+    }
+}
+
+impl Report {
+    /// Whether every assertion passed.
+    pub fn passed(&self) -> bool {
+        self.outcomes.iter().all(Outcome::passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &Outcome> {
+        self.outcomes.iter().filter(|o| !o.passed())
+    }
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.actual.as_ref() == Ok(&self.assertion.expected)
+    }
+
+    fn render(&self, f: &mut std::fmt::Formatter<'_>, path: Option<&Path>) -> std::fmt::Result {
+        let status = if self.passed() { "PASS" } else { "FAIL" };
+        write!(f, "{status} ")?;
+        if let Some(path) = path {
+            write!(f, "{}:", path.display())?;
+        }
+        writeln!(
+            f,
+            "{} {}: {}",
+            self.assertion.location, self.test, self.assertion.query
+        )?;
+
+        match (&self.assertion.expected, &self.actual) {
+            (_, Err(e)) => writeln!(f, "  error: {e}"),
+            (Answer::Allowed(exp), Ok(Answer::Allowed(got))) if exp != got => {
+                writeln!(f, "  expected {exp}, got {got}")
+            }
+            (Answer::Objects(exp), Ok(Answer::Objects(got)))
+            | (Answer::Users(exp), Ok(Answer::Users(got))) => {
+                for missing in exp.iter().filter(|e| !got.contains(e)) {
+                    writeln!(f, "  - {missing}")?;
+                }
+                for extra in got.iter().filter(|g| !exp.contains(g)) {
+                    writeln!(f, "  + {extra}")?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.render(f, None)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for outcome in &self.outcomes {
+            outcome.render(f, self.path.as_deref())?;
+        }
+        let failed = self.failures().count();
+        writeln!(
+            f,
+            "{} passed, {failed} failed",
+            self.outcomes.len() - failed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::EvalError;
+
+    const STORE: &str = "model: |
+  type user
+  type group
+    relations
+      define member as self
+  type document
+    relations
+      define blocked as self
+      define viewer as self but not blocked
+tuples:
+  - user: group:eng#member
+    relation: viewer
+    object: document:1
+  - user: user:anne
+    relation: member
+    object: group:eng
+tests:
+  - name: blocking
+    tuples:
+      - user: user:anne
+        relation: blocked
+        object: document:1
+    check:
+      - user: user:anne
+        object: document:1
+        assertions:
+          viewer: true
+          owner: false
+    list_objects:
+      - user: user:anne
+        type: document
+        assertions:
+          viewer: []
+  - name: members
+    list_users:
+      - object: document:1
+        user_filter:
+          - type: user
+        assertions:
+          viewer:
+            users: [user:bob]
+";
+
+    #[test]
+    fn reports_failures() {
+        let report = StoreFile::parse(STORE).unwrap().run().unwrap();
+        assert!(!report.passed());
+        assert_eq!(
+            report.outcomes[1].actual,
+            Err(EvalError::UnknownRelation(
+                "document".into(),
+                "owner".into()
+            ))
+        );
+        assert_eq!(
+            report.to_string(),
+            "FAIL 27:11 blocking: check document:1#viewer@user:anne
+  expected true, got false
+FAIL 28:11 blocking: check document:1#owner@user:anne
+  error: relation 'owner' is not defined on type 'document'
+PASS 33:11 blocking: list_objects document#viewer@user:anne
+FAIL 40:11 members: list_users document:1#viewer@user
+  - user:bob
+  + user:anne
+1 passed, 3 failed
+"
+        );
+    }
+
+    #[test]
+    fn rejects_tuples_outside_model() {
+        let input = STORE.replace("relation: blocked", "relation: editor");
+        let err = StoreFile::parse(&input).unwrap().run().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "20:9: invalid tuple: document:1#editor@user:anne: \
+             relation 'editor' is not defined on type 'document'"
+        );
+    }
+
+    #[test]
+    fn applies_contextual_tuples_per_assertion() {
+        let input = STORE.replace(
+            "          viewer: []",
+            "          viewer: []
+      - user: user:bob
+        type: document
+        contextual_tuples:
+          - user: user:bob
+            relation: member
+            object: group:eng
+        assertions:
+          viewer: [document:1]",
+        );
+        let report = StoreFile::parse(&input).unwrap().run().unwrap();
+        let outcome = &report.outcomes[3];
+        assert_eq!(outcome.assertion.contextual_tuples.len(), 1);
+        assert!(outcome.passed(), "{outcome}");
+        // the contextual member does not leak into later assertions
+        assert_eq!(
+            report.outcomes[4].actual,
+            Ok(Answer::Users(vec!["user:anne".into()]))
+        );
+    }
+    #[test]
+    fn evaluates_conditions_with_context() {
+        let input = "model: |
+  type user
+  type document
+    relations
+      define viewer as self
+  condition in_office(ip: ipaddress, max_level: int, level: int) {
+    ip.in_cidr('10.0.0.0/8') && level <= max_level
+  }
+tuples:
+  - user: user:anne
+    relation: viewer
+    object: document:1
+    condition:
+      name: in_office
+      context:
+        max_level: 3
+tests:
+  - name: office
+    check:
+      - user: user:anne
+        object: document:1
+        context:
+          ip: 10.1.2.3
+          level: 2
+        assertions:
+          viewer: true
+      - user: user:anne
+        object: document:1
+        context:
+          ip: 192.168.0.1
+          level: 2
+        assertions:
+          viewer: false
+      - user: user:anne
+        object: document:1
+        assertions:
+          viewer: false
+";
+        let report = StoreFile::parse(input).unwrap().run().unwrap();
+        assert_eq!(report.outcomes[0].actual, Ok(Answer::Allowed(true)));
+        assert_eq!(report.outcomes[1].actual, Ok(Answer::Allowed(false)));
+        assert_eq!(
+            report.outcomes[2].actual,
+            Err(EvalError::MissingContext(vec!["ip".into(), "level".into()]))
+        );
+
+        let input = input.replace("name: in_office", "name: at_home");
+        let err = StoreFile::parse(&input).unwrap().run().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "10:5: invalid tuple: document:1#viewer@user:anne with at_home {\"max_level\":3}: \
+             condition 'at_home' is not defined"
+        );
+    }
+}
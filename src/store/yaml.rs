@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Number, Value as Json};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+use super::{Location, StoreError, StoreResult};
+
+/// A YAML node, located in its source.
+#[derive(Debug, Clone)]
+pub(super) struct Yaml {
+    pub(super) value: Value,
+    pub(super) location: Location,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum Value {
+    Null,
+    Scalar(String, TScalarStyle),
+    Seq(Vec<Yaml>),
+    Map(Vec<(Yaml, Yaml)>),
+}
+
+/// The entries of a mapping whose keys have been checked.
+pub(super) struct Fields<'y> {
+    node: &'y Yaml,
+    entries: &'y [(Yaml, Yaml)],
+}
+
+#[derive(Default)]
+struct Events(Vec<(Event, Marker)>);
+
+impl MarkedEventReceiver for Events {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        self.0.push((ev, mark));
+    }
+}
+
+struct Builder {
+    events: std::vec::IntoIter<(Event, Marker)>,
+    anchors: HashMap<usize, Yaml>,
+}
+
+impl From<Marker> for Location {
+    fn from(mark: Marker) -> Self {
+        Location {
+            line: mark.line(),
+            column: mark.col() + 1,
+        }
+    }
+}
+
+/// Loads the first document of `input`.
+pub(super) fn load(input: &str) -> StoreResult<Yaml> {
+    let mut events = Events::default();
+    Parser::new_from_str(input)
+        .load(&mut events, false)
+        .map_err(|e| StoreError::Yaml {
+            message: e.info().to_string(),
+            location: (*e.marker()).into(),
+        })?;
+    let mut builder = Builder {
+        events: events.0.into_iter(),
+        anchors: HashMap::new(),
+    };
+    builder.document()
+}
+
+impl Builder {
+    fn document(&mut self) -> StoreResult<Yaml> {
+        loop {
+            match self.events.next() {
+                Some((Event::StreamStart | Event::DocumentStart, _)) => continue,
+                Some((ev, mark)) => return self.node(ev, mark),
+                None => {
+                    return Ok(Yaml {
+                        value: Value::Null,
+                        location: Location { line: 1, column: 1 },
+                    })
+                }
+            }
+        }
+    }
+
+    fn next(&mut self) -> StoreResult<(Event, Marker)> {
+        self.events.next().ok_or_else(|| StoreError::Yaml {
+            message: "unexpected end of document".into(),
+            location: Location::default(),
+        })
+    }
+
+    fn node(&mut self, ev: Event, mark: Marker) -> StoreResult<Yaml> {
+        let mut location = mark.into();
+        let (value, anchor) = match ev {
+            Event::Scalar(s, style, anchor, _) => {
+                let null = style == TScalarStyle::Plain && matches!(s.as_str(), "" | "~" | "null");
+                let value = if null {
+                    Value::Null
+                } else {
+                    Value::Scalar(s, style)
+                };
+                (value, anchor)
+            }
+            Event::SequenceStart(anchor, _) => {
+                let mut items = Vec::new();
+                loop {
+                    match self.next()? {
+                        (Event::SequenceEnd, _) => break,
+                        (ev, mark) => items.push(self.node(ev, mark)?),
+                    }
+                }
+                (Value::Seq(items), anchor)
+            }
+            Event::MappingStart(anchor, _) => {
+                let mut entries = Vec::new();
+                loop {
+                    let key = match self.next()? {
+                        (Event::MappingEnd, _) => break,
+                        (ev, mark) => self.node(ev, mark)?,
+                    };
+                    let (ev, mark) = self.next()?;
+                    entries.push((key, self.node(ev, mark)?));
+                }
+                // block mappings are marked after their first key
+                if let Some((key, _)) = entries.first() {
+                    location = key.location;
+                }
+                (Value::Map(entries), anchor)
+            }
+            Event::Alias(id) => {
+                return self.anchors.get(&id).cloned().ok_or(StoreError::Yaml {
+                    message: "unknown alias".into(),
+                    location,
+                })
+            }
+            ev => {
+                return Err(StoreError::Yaml {
+                    message: format!("unexpected {ev:?}"),
+                    location,
+                })
+            }
+        };
+        let yaml = Yaml { value, location };
+        if anchor > 0 {
+            self.anchors.insert(anchor, yaml.clone());
+        }
+        Ok(yaml)
+    }
+}
+
+pub(super) fn schema(message: String, location: Location) -> StoreError {
+    StoreError::Schema { message, location }
+}
+
+impl Yaml {
+    /// The entries of a mapping, rejecting keys not in `known`.
+    pub(super) fn fields(&self, what: &str, known: &[&str]) -> StoreResult<Fields<'_>> {
+        let entries = match &self.value {
+            Value::Map(entries) => entries.as_slice(),
+            Value::Null => &[],
+            _ => {
+                return Err(schema(
+                    format!("expected {what} to be a mapping"),
+                    self.location,
+                ))
+            }
+        };
+        for (key, _) in entries {
+            let name = key.string("key")?;
+            if !known.contains(&name) {
+                return Err(schema(
+                    format!("unknown key '{name}' in {what}"),
+                    key.location,
+                ));
+            }
+        }
+        Ok(Fields {
+            node: self,
+            entries,
+        })
+    }
+
+    /// The entries of a mapping with arbitrary string keys.
+    pub(super) fn entries(&self, what: &str) -> StoreResult<Vec<(&Yaml, &Yaml)>> {
+        match &self.value {
+            Value::Map(entries) => Ok(entries.iter().map(|(k, v)| (k, v)).collect()),
+            Value::Null => Ok(Vec::new()),
+            _ => Err(schema(
+                format!("expected {what} to be a mapping"),
+                self.location,
+            )),
+        }
+    }
+
+    pub(super) fn items(&self, what: &str) -> StoreResult<&[Yaml]> {
+        match &self.value {
+            Value::Seq(items) => Ok(items),
+            Value::Null => Ok(&[]),
+            _ => Err(schema(
+                format!("expected {what} to be a list"),
+                self.location,
+            )),
+        }
+    }
+
+    pub(super) fn string(&self, what: &str) -> StoreResult<&str> {
+        match &self.value {
+            Value::Scalar(s, _) => Ok(s),
+            _ => Err(schema(
+                format!("expected {what} to be a string"),
+                self.location,
+            )),
+        }
+    }
+
+    pub(super) fn boolean(&self, what: &str) -> StoreResult<bool> {
+        match &self.value {
+            Value::Scalar(s, TScalarStyle::Plain) if s == "true" => Ok(true),
+            Value::Scalar(s, TScalarStyle::Plain) if s == "false" => Ok(false),
+            _ => Err(schema(
+                format!("expected {what} to be true or false"),
+                self.location,
+            )),
+        }
+    }
+
+    /// The node as JSON, reading plain scalars as YAML's core schema does.
+    pub(super) fn json(&self, what: &str) -> StoreResult<Json> {
+        Ok(match &self.value {
+            Value::Null => Json::Null,
+            Value::Scalar(s, TScalarStyle::Plain) => match s.as_str() {
+                "true" => Json::Bool(true),
+                "false" => Json::Bool(false),
+                _ => match (s.parse::<i64>(), s.parse::<f64>()) {
+                    (Ok(int), _) => Json::from(int),
+                    (_, Ok(float)) => Number::from_f64(float).map_or(Json::Null, Json::Number),
+                    _ => Json::String(s.clone()),
+                },
+            },
+            Value::Scalar(s, _) => Json::String(s.clone()),
+            Value::Seq(items) => Json::Array(
+                items
+                    .iter()
+                    .map(|item| item.json(what))
+                    .collect::<StoreResult<_>>()?,
+            ),
+            Value::Map(_) => Json::Object(self.object(what)?),
+        })
+    }
+
+    /// A mapping as a JSON object.
+    pub(super) fn object(&self, what: &str) -> StoreResult<Map<String, Json>> {
+        self.entries(what)?
+            .into_iter()
+            .map(|(key, value)| Ok((key.string("key")?.to_string(), value.json(what)?)))
+            .collect()
+    }
+
+    /// Whether the node is a `|` block, so its lines map onto the source.
+    pub(super) fn is_literal(&self) -> bool {
+        matches!(self.value, Value::Scalar(_, TScalarStyle::Literal))
+    }
+}
+
+impl<'y> Fields<'y> {
+    pub(super) fn get(&self, key: &str) -> Option<&'y Yaml> {
+        self.entries
+            .iter()
+            .find(|(k, _)| matches!(&k.value, Value::Scalar(s, _) if s == key))
+            .map(|(_, v)| v)
+    }
+
+    pub(super) fn required(&self, key: &str, what: &str) -> StoreResult<&'y Yaml> {
+        self.get(key)
+            .ok_or_else(|| schema(format!("missing '{key}' in {what}"), self.node.location))
+    }
+}
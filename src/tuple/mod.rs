@@ -0,0 +1,493 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+use crate::lexer::token::Span;
+
+/// Result type for parsing relationship tuples.
+pub type TupleResult<T> = Result<T, TupleError>;
+
+/// A relationship tuple relating a `user` to an `object` through a
+/// `relation`, written `object#relation@user`, optionally followed by
+/// `with condition` and a JSON object of condition context.
+///
+/// ```
+/// use openfga_dsl_parser::tuple::TupleKey;
+///
+/// let tuple: TupleKey = "folder:x#viewer@group:eng#member".parse().unwrap();
+/// assert_eq!(tuple.user, "group:eng#member");
+///
+/// let tuple = TupleKey::parse(r#"document:1#viewer@user:anne with in_office {"ip": "10.0.0.1"}"#)
+///     .unwrap();
+/// assert_eq!(tuple.condition.unwrap().name, "in_office");
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TupleKey {
+    pub object: String,
+    pub relation: String,
+    pub user: String,
+    pub condition: Option<TupleCondition>,
+}
+
+/// A condition a [TupleKey](crate::tuple::TupleKey) is subject to, with
+/// the context stored alongside the tuple.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TupleCondition {
+    pub name: String,
+    pub context: Map<String, Value>,
+}
+
+/// The user of a relationship tuple.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum User<'a> {
+    /// An object, e.g. `user:anne`.
+    Object(&'a str),
+    /// The object and relation of a userset, e.g. `group:eng#member`.
+    Userset(&'a str, &'a str),
+    /// The type of a wildcard, e.g. `user` for `user:*`.
+    Wildcard(&'a str),
+}
+
+/// Error from parsing a [TupleKey](crate::tuple::TupleKey), located
+/// within the parsed input.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TupleError {
+    pub kind: TupleErrorKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TupleErrorKind {
+    /// A required part of the tuple, e.g. `object type`, is empty.
+    Missing(&'static str),
+    /// Expected one character but found another.
+    Expected(char, Option<char>),
+    UnexpectedChar(char),
+    /// The condition context is not a JSON object.
+    InvalidContext(String),
+}
+
+struct Cursor<'a> {
+    input: &'a str,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    /// Character offset of the input within a larger document.
+    offset: usize,
+}
+
+impl TupleKey {
+    pub fn new(object: &str, relation: &str, user: &str) -> Self {
+        Self {
+            object: object.to_string(),
+            relation: relation.to_string(),
+            user: user.to_string(),
+            condition: None,
+        }
+    }
+
+    pub fn with_condition(mut self, name: &str, context: Map<String, Value>) -> Self {
+        self.condition = Some(TupleCondition {
+            name: name.to_string(),
+            context,
+        });
+        self
+    }
+
+    /// Parses a single tuple, rejecting anything around it but whitespace.
+    pub fn parse(input: &str) -> TupleResult<Self> {
+        parse_at(input, 0)
+    }
+}
+
+impl<'a> User<'a> {
+    /// Parses a user on its own, such as the user of a stored tuple.
+    pub fn parse(input: &'a str) -> TupleResult<Self> {
+        let mut cur = Cursor::new(input, 0);
+        let user = cur.user()?;
+        match cur.peek() {
+            Some(c) => Err(cur.error(TupleErrorKind::UnexpectedChar(c))),
+            None => Ok(user),
+        }
+    }
+
+    /// The type of the user, e.g. `group` for `group:eng#member`.
+    pub fn type_name(&self) -> &'a str {
+        match self {
+            User::Object(object) | User::Userset(object, _) => {
+                object.split_once(':').map_or(object, |(ty, _)| ty)
+            }
+            User::Wildcard(ty) => ty,
+        }
+    }
+}
+
+/// Parses one tuple per line, skipping blank lines and lines starting
+/// with `#`. Error spans are relative to the whole input.
+pub fn parse_tuples(input: &str) -> TupleResult<Vec<TupleKey>> {
+    let mut tuples = Vec::new();
+    let mut offset = 0;
+    for line in input.split('\n') {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            tuples.push(parse_at(line, offset)?);
+        }
+        offset += line.chars().count() + 1;
+    }
+    Ok(tuples)
+}
+
+fn parse_at(input: &str, offset: usize) -> TupleResult<TupleKey> {
+    let mut cur = Cursor::new(input, offset);
+    cur.skip_whitespace();
+
+    let object = cur.object("object type", "object id")?.to_string();
+    cur.expect('#')?;
+    let relation = cur.name("relation")?;
+    cur.expect('@')?;
+    let user = cur.user()?.to_string();
+
+    let mut tuple = TupleKey {
+        object,
+        relation,
+        user,
+        condition: None,
+    };
+
+    cur.skip_whitespace();
+    if cur.peek().is_some() {
+        let start = cur.pos;
+        let keyword = cur.take_while(|c| !c.is_whitespace());
+        if keyword != "with" {
+            return Err(cur.error_at(start, TupleErrorKind::UnexpectedChar(cur.chars[start].1)));
+        }
+        cur.skip_whitespace();
+        let name = cur.name("condition name")?;
+        cur.skip_whitespace();
+        let context = cur.context()?;
+        tuple.condition = Some(TupleCondition { name, context });
+    }
+
+    cur.skip_whitespace();
+    match cur.peek() {
+        Some(c) => Err(cur.error(TupleErrorKind::UnexpectedChar(c))),
+        None => Ok(tuple),
+    }
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str, offset: usize) -> Self {
+        Self {
+            input,
+            chars: input.char_indices().collect(),
+            pos: 0,
+            offset,
+        }
+    }
+
+    /// The input between two character positions.
+    fn slice(&self, start: usize, end: usize) -> &'a str {
+        let byte = |pos: usize| self.chars.get(pos).map_or(self.input.len(), |(b, _)| *b);
+        &self.input[byte(start)..byte(end)]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|(_, c)| *c)
+    }
+
+    fn error(&self, kind: TupleErrorKind) -> TupleError {
+        self.error_at(self.pos, kind)
+    }
+
+    fn error_at(&self, pos: usize, kind: TupleErrorKind) -> TupleError {
+        let start = self.offset + pos;
+        let end = if pos < self.chars.len() {
+            start + 1
+        } else {
+            start
+        };
+        TupleError {
+            kind,
+            span: Span::new(start, end),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> TupleResult<()> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            got => Err(self.error(TupleErrorKind::Expected(expected, got))),
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().map(|(_, c)| c).collect()
+    }
+
+    /// A type, relation or condition name.
+    fn name(&mut self, what: &'static str) -> TupleResult<String> {
+        let name = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        if name.is_empty() {
+            return Err(self.error(TupleErrorKind::Missing(what)));
+        }
+        Ok(name)
+    }
+
+    /// An object of the form `type:id`. The id may itself contain `:`.
+    fn object(&mut self, ty_what: &'static str, id_what: &'static str) -> TupleResult<&'a str> {
+        let start = self.pos;
+        self.name(ty_what)?;
+        self.expect(':')?;
+        let id = self.take_while(|c| !c.is_whitespace() && !matches!(c, '#' | '*'));
+        if id.is_empty() {
+            return Err(self.error(TupleErrorKind::Missing(id_what)));
+        }
+        Ok(self.slice(start, self.pos))
+    }
+
+    /// An object, userset (`group:eng#member`) or wildcard (`user:*`).
+    fn user(&mut self) -> TupleResult<User<'a>> {
+        let start = self.pos;
+        self.name("user type")?;
+        if self.chars.get(self.pos + 1).map(|(_, c)| *c) == Some('*') && self.peek() == Some(':') {
+            let ty = self.slice(start, self.pos);
+            self.pos += 2;
+            return Ok(User::Wildcard(ty));
+        }
+        self.pos = start;
+        let object = self.object("user type", "user id")?;
+        if self.peek() == Some('#') {
+            self.pos += 1;
+            let start = self.pos;
+            self.name("userset relation")?;
+            return Ok(User::Userset(object, self.slice(start, self.pos)));
+        }
+        Ok(User::Object(object))
+    }
+
+    fn context(&mut self) -> TupleResult<Map<String, Value>> {
+        let Some((byte, _)) = self.chars.get(self.pos) else {
+            return Ok(Map::new());
+        };
+        let start = self.pos;
+        let mut stream = serde_json::Deserializer::from_str(&self.input[*byte..]).into_iter();
+        match stream.next() {
+            Some(Ok(Value::Object(context))) => {
+                let consumed = self.input[*byte..*byte + stream.byte_offset()]
+                    .chars()
+                    .count();
+                self.pos += consumed;
+                Ok(context)
+            }
+            Some(Ok(_)) => Err(self.error_at(
+                start,
+                TupleErrorKind::InvalidContext("expected a JSON object".into()),
+            )),
+            Some(Err(e)) => {
+                Err(self.error_at(start, TupleErrorKind::InvalidContext(e.to_string())))
+            }
+            None => Ok(Map::new()),
+        }
+    }
+}
+
+impl FromStr for TupleKey {
+    type Err = TupleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TupleKey::parse(s)
+    }
+}
+
+impl Display for TupleKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}#{}@{}", self.object, self.relation, self.user)?;
+        if let Some(condition) = &self.condition {
+            write!(f, " with {}", condition.name)?;
+            if !condition.context.is_empty() {
+                write!(f, " {}", Value::Object(condition.context.clone()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for User<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            User::Object(object) => write!(f, "{object}"),
+            User::Userset(object, relation) => write!(f, "{object}#{relation}"),
+            User::Wildcard(ty) => write!(f, "{ty}:*"),
+        }
+    }
+}
+
+impl Display for TupleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use TupleErrorKind::*;
+        match &self.kind {
+            Missing(what) => write!(f, "missing {what}")?,
+            Expected(exp, Some(got)) => write!(f, "expected '{exp}', got '{got}'")?,
+            Expected(exp, None) => write!(f, "expected '{exp}', got end of input")?,
+            UnexpectedChar(c) => write!(f, "unexpected character '{c}'")?,
+            InvalidContext(e) => write!(f, "invalid condition context: {e}")?,
+        }
+        write!(f, " at position {}", self.span.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn err(input: &str) -> (TupleErrorKind, usize) {
+        let e = TupleKey::parse(input).unwrap_err();
+        (e.kind, e.span.start)
+    }
+
+    #[test]
+    fn parses_users() {
+        assert_eq!(
+            TupleKey::parse("document:1#viewer@user:anne"),
+            Ok(TupleKey::new("document:1", "viewer", "user:anne"))
+        );
+        assert_eq!(
+            TupleKey::parse("folder:x#viewer@group:eng#member"),
+            Ok(TupleKey::new("folder:x", "viewer", "group:eng#member"))
+        );
+        assert_eq!(
+            TupleKey::parse("  document:1#viewer@user:*  "),
+            Ok(TupleKey::new("document:1", "viewer", "user:*"))
+        );
+        assert_eq!(
+            TupleKey::parse("document:a/b.c#viewer@user:anne@example.com"),
+            Ok(TupleKey::new(
+                "document:a/b.c",
+                "viewer",
+                "user:anne@example.com"
+            ))
+        );
+        assert_eq!(
+            TupleKey::parse("doc:2024:q1#viewer@team:eu:eng#member"),
+            Ok(TupleKey::new("doc:2024:q1", "viewer", "team:eu:eng#member"))
+        );
+    }
+
+    #[test]
+    fn parses_users_alone() {
+        assert_eq!(User::parse("user:anne"), Ok(User::Object("user:anne")));
+        assert_eq!(
+            User::parse("team:eu:eng#member"),
+            Ok(User::Userset("team:eu:eng", "member"))
+        );
+        assert_eq!(User::parse("user:*"), Ok(User::Wildcard("user")));
+        assert_eq!(
+            User::parse("user:*#member").map_err(|e| e.kind),
+            Err(TupleErrorKind::UnexpectedChar('#'))
+        );
+        assert_eq!(
+            User::parse("anne").map_err(|e| e.kind),
+            Err(TupleErrorKind::Expected(':', None))
+        );
+    }
+
+    #[test]
+    fn parses_conditions() {
+        let exp = TupleKey::new("document:1", "viewer", "user:anne").with_condition(
+            "in_office",
+            json!({ "ip": "10.0.0.1", "ports": [1, 2] })
+                .as_object()
+                .unwrap()
+                .clone(),
+        );
+        let i = r#"document:1#viewer@user:anne with in_office {"ip": "10.0.0.1", "ports": [1, 2]}"#;
+        assert_eq!(TupleKey::parse(i), Ok(exp.clone()));
+        assert_eq!(TupleKey::parse(&exp.to_string()), Ok(exp));
+
+        let exp = TupleKey::new("document:1", "viewer", "user:anne")
+            .with_condition("in_office", Map::new());
+        assert_eq!(
+            TupleKey::parse("document:1#viewer@user:anne with in_office"),
+            Ok(exp)
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for i in [
+            "document:1#viewer@user:anne",
+            "folder:x#viewer@group:eng#member",
+            "document:1#viewer@user:*",
+            r#"document:1#viewer@user:anne with in_office {"ip":"10.0.0.1"}"#,
+        ] {
+            assert_eq!(TupleKey::parse(i).unwrap().to_string(), i);
+        }
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(
+            err("document#viewer@user:anne"),
+            (TupleErrorKind::Expected(':', Some('#')), 8)
+        );
+        assert_eq!(
+            err("document:#viewer@user:anne"),
+            (TupleErrorKind::Missing("object id"), 9)
+        );
+        assert_eq!(
+            err("document:1#@user:anne"),
+            (TupleErrorKind::Missing("relation"), 11)
+        );
+        assert_eq!(
+            err("document:1#viewer"),
+            (TupleErrorKind::Expected('@', None), 17)
+        );
+        assert_eq!(
+            err("document:1#viewer@user:anne#"),
+            (TupleErrorKind::Missing("userset relation"), 28)
+        );
+        assert_eq!(
+            err("document:1#viewer@user:anne and"),
+            (TupleErrorKind::UnexpectedChar('a'), 28)
+        );
+        assert_eq!(
+            err("document:1#viewer@user:anne with c [1]"),
+            (
+                TupleErrorKind::InvalidContext("expected a JSON object".into()),
+                35
+            )
+        );
+        assert!(matches!(
+            err("document:1#viewer@user:anne with c {\"a\": }"),
+            (TupleErrorKind::InvalidContext(_), 35)
+        ));
+    }
+
+    #[test]
+    fn parses_many() {
+        let i = "# tuples
+document:1#viewer@user:anne
+
+document:1#editor@user:bob
+document:1#editor@";
+        let e = parse_tuples(i).unwrap_err();
+        assert_eq!(e.kind, TupleErrorKind::Missing("user type"));
+        assert_eq!(e.span.line_col(i), (4, 18));
+
+        let tuples = parse_tuples(&format!("{i}user:carl")).unwrap();
+        assert_eq!(tuples.len(), 3);
+    }
+}
@@ -0,0 +1,711 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::ast::{Document, Type};
+use crate::tuple::{TupleKey, User};
+
+mod usage;
+
+pub use usage::{Observed, TupleUsage, Violation};
+
+/// A user type that may be directly related to a relation.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum RelatedType {
+    /// A concrete type, e.g. `user`.
+    Direct(String),
+    /// Every object of a type, e.g. `user:*`.
+    Wildcard(String),
+    /// A userset, e.g. `group#member`.
+    Userset(String, String),
+}
+
+/// The user types directly related to each relation of a model,
+/// keyed by type and relation name.
+///
+/// Schema 1.0 models do not declare these restrictions, so they
+/// are supplied alongside the [Document](crate::ast::Document).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DirectlyRelated {
+    types: HashMap<(String, String), Vec<RelatedType>>,
+}
+
+/// Enumerated error type for the [Validator](crate::validate::Validator) type.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ValidationError {
+    /// The tupleset relation of a `from` alias is not defined on the type.
+    UndefinedTupleset {
+        ty: String,
+        relation: String,
+        tupleset: String,
+    },
+    /// Some types directly related to the tupleset relation do not
+    /// define the computed relation.
+    MissingTuplesetTarget {
+        ty: String,
+        relation: String,
+        tupleset: String,
+        computed: String,
+        missing: Vec<String>,
+    },
+    /// No type in the model defines the computed relation. Reported when
+    /// the tupleset relation has no directly related types to resolve.
+    UndefinedTuplesetTarget {
+        ty: String,
+        relation: String,
+        tupleset: String,
+        computed: String,
+    },
+}
+
+/// Reasons OpenFGA would reject writing a [TupleKey](crate::tuple::TupleKey).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TupleValidationError {
+    /// An object or user that is not of the form `type:id`.
+    InvalidObject(String),
+    UnknownType(String),
+    UnknownRelation(String, String),
+    /// The relation is not defined with `self`, so it cannot be written.
+    NotAssignable(String, String),
+    UnknownUserType(String),
+    /// A userset user refers to a relation its type does not define.
+    UndefinedUsersetRelation(String, String),
+    /// The user's type is not directly related to the relation.
+    UserTypeNotAllowed {
+        ty: String,
+        relation: String,
+        user: RelatedType,
+    },
+    /// A wildcard user on a relation that does not allow one.
+    WildcardNotAllowed {
+        ty: String,
+        relation: String,
+        user: RelatedType,
+    },
+    /// The tuple's condition is not declared in the model.
+    UnknownCondition(String),
+}
+
+/// Validator for checking a [Document](crate::ast::Document)
+/// for errors the parser does not catch.
+pub struct Validator<'d> {
+    doc: &'d Document,
+    related: Option<&'d DirectlyRelated>,
+}
+
+impl RelatedType {
+    /// The name of the type being related.
+    pub fn type_name(&self) -> &str {
+        match self {
+            RelatedType::Direct(ty) | RelatedType::Wildcard(ty) | RelatedType::Userset(ty, _) => ty,
+        }
+    }
+
+    /// How the user of a tuple is related: `user:anne` is `user`,
+    /// `user:*` is `user:*` and `group:eng#member` is `group#member`.
+    pub fn of_user(user: &str) -> Option<RelatedType> {
+        let user = User::parse(user).ok()?;
+        let ty = user.type_name().to_string();
+        Some(match user {
+            User::Object(_) => RelatedType::Direct(ty),
+            User::Userset(_, relation) => RelatedType::Userset(ty, relation.into()),
+            User::Wildcard(_) => RelatedType::Wildcard(ty),
+        })
+    }
+}
+
+impl From<&str> for RelatedType {
+    /// Reads `user`, `user:*` and `group#member` forms.
+    fn from(s: &str) -> Self {
+        if let Some(ty) = s.strip_suffix(":*") {
+            RelatedType::Wildcard(ty.into())
+        } else if let Some((ty, rel)) = s.split_once('#') {
+            RelatedType::Userset(ty.into(), rel.into())
+        } else {
+            RelatedType::Direct(s.into())
+        }
+    }
+}
+
+impl Display for RelatedType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RelatedType::Direct(ty) => write!(f, "{ty}"),
+            RelatedType::Wildcard(ty) => write!(f, "{ty}:*"),
+            RelatedType::Userset(ty, rel) => write!(f, "{ty}#{rel}"),
+        }
+    }
+}
+
+impl DirectlyRelated {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the types directly related to `ty#relation`.
+    pub fn insert<I, R>(&mut self, ty: &str, relation: &str, related: I)
+    where
+        I: IntoIterator<Item = R>,
+        R: Into<RelatedType>,
+    {
+        let related = related.into_iter().map(Into::into).collect();
+        self.types.insert((ty.into(), relation.into()), related);
+    }
+
+    /// Collects the user types seen on each relation of `tuples`, in
+    /// the order they first appear. Tuples with malformed objects or
+    /// users are skipped.
+    pub fn from_tuples<'t, I>(tuples: I) -> Self
+    where
+        I: IntoIterator<Item = &'t TupleKey>,
+    {
+        let mut related = Self::new();
+        for tuple in tuples {
+            let (Some((ty, _)), Some(user)) = (
+                tuple.object.split_once(':'),
+                RelatedType::of_user(&tuple.user),
+            ) else {
+                continue;
+            };
+            let seen = related
+                .types
+                .entry((ty.to_string(), tuple.relation.clone()))
+                .or_default();
+            if !seen.contains(&user) {
+                seen.push(user);
+            }
+        }
+        related
+    }
+
+    /// The types directly related to `ty#relation`, if known.
+    pub fn get(&self, ty: &str, relation: &str) -> Option<&[RelatedType]> {
+        self.types
+            .get(&(ty.to_string(), relation.to_string()))
+            .map(Vec::as_slice)
+    }
+}
+
+impl<'d> Validator<'d> {
+    pub fn new(doc: &'d Document) -> Self {
+        Self { doc, related: None }
+    }
+
+    /// Resolve tupleset relations using the provided directly related types.
+    pub fn with_related_types(mut self, related: &'d DirectlyRelated) -> Self {
+        self.related = Some(related);
+        self
+    }
+
+    /// Runs every check, returning all errors found.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        for ty in &self.doc.types {
+            self.validate_tuple_to_usersets(ty, &mut errors);
+        }
+        errors
+    }
+
+    /// Checks tuples against the model, returning the index and error
+    /// of each tuple OpenFGA would reject.
+    pub fn validate_tuples<'t, I>(&self, tuples: I) -> Vec<(usize, TupleValidationError)>
+    where
+        I: IntoIterator<Item = &'t TupleKey>,
+    {
+        tuples
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, tuple)| self.validate_tuple(tuple).err().map(|e| (i, e)))
+            .collect()
+    }
+
+    /// Checks a single tuple against the model.
+    pub fn validate_tuple(&self, tuple: &TupleKey) -> Result<(), TupleValidationError> {
+        use TupleValidationError::*;
+
+        let ty = match tuple.object.split_once(':') {
+            Some((ty, id)) if !ty.is_empty() && !id.is_empty() && id != "*" => ty,
+            _ => return Err(InvalidObject(tuple.object.clone())),
+        };
+        let ty_def = self
+            .doc
+            .get_type(ty)
+            .ok_or_else(|| UnknownType(ty.to_string()))?;
+        let rel = ty_def
+            .get_relation(&tuple.relation)
+            .ok_or_else(|| UnknownRelation(ty.to_string(), tuple.relation.clone()))?;
+        if !rel.is_assignable() {
+            return Err(NotAssignable(ty.to_string(), tuple.relation.clone()));
+        }
+        if let Some(condition) = &tuple.condition {
+            if !self.doc.conditions.iter().any(|c| c.name == condition.name) {
+                return Err(UnknownCondition(condition.name.clone()));
+            }
+        }
+
+        let user =
+            RelatedType::of_user(&tuple.user).ok_or_else(|| InvalidObject(tuple.user.clone()))?;
+        let user_ty = self
+            .doc
+            .get_type(user.type_name())
+            .ok_or_else(|| UnknownUserType(user.type_name().to_string()))?;
+        if let RelatedType::Userset(_, set_relation) = &user {
+            if user_ty.get_relation(set_relation).is_none() {
+                return Err(UndefinedUsersetRelation(
+                    user_ty.kind.clone(),
+                    set_relation.clone(),
+                ));
+            }
+        }
+
+        // objects are followed through a tupleset, so it cannot hold a wildcard
+        let is_tupleset = ty_def
+            .relations
+            .iter()
+            .flat_map(|r| &r.aliases)
+            .any(|a| a.parent.as_ref() == Some(&tuple.relation));
+        if is_tupleset && matches!(user, RelatedType::Wildcard(_)) {
+            return Err(WildcardNotAllowed {
+                ty: ty.to_string(),
+                relation: tuple.relation.clone(),
+                user,
+            });
+        }
+
+        let Some(related) = self.related.and_then(|r| r.get(ty, &tuple.relation)) else {
+            return Ok(());
+        };
+        if related.contains(&user) {
+            return Ok(());
+        }
+        let ty = ty.to_string();
+        let relation = tuple.relation.clone();
+        Err(match user {
+            RelatedType::Wildcard(_) => WildcardNotAllowed { ty, relation, user },
+            _ => UserTypeNotAllowed { ty, relation, user },
+        })
+    }
+
+    fn validate_tuple_to_usersets(&self, ty: &Type, errors: &mut Vec<ValidationError>) {
+        for rel in &ty.relations {
+            for alias in &rel.aliases {
+                let (Some(tupleset), Some(computed)) = (&alias.parent, alias.kind.relation())
+                else {
+                    continue;
+                };
+
+                if ty.get_relation(tupleset).is_none() {
+                    errors.push(ValidationError::UndefinedTupleset {
+                        ty: ty.kind.clone(),
+                        relation: rel.kind.clone(),
+                        tupleset: tupleset.clone(),
+                    });
+                    continue;
+                }
+
+                match self.related.and_then(|r| r.get(&ty.kind, tupleset)) {
+                    Some(related) => {
+                        let mut missing: Vec<String> = Vec::new();
+                        for name in related.iter().map(RelatedType::type_name) {
+                            let defined = self
+                                .doc
+                                .get_type(name)
+                                .and_then(|t| t.get_relation(computed))
+                                .is_some();
+                            if !defined && !missing.iter().any(|m| m == name) {
+                                missing.push(name.to_string());
+                            }
+                        }
+                        if !missing.is_empty() {
+                            errors.push(ValidationError::MissingTuplesetTarget {
+                                ty: ty.kind.clone(),
+                                relation: rel.kind.clone(),
+                                tupleset: tupleset.clone(),
+                                computed: computed.to_string(),
+                                missing,
+                            });
+                        }
+                    }
+                    None => {
+                        let defined = self
+                            .doc
+                            .types
+                            .iter()
+                            .any(|t| t.get_relation(computed).is_some());
+                        if !defined {
+                            errors.push(ValidationError::UndefinedTuplesetTarget {
+                                ty: ty.kind.clone(),
+                                relation: rel.kind.clone(),
+                                tupleset: tupleset.clone(),
+                                computed: computed.to_string(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ValidationError {
+    /// The type of the relation the error is about.
+    pub fn ty(&self) -> &str {
+        match self {
+            ValidationError::UndefinedTupleset { ty, .. }
+            | ValidationError::MissingTuplesetTarget { ty, .. }
+            | ValidationError::UndefinedTuplesetTarget { ty, .. } => ty,
+        }
+    }
+
+    /// The relation the error is about.
+    pub fn relation(&self) -> &str {
+        match self {
+            ValidationError::UndefinedTupleset { relation, .. }
+            | ValidationError::MissingTuplesetTarget { relation, .. }
+            | ValidationError::UndefinedTuplesetTarget { relation, .. } => relation,
+        }
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ValidationError::*;
+        match self {
+            UndefinedTupleset {
+                ty,
+                relation,
+                tupleset,
+            } => write!(
+                f,
+                "{ty}#{relation}: tupleset relation '{tupleset}' is not defined on type '{ty}'"
+            ),
+            MissingTuplesetTarget {
+                ty,
+                relation,
+                tupleset,
+                computed,
+                missing,
+            } => write!(
+                f,
+                "{ty}#{relation}: '{computed}' is not defined on types related through '{tupleset}': {}",
+                missing.join(", ")
+            ),
+            UndefinedTuplesetTarget {
+                ty,
+                relation,
+                tupleset,
+                computed,
+            } => write!(
+                f,
+                "{ty}#{relation}: '{computed}' from '{tupleset}' is not defined on any type"
+            ),
+        }
+    }
+}
+
+impl Display for TupleValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use TupleValidationError::*;
+        match self {
+            InvalidObject(object) => write!(f, "invalid object '{object}', expected type:id"),
+            UnknownType(ty) => write!(f, "type '{ty}' is not defined"),
+            UnknownRelation(ty, rel) => write!(f, "relation '{rel}' is not defined on type '{ty}'"),
+            NotAssignable(ty, rel) => {
+                write!(f, "relation '{ty}#{rel}' is not directly assignable")
+            }
+            UnknownUserType(ty) => write!(f, "user type '{ty}' is not defined"),
+            UndefinedUsersetRelation(ty, rel) => {
+                write!(f, "userset relation '{rel}' is not defined on type '{ty}'")
+            }
+            UserTypeNotAllowed { ty, relation, user } => {
+                write!(f, "'{user}' is not directly related to '{ty}#{relation}'")
+            }
+            WildcardNotAllowed { ty, relation, user } => {
+                write!(f, "wildcard '{user}' is not allowed on '{ty}#{relation}'")
+            }
+            UnknownCondition(name) => write!(f, "condition '{name}' is not defined"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::TupleCondition;
+    use crate::Parser;
+
+    fn parse(i: &str) -> Document {
+        Parser::new(i).parse_document().unwrap()
+    }
+
+    const MODEL: &str = "type user
+type folder
+  relations
+    define viewer as self
+type team
+  relations
+    define member as self
+type document
+  relations
+    define parent as self
+    define viewer as self or viewer from parent";
+
+    #[test]
+    fn related_type_from_str() {
+        assert_eq!(
+            RelatedType::from("user"),
+            RelatedType::Direct("user".into())
+        );
+        assert_eq!(
+            RelatedType::from("user:*"),
+            RelatedType::Wildcard("user".into())
+        );
+        assert_eq!(
+            RelatedType::from("group#member"),
+            RelatedType::Userset("group".into(), "member".into())
+        );
+    }
+
+    #[test]
+    fn related_types_from_tuples() {
+        let mut tuples = crate::tuple::parse_tuples(
+            "document:1#viewer@user:anne
+             document:2#viewer@team:eng#member
+             document:3#viewer@user:bob
+             document:3#viewer@user:*",
+        )
+        .unwrap();
+        tuples.push(TupleKey::new("document:4", "viewer", "carl"));
+        let related = DirectlyRelated::from_tuples(&tuples);
+        assert_eq!(
+            related.get("document", "viewer"),
+            Some(
+                &[
+                    RelatedType::from("user"),
+                    RelatedType::from("team#member"),
+                    RelatedType::from("user:*"),
+                ][..]
+            )
+        );
+        assert_eq!(related.get("document", "parent"), None);
+    }
+
+    #[test]
+    fn valid_tuple_to_userset() {
+        let doc = parse(MODEL);
+        let mut related = DirectlyRelated::new();
+        related.insert("document", "parent", ["folder"]);
+
+        let errors = Validator::new(&doc).with_related_types(&related).validate();
+        assert_eq!(errors, Vec::new());
+    }
+
+    #[test]
+    fn reports_related_types_missing_computed_relation() {
+        let doc = parse(MODEL);
+        let mut related = DirectlyRelated::new();
+        related.insert("document", "parent", ["folder", "team", "user"]);
+
+        let errors = Validator::new(&doc).with_related_types(&related).validate();
+        assert_eq!(
+            errors,
+            vec![ValidationError::MissingTuplesetTarget {
+                ty: "document".into(),
+                relation: "viewer".into(),
+                tupleset: "parent".into(),
+                computed: "viewer".into(),
+                missing: vec!["team".into(), "user".into()],
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_undefined_tupleset() {
+        let doc = parse(
+            "type document
+  relations
+    define viewer as self or viewer from owner",
+        );
+
+        let errors = Validator::new(&doc).validate();
+        assert_eq!(
+            errors,
+            vec![ValidationError::UndefinedTupleset {
+                ty: "document".into(),
+                relation: "viewer".into(),
+                tupleset: "owner".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn unresolved_tupleset_requires_some_definition() {
+        let doc = parse(
+            "type document
+  relations
+    define parent as self
+    define viewer as self but not blocked from parent",
+        );
+
+        let errors = Validator::new(&doc).validate();
+        assert_eq!(
+            errors,
+            vec![ValidationError::UndefinedTuplesetTarget {
+                ty: "document".into(),
+                relation: "viewer".into(),
+                tupleset: "parent".into(),
+                computed: "blocked".into(),
+            }]
+        );
+    }
+
+    fn tuple_errors(
+        related: Option<&DirectlyRelated>,
+        tuples: &[&str],
+    ) -> Vec<(usize, TupleValidationError)> {
+        let doc = parse(MODEL);
+        let tuples: Vec<TupleKey> = tuples.iter().map(|t| TupleKey::parse(t).unwrap()).collect();
+        let validator = Validator::new(&doc);
+        match related {
+            Some(related) => validator
+                .with_related_types(related)
+                .validate_tuples(&tuples),
+            None => validator.validate_tuples(&tuples),
+        }
+    }
+
+    #[test]
+    fn related_type_of_user() {
+        assert_eq!(RelatedType::of_user("user:anne"), Some("user".into()));
+        assert_eq!(RelatedType::of_user("user:*"), Some("user:*".into()));
+        assert_eq!(
+            RelatedType::of_user("team:a#member"),
+            Some("team#member".into())
+        );
+        assert_eq!(RelatedType::of_user("user:*#member"), None);
+        assert_eq!(RelatedType::of_user("anne"), None);
+    }
+
+    #[test]
+    fn validates_tuples_against_model() {
+        use TupleValidationError::*;
+
+        let errors = tuple_errors(
+            None,
+            &[
+                "document:1#viewer@user:anne",
+                "page:1#viewer@user:anne",
+                "document:1#owner@user:anne",
+                "document:1#viewer@bot:anne",
+                "document:1#viewer@team:a#owner",
+                "document:1#viewer@team:a#member",
+                "document:1#viewer@user:*",
+                "document:1#parent@folder:*",
+            ],
+        );
+        assert_eq!(
+            errors,
+            vec![
+                (1, UnknownType("page".into())),
+                (2, UnknownRelation("document".into(), "owner".into())),
+                (3, UnknownUserType("bot".into())),
+                (4, UndefinedUsersetRelation("team".into(), "owner".into())),
+                (
+                    7,
+                    WildcardNotAllowed {
+                        ty: "document".into(),
+                        relation: "parent".into(),
+                        user: "folder:*".into(),
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_tuples_against_related_types() {
+        use TupleValidationError::*;
+
+        let mut related = DirectlyRelated::new();
+        related.insert("document", "viewer", ["user", "team#member"]);
+        related.insert("folder", "viewer", ["user", "user:*"]);
+
+        let errors = tuple_errors(
+            Some(&related),
+            &[
+                "document:1#viewer@user:anne",
+                "document:1#viewer@team:a#member",
+                "document:1#viewer@folder:x",
+                "document:1#viewer@user:*",
+                "folder:x#viewer@user:*",
+                "document:1#parent@folder:x",
+            ],
+        );
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    2,
+                    UserTypeNotAllowed {
+                        ty: "document".into(),
+                        relation: "viewer".into(),
+                        user: "folder".into(),
+                    }
+                ),
+                (
+                    3,
+                    WildcardNotAllowed {
+                        ty: "document".into(),
+                        relation: "viewer".into(),
+                        user: "user:*".into(),
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_non_assignable_relations() {
+        let doc = parse(
+            "type user
+type document
+  relations
+    define owner as self
+    define viewer as owner",
+        );
+        for user in ["user:anne", "user:*"] {
+            let tuple = TupleKey::new("document:1", "viewer", user);
+            assert_eq!(
+                Validator::new(&doc).validate_tuple(&tuple),
+                Err(TupleValidationError::NotAssignable(
+                    "document".into(),
+                    "viewer".into()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_conditions() {
+        let doc = parse(
+            "type user
+type document
+  relations
+    define viewer as self
+condition in_office(ip: ipaddress) { ip.in_cidr('10.0.0.0/8') }",
+        );
+        let validator = Validator::new(&doc);
+        let mut tuple = TupleKey::new("document:1", "viewer", "user:anne");
+        let condition = |name: &str| TupleCondition {
+            name: name.into(),
+            context: Default::default(),
+        };
+
+        tuple.condition = Some(condition("in_office"));
+        assert_eq!(validator.validate_tuple(&tuple), Ok(()));
+        tuple.condition = Some(condition("at_home"));
+        assert_eq!(
+            validator.validate_tuple(&tuple),
+            Err(TupleValidationError::UnknownCondition("at_home".into()))
+        );
+    }
+}
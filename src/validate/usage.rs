@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use super::{DirectlyRelated, RelatedType, TupleValidationError, Validator};
+use crate::ast::Document;
+use crate::tuple::TupleKey;
+
+/// Aggregates the user types found on each relation of a tuple export,
+/// one tuple at a time, to suggest and test type restrictions for a
+/// [Document](crate::ast::Document).
+///
+/// ```
+/// use openfga_dsl_parser::{tuple::TupleKey, validate::TupleUsage, Parser};
+///
+/// let doc = Parser::new("type user
+/// type document
+///   relations
+///     define viewer as self")
+/// .parse_document()
+/// .unwrap();
+///
+/// let export = "document:1#viewer@user:anne\ndocument:2#viewer@user:*";
+/// let mut usage = TupleUsage::new(&doc);
+/// for line in export.lines() {
+///     usage.observe(&TupleKey::parse(line).unwrap());
+/// }
+/// let suggested = usage.suggest();
+/// assert_eq!(suggested.get("document", "viewer").unwrap().len(), 2);
+/// ```
+pub struct TupleUsage<'d> {
+    validator: Validator<'d>,
+    observed: BTreeMap<(String, String), Vec<Observed>>,
+    rejected: Vec<(usize, TupleValidationError)>,
+    count: usize,
+}
+
+/// How often a user type was found on a relation.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Observed {
+    pub user: RelatedType,
+    pub count: usize,
+    /// The index of the first tuple with this user type.
+    pub first: usize,
+}
+
+/// Tuples of a user type that a set of restrictions would reject.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Violation {
+    pub count: usize,
+    /// The index of the first rejected tuple.
+    pub first: usize,
+    pub error: TupleValidationError,
+}
+
+impl<'d> TupleUsage<'d> {
+    pub fn new(doc: &'d Document) -> Self {
+        Self {
+            validator: Validator::new(doc),
+            observed: BTreeMap::new(),
+            rejected: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// Records the next tuple of the export. Tuples the model already
+    /// rejects are kept apart, see [rejected](Self::rejected).
+    pub fn observe(&mut self, tuple: &TupleKey) {
+        let index = self.count;
+        self.count += 1;
+        if let Err(e) = self.validator.validate_tuple(tuple) {
+            self.rejected.push((index, e));
+            return;
+        }
+        let (Some((ty, _)), Some(user)) = (
+            tuple.object.split_once(':'),
+            RelatedType::of_user(&tuple.user),
+        ) else {
+            return;
+        };
+
+        let observed = self
+            .observed
+            .entry((ty.to_string(), tuple.relation.clone()))
+            .or_default();
+        match observed.iter_mut().find(|o| o.user == user) {
+            Some(o) => o.count += 1,
+            None => observed.push(Observed {
+                user,
+                count: 1,
+                first: index,
+            }),
+        }
+    }
+
+    /// The number of tuples observed.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The user types found on `ty#relation`, in order of first appearance.
+    pub fn observed(&self, ty: &str, relation: &str) -> &[Observed] {
+        self.observed
+            .get(&(ty.to_string(), relation.to_string()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The index and error of each tuple the model rejects outright.
+    pub fn rejected(&self) -> &[(usize, TupleValidationError)] {
+        &self.rejected
+    }
+
+    /// Restricts each relation to the user types found on it. Relations
+    /// without tuples are left unrestricted.
+    pub fn suggest(&self) -> DirectlyRelated {
+        let mut related = DirectlyRelated::new();
+        for ((ty, relation), observed) in &self.observed {
+            related.insert(ty, relation, observed.iter().map(|o| o.user.clone()));
+        }
+        related
+    }
+
+    /// The tuples `related` would reject, grouped by relation and user
+    /// type, in order of the relations.
+    pub fn violations(&self, related: &DirectlyRelated) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for ((ty, relation), observed) in &self.observed {
+            let Some(allowed) = related.get(ty, relation) else {
+                continue;
+            };
+            for o in observed.iter().filter(|o| !allowed.contains(&o.user)) {
+                let (ty, relation, user) = (ty.clone(), relation.clone(), o.user.clone());
+                let error = match user {
+                    RelatedType::Wildcard(_) => {
+                        TupleValidationError::WildcardNotAllowed { ty, relation, user }
+                    }
+                    _ => TupleValidationError::UserTypeNotAllowed { ty, relation, user },
+                };
+                violations.push(Violation {
+                    count: o.count,
+                    first: o.first,
+                    error,
+                });
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::parse_tuples;
+    use crate::Parser;
+
+    const MODEL: &str = "type user
+type team
+  relations
+    define member as self
+type document
+  relations
+    define owner as self
+    define viewer as self or owner";
+
+    const EXPORT: &str = "document:1#viewer@user:anne
+document:1#owner@user:bob
+document:2#viewer@team:eng#member
+document:2#viewer@user:carl
+document:3#viewer@user:*
+document:3#editor@user:anne
+team:eng#member@user:dan";
+
+    fn usage(doc: &Document) -> TupleUsage<'_> {
+        let mut usage = TupleUsage::new(doc);
+        for tuple in parse_tuples(EXPORT).unwrap() {
+            usage.observe(&tuple);
+        }
+        usage
+    }
+
+    #[test]
+    fn aggregates_user_types() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let usage = usage(&doc);
+        assert_eq!(usage.len(), 7);
+        assert_eq!(
+            usage.observed("document", "viewer"),
+            &[
+                Observed {
+                    user: "user".into(),
+                    count: 2,
+                    first: 0
+                },
+                Observed {
+                    user: "team#member".into(),
+                    count: 1,
+                    first: 2
+                },
+                Observed {
+                    user: "user:*".into(),
+                    count: 1,
+                    first: 4
+                },
+            ]
+        );
+        assert_eq!(
+            usage.rejected(),
+            &[(
+                5,
+                TupleValidationError::UnknownRelation("document".into(), "editor".into())
+            )]
+        );
+
+        let suggested = usage.suggest();
+        assert_eq!(
+            suggested.get("document", "owner"),
+            Some(&[RelatedType::from("user")][..])
+        );
+        assert_eq!(suggested.get("team", "member").map(<[_]>::len), Some(1));
+        // the suggestion admits every tuple it was drawn from
+        assert_eq!(usage.violations(&suggested), vec![]);
+    }
+
+    #[test]
+    fn flags_tuples_stricter_restrictions_reject() {
+        let doc = Parser::new(MODEL).parse_document().unwrap();
+        let usage = usage(&doc);
+        let mut related = DirectlyRelated::new();
+        related.insert("document", "viewer", ["user", "team#member"]);
+        related.insert("document", "owner", ["team#member"]);
+
+        assert_eq!(
+            usage.violations(&related),
+            vec![
+                Violation {
+                    count: 1,
+                    first: 1,
+                    error: TupleValidationError::UserTypeNotAllowed {
+                        ty: "document".into(),
+                        relation: "owner".into(),
+                        user: "user".into(),
+                    },
+                },
+                Violation {
+                    count: 1,
+                    first: 4,
+                    error: TupleValidationError::WildcardNotAllowed {
+                        ty: "document".into(),
+                        relation: "viewer".into(),
+                        user: "user:*".into(),
+                    },
+                },
+            ]
+        );
+    }
+}
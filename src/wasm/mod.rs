@@ -0,0 +1,224 @@
+//! JavaScript bindings, enabled with the `wasm` feature.
+//!
+//! Build with `wasm-pack build --target web -- --features wasm`. Every
+//! function takes the model as a string. Failures are thrown as plain
+//! objects with a `kind`, a `message` and, when known, a `span` of
+//! character offsets and a one-based `line` and `column`.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::analysis::Analysis;
+use crate::ast::{Alias, AliasKind, AliasSource, Document, SourceMap};
+use crate::json::{self, JsonError, JsonTransformer};
+use crate::lexer::token::Span;
+use crate::lint::Severity;
+use crate::{Parser, ParserError};
+
+/// Parses `input` into its syntax tree, with the span of every node.
+#[wasm_bindgen]
+pub fn parse(input: &str) -> Result<JsValue, JsValue> {
+    to_js(parse_value(input))
+}
+
+/// Returns the problems found in `input` by the parser, the validator
+/// and the linter. An empty array means the model is valid.
+#[wasm_bindgen]
+pub fn validate(input: &str) -> JsValue {
+    to_js::<Value>(Ok(validate_value(input))).unwrap_or(JsValue::NULL)
+}
+
+/// Transforms `input` into the JSON model accepted by the OpenFGA API.
+#[wasm_bindgen(js_name = dslToJson)]
+pub fn dsl_to_json(input: &str) -> Result<JsValue, JsValue> {
+    to_js(dsl_to_json_value(input))
+}
+
+/// Transforms a JSON model back into the DSL.
+#[wasm_bindgen(js_name = jsonToDsl)]
+pub fn json_to_dsl(input: &str) -> Result<String, JsValue> {
+    json::deserialize(input)
+        .map(|doc| doc.to_string())
+        .map_err(|e| to_js_value(&json_error(&e)))
+}
+
+fn parse_value(input: &str) -> Result<Value, Value> {
+    let mut parser = Parser::new(input);
+    match parser.parse_document() {
+        Ok(doc) => Ok(document(&doc, parser.source_map())),
+        Err(e) => Err(parser_error(input, &e, parser.error_span())),
+    }
+}
+
+fn validate_value(input: &str) -> Value {
+    let analysis = Analysis::new(input);
+    let diagnostics = analysis
+        .diagnostics()
+        .iter()
+        .filter(|d| d.severity != Severity::Allow)
+        .map(|d| {
+            let mut value = located(input, d.span);
+            value["message"] = d.message.clone().into();
+            value["source"] = d.source.clone().into();
+            value["severity"] = match d.severity {
+                Severity::Error => "error",
+                _ => "warning",
+            }
+            .into();
+            value
+        })
+        .collect();
+    Value::Array(diagnostics)
+}
+
+fn dsl_to_json_value(input: &str) -> Result<Value, Value> {
+    let mut parser = Parser::new(input);
+    match parser.parse_document() {
+        Ok(doc) => Ok(
+            serde_json::from_str(&JsonTransformer::new(&doc).serialize()).unwrap_or(Value::Null),
+        ),
+        Err(e) => Err(parser_error(input, &e, parser.error_span())),
+    }
+}
+
+fn document(doc: &Document, source: &SourceMap) -> Value {
+    let types = doc
+        .types
+        .iter()
+        .zip(&source.types)
+        .map(|(ty, ts)| {
+            let relations = ty
+                .relations
+                .iter()
+                .zip(&ts.relations)
+                .map(|(rel, rs)| {
+                    let aliases = rel
+                        .aliases
+                        .iter()
+                        .zip(&rs.aliases)
+                        .map(|(a, s)| alias(a, s));
+                    json!({
+                        "name": rel.kind,
+                        "span": span(rs.span),
+                        "aliases": aliases.collect::<Vec<_>>(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({"name": ty.kind, "span": span(ts.span), "relations": relations})
+        })
+        .collect::<Vec<_>>();
+    json!({ "types": types })
+}
+
+fn alias(alias: &Alias, source: &AliasSource) -> Value {
+    let mut value = match &alias.kind {
+        AliasKind::This => json!({"kind": "self"}),
+        AliasKind::Named(name) => json!({"kind": "relation", "relation": name}),
+        AliasKind::Intersection(name) => json!({"kind": "intersection", "relation": name}),
+        AliasKind::Negative(name) => json!({"kind": "exclusion", "relation": name}),
+    };
+    value["span"] = span(source.span);
+    if let Some(parent) = &alias.parent {
+        value["parent"] = parent.clone().into();
+    }
+    value
+}
+
+fn parser_error(input: &str, error: &ParserError, at: Span) -> Value {
+    let mut value = located(input, at);
+    value["kind"] = "parse".into();
+    value["message"] = error.to_string().into();
+    value
+}
+
+fn json_error(error: &JsonError) -> Value {
+    let mut value = json!({"kind": "json", "message": error.to_string()});
+    match error {
+        JsonError::Syntax { line, column, .. } => {
+            value["line"] = (*line).into();
+            value["column"] = (*column).into();
+        }
+        JsonError::Unsupported { path, .. } => value["path"] = path.clone().into(),
+    }
+    value
+}
+
+/// An object with the span and the one-based position of its start.
+fn located(input: &str, at: Span) -> Value {
+    let (line, column) = at.line_col(input);
+    json!({"span": span(at), "line": line + 1, "column": column + 1})
+}
+
+fn span(span: Span) -> Value {
+    json!({"start": span.start, "end": span.end})
+}
+
+fn to_js<T: Serialize>(result: Result<T, Value>) -> Result<JsValue, JsValue> {
+    match result {
+        Ok(value) => Ok(to_js_value(&value)),
+        Err(error) => Err(to_js_value(&error)),
+    }
+}
+
+fn to_js_value<T: Serialize>(value: &T) -> JsValue {
+    // plain objects rather than `Map`s
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    value.serialize(&serializer).unwrap_or(JsValue::NULL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reports_spans() {
+        let value = parse_value(
+            "type user\ntype doc\n  relations\n    define viewer as self or owner from parent",
+        )
+        .unwrap();
+        assert_eq!(value["types"][1]["name"], "doc");
+        assert_eq!(
+            value["types"][1]["relations"][0]["aliases"][1],
+            json!({
+                "kind": "relation",
+                "relation": "owner",
+                "parent": "parent",
+                "span": {"start": 60, "end": 77},
+            })
+        );
+
+        let error = parse_value("type doc\n  relations\n    define viewer as").unwrap_err();
+        assert_eq!(error["kind"], "parse");
+        assert_eq!(
+            (error["line"].clone(), error["column"].clone()),
+            (json!(3), json!(21))
+        );
+    }
+
+    #[test]
+    fn validate_reports_diagnostics() {
+        assert_eq!(validate_value("type user\n"), json!([]));
+        let value =
+            validate_value("type doc\n  relations\n    define viewer as viewer from parent");
+        assert_eq!(value[0]["severity"], "error");
+        assert_eq!(value[0]["source"], "validate");
+        assert_eq!(value[0]["line"], 3);
+    }
+
+    #[test]
+    fn transforms_both_ways() {
+        let input = "type doc\n  relations\n    define viewer as self\n";
+        let value = dsl_to_json_value(input).unwrap();
+        assert_eq!(
+            value["type_definitions"][0]["relations"]["viewer"],
+            json!({"this": {}})
+        );
+        let dsl = json::deserialize(&value.to_string()).unwrap().to_string();
+        assert_eq!(dsl, input);
+
+        let error = json_error(&json::deserialize("[").unwrap_err());
+        assert_eq!(error["kind"], "json");
+        assert_eq!(error["line"], 1);
+    }
+}
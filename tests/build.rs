@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::Path;
+
+use openfga_dsl_parser::build::{BuildError, Builder};
+use serde_json::Value;
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+}
+
+fn out_dir(name: &str) -> std::path::PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn compiles_models() {
+    let out = out_dir("build-models");
+    let compiled = Builder::new(fixture("models"))
+        .with_out_dir(&out)
+        .compile()
+        .unwrap();
+    let written: Vec<_> = compiled.iter().map(|c| c.json.clone()).collect();
+    assert_eq!(
+        written,
+        vec![out.join("authz.json"), out.join("groups/team.json")]
+    );
+
+    let json: Value = serde_json::from_str(&fs::read_to_string(&written[1]).unwrap()).unwrap();
+    assert_eq!(json["type_definitions"][0]["type"], "team");
+}
+
+#[test]
+fn renders_errors() {
+    let out = out_dir("build-invalid");
+    let err = Builder::new(fixture("invalid_models"))
+        .with_out_dir(&out)
+        .compile()
+        .unwrap_err();
+    let BuildError::Invalid(models) = &err else {
+        panic!("expected invalid models, got {err:?}");
+    };
+    assert_eq!(models.len(), 2);
+    assert!(!out.exists());
+
+    let rendered = err.to_string();
+    let broken = format!(
+        "error: received an unexpected EOF
+ --> {}:3:21
+  |
+3 |     define viewer as
+  |                     ^
+",
+        fixture("invalid_models/broken.fga")
+    );
+    assert!(rendered.starts_with(&broken), "{rendered}");
+    assert!(rendered.contains(
+        "3 |     define viewer as viewer from parent
+  |            ^^^^^^
+"
+    ));
+}
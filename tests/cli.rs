@@ -0,0 +1,144 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use serde_json::Value;
+
+fn fga_dsl(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fga-dsl"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // the process may exit before reading its input, e.g. on bad usage
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+const MODEL: &str = "type user
+type document
+  relations
+    define viewer   as self
+";
+
+#[test]
+fn transforms_to_json() {
+    let output = fga_dsl(&["transform"], MODEL);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["type_definitions"][1]["type"], "document");
+    assert_eq!(
+        json["type_definitions"][1]["relations"]["viewer"],
+        serde_json::json!({"this": {}})
+    );
+}
+
+#[test]
+fn reports_parse_errors_as_json() {
+    let output = fga_dsl(
+        &["transform", "--error-format", "json"],
+        "type document\n  relations\n    define viewer as",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    let error: Value = serde_json::from_str(stderr(&output).trim()).unwrap();
+    assert_eq!(error["file"], Value::Null);
+    assert_eq!(error["line"], 3);
+    assert_eq!(error["column"], 21);
+    assert_eq!(error["severity"], "error");
+}
+
+#[test]
+fn validates_models() {
+    let output = fga_dsl(&["validate"], MODEL);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let invalid = "type document
+  relations
+    define viewer as viewer from parent
+";
+    let output = fga_dsl(&["validate"], invalid);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "<stdin>:3:12: error: document#viewer: tupleset relation 'parent' \
+         is not defined on type 'document'\n"
+    );
+    let invalid = "type user
+condition in_office(ip: ipaddress) {
+  ip.in_cidr(request_cidr)
+}
+";
+    let output = fga_dsl(&["validate"], invalid);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "<stdin>:3:14: error: undeclared reference to 'request_cidr' at position 60\n"
+    );
+}
+
+#[test]
+fn formats_models() {
+    let output = fga_dsl(&["format"], MODEL);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let formatted = stdout(&output);
+    assert!(formatted.contains("    define viewer as self\n"));
+
+    let output = fga_dsl(&["format", "--check"], MODEL);
+    assert_eq!(output.status.code(), Some(1));
+    let output = fga_dsl(&["format", "--check", "-"], &formatted);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = fga_dsl(&["format", "--check"], &formatted.replace('\n', "\r\n"));
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = fga_dsl(&["format", "--check"], formatted.trim_end());
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn refuses_to_format_comments() {
+    let model = "type user
+type document
+  relations
+    # lint:allow(unused-relation)
+    define viewer as self
+condition recent(tag: string) { tag == '#1' }
+";
+    let path = std::env::temp_dir().join(format!("fga-dsl-comments-{}.fga", std::process::id()));
+    std::fs::write(&path, model).unwrap();
+    let file = path.to_str().unwrap();
+
+    let output = fga_dsl(&["format", "--write", file], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        stderr(&output),
+        format!("{file}:4:5: error: cannot format a model with comments, they would be lost\n")
+    );
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), model);
+    std::fs::remove_file(&path).unwrap();
+
+    let output = fga_dsl(
+        &["format"],
+        &model.replace("    # lint:allow(unused-relation)\n", ""),
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn rejects_bad_usage() {
+    let output = fga_dsl(&["lint"], MODEL);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("error: unknown command 'lint'"));
+
+    let output = fga_dsl(&["validate", "does/not/exist.fga"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("error: cannot read does/not/exist.fga"));
+}
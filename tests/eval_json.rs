@@ -0,0 +1,148 @@
+use openfga_dsl_parser::eval::{Checker, TupleStore};
+use openfga_dsl_parser::json::JsonTransformer;
+use openfga_dsl_parser::Parser;
+use serde_json::Value;
+
+const MODEL: &str = "type user
+type group
+  relations
+    define member as self
+type folder
+  relations
+    define blocked as self
+    define owner as self
+    define parent as self
+    define viewer as self or owner or viewer from parent but not blocked
+type document
+  relations
+    define parent as self
+    define blocked as self
+    define editor as self but not blocked
+    define viewer as self or editor or viewer from parent but not blocked from parent
+    define commenter as self and viewer";
+
+const USERS: &[&str] = &[
+    "user:anne",
+    "user:bob",
+    "user:carl",
+    "user:dan",
+    "user:erin",
+    "group:eng#member",
+];
+
+fn store() -> TupleStore {
+    let mut tuples = TupleStore::new();
+    tuples.add("group:eng", "member", "user:anne");
+    tuples.add("group:eng", "member", "user:bob");
+    tuples.add("folder:root", "owner", "user:carl");
+    tuples.add("folder:root", "viewer", "group:eng#member");
+    tuples.add("folder:root", "blocked", "user:bob");
+    tuples.add("folder:x", "parent", "folder:root");
+    tuples.add("folder:x", "blocked", "user:dan");
+    tuples.add("document:1", "parent", "folder:x");
+    tuples.add("document:1", "viewer", "user:dan");
+    tuples.add("document:1", "editor", "user:erin");
+    tuples.add("document:1", "blocked", "user:erin");
+    tuples.add("document:1", "commenter", "user:anne");
+    tuples.add("document:1", "commenter", "user:dan");
+    tuples.add("document:2", "viewer", "user:*");
+    tuples.add("document:2", "blocked", "user:anne");
+    tuples
+}
+
+/// Evaluates the rewrites of the model's JSON the way OpenFGA does.
+struct JsonModel<'a> {
+    types: &'a [Value],
+    tuples: &'a TupleStore,
+}
+
+impl JsonModel<'_> {
+    fn check(&self, object: &str, relation: &str, user: &str) -> bool {
+        let ty = object.split_once(':').unwrap().0;
+        let definition = self
+            .types
+            .iter()
+            .find(|t| t["type"] == ty)
+            .and_then(|t| t["relations"].get(relation));
+        match definition {
+            Some(rewrite) => self.rewrite(rewrite, object, relation, user),
+            None => false,
+        }
+    }
+
+    fn rewrite(&self, rewrite: &Value, object: &str, relation: &str, user: &str) -> bool {
+        let (key, value) = rewrite.as_object().unwrap().iter().next().unwrap();
+        let children = || value["child"].as_array().unwrap().iter();
+        match key.as_str() {
+            "this" => self.tuples.users(object, relation).iter().any(|direct| {
+                let user_type = user.split_once(':').unwrap().0;
+                match direct.split_once('#') {
+                    _ if direct == user => true,
+                    Some((set_object, set_relation)) => self.check(set_object, set_relation, user),
+                    None => !user.contains('#') && *direct == format!("{user_type}:*"),
+                }
+            }),
+            "computedUserset" => self.check(object, value["relation"].as_str().unwrap(), user),
+            "tupleToUserset" => {
+                let tupleset = value["tupleset"]["relation"].as_str().unwrap();
+                let computed = value["computedUserset"]["relation"].as_str().unwrap();
+                self.tuples
+                    .users(object, tupleset)
+                    .iter()
+                    .any(|parent| self.check(parent, computed, user))
+            }
+            "union" => children().any(|c| self.rewrite(c, object, relation, user)),
+            "intersection" => children().all(|c| self.rewrite(c, object, relation, user)),
+            "difference" => {
+                self.rewrite(&value["base"], object, relation, user)
+                    && !self.rewrite(&value["subtract"], object, relation, user)
+            }
+            _ => panic!("unknown rewrite {key}"),
+        }
+    }
+}
+
+#[test]
+fn check_agrees_with_json_model() {
+    let doc = Parser::new(MODEL).parse_document().unwrap();
+    let json: Value = serde_json::from_str(&JsonTransformer::new(&doc).serialize()).unwrap();
+    let tuples = store();
+    let model = JsonModel {
+        types: json["type_definitions"].as_array().unwrap(),
+        tuples: &tuples,
+    };
+    let checker = Checker::new(&doc, &tuples);
+
+    let mut objects: Vec<&str> = tuples.iter().map(|(object, _, _)| object).collect();
+    objects.sort();
+    objects.dedup();
+    for object in objects {
+        let ty = doc.get_type(object.split_once(':').unwrap().0).unwrap();
+        for rel in &ty.relations {
+            for user in USERS {
+                assert_eq!(
+                    checker.check(object, &rel.kind, user),
+                    Ok(model.check(object, &rel.kind, user)),
+                    "{object}#{}@{user}",
+                    rel.kind
+                );
+            }
+        }
+    }
+
+    // blocked on the parent folder, so excluded from the document
+    assert_eq!(checker.check("document:1", "viewer", "user:dan"), Ok(false));
+    assert_eq!(
+        checker.check("document:1", "editor", "user:erin"),
+        Ok(false)
+    );
+    assert_eq!(checker.check("document:2", "viewer", "user:anne"), Ok(true));
+    assert_eq!(
+        checker.check("document:1", "commenter", "user:anne"),
+        Ok(true)
+    );
+    assert_eq!(
+        checker.check("document:1", "commenter", "user:dan"),
+        Ok(false)
+    );
+}
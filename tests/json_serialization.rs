@@ -229,10 +229,9 @@ type app
 }"#;
     let exp: Value = serde_json::from_str(exp_raw).unwrap();
 
-    let mut parser = Parser::new(&i);
+    let mut parser = Parser::new(i);
     let doc = parser.parse_document().unwrap();
 
-    let res = json::JsonTransformer::new(&doc)
-        .serialize();
+    let res = json::JsonTransformer::new(&doc).serialize();
     assert_eq!(exp, serde_json::from_str::<Value>(&res).unwrap());
 }
@@ -0,0 +1,43 @@
+//! Runs with `cargo test --features store`.
+#![cfg(feature = "store")]
+
+use std::path::Path;
+
+use openfga_dsl_parser::store::{Location, StoreError, StoreFile};
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn runs_store_file() {
+    let store = StoreFile::load(fixture("docs.fga.yaml")).unwrap();
+    assert_eq!(store.tuples.len(), 5);
+    assert_eq!(store.tests[0].tuples.len(), 1);
+
+    let report = store.run().unwrap();
+    assert!(report.passed(), "{report}");
+    assert_eq!(report.outcomes.len(), 5);
+    assert!(report.to_string().ends_with("5 passed, 0 failed\n"));
+}
+
+#[test]
+fn locates_errors_in_referenced_files() {
+    let input = format!(
+        "model_file: {}\ntuple_file: {}\n",
+        fixture("docs.fga"),
+        fixture("docs.fga")
+    );
+    let err = StoreFile::parse(&input).unwrap_err();
+    let StoreError::InFile(path, err) = err else {
+        panic!("expected an error in the tuple file, got {err:?}");
+    };
+    assert_eq!(path, Path::new(&fixture("docs.fga")));
+    assert!(matches!(
+        *err,
+        StoreError::Tuple {
+            location: Location { line: 1, column: 5 },
+            ..
+        }
+    ));
+}
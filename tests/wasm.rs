@@ -0,0 +1,56 @@
+//! Runs under Node with
+//! `cargo test --target wasm32-unknown-unknown --features wasm --test wasm`
+//! and `wasm-bindgen-test-runner` as the target's runner.
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use openfga_dsl_parser::wasm;
+use serde_json::{json, Value};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const MODEL: &str = "type user
+type document
+  relations
+    define owner as self
+    define viewer as self or owner
+";
+
+fn value(js: JsValue) -> Value {
+    serde_wasm_bindgen::from_value(js).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn parses_with_spans() {
+    let ast = value(wasm::parse(MODEL).unwrap());
+    assert_eq!(ast["types"][1]["relations"][1]["name"], "viewer");
+    assert_eq!(ast["types"][0]["span"], json!({"start": 0, "end": 9}));
+
+    let error = value(wasm::parse("type document\n  relations\n    define viewer as").unwrap_err());
+    assert_eq!(error["kind"], "parse");
+    assert_eq!(error["line"], 3);
+    assert_eq!(error["column"], 21);
+}
+
+#[wasm_bindgen_test]
+fn validates() {
+    assert_eq!(value(wasm::validate(MODEL)), json!([]));
+    let diagnostics = value(wasm::validate(
+        "type document\n  relations\n    define viewer as viewer from parent",
+    ));
+    assert_eq!(diagnostics[0]["severity"], "error");
+    assert_eq!(diagnostics[0]["source"], "validate");
+    assert_eq!(diagnostics[0]["span"]["start"], 37);
+}
+
+#[wasm_bindgen_test]
+fn transforms_both_ways() {
+    let model = value(wasm::dsl_to_json(MODEL).unwrap());
+    assert_eq!(model["type_definitions"][0]["type"], "user");
+
+    let dsl = wasm::json_to_dsl(&model.to_string()).unwrap();
+    assert_eq!(dsl, MODEL);
+
+    let error = value(wasm::json_to_dsl("{\"type_definitions\": 1}").unwrap_err());
+    assert_eq!(error["kind"], "json");
+    assert_eq!(error["path"], "type_definitions");
+}